@@ -0,0 +1,30 @@
+// Output levels of the 2A03 channels, using the nonlinear DAC approximation from the NESdev wiki.
+// Expansion audio from the cartridge is reported in 2A03 pulse volume steps, so every
+// sound chip only has to state how loud it is compared to one APU pulse channel.
+
+pub const EXPANSION_STEP: f32 = 0.00752; // Linear approximation of one pulse volume step
+
+pub fn pulse_out(pulse1: u8, pulse2: u8) -> f32 {
+    let sum = (pulse1 + pulse2) as f32;
+
+    if sum == 0.0 {
+        0.0
+    } else {
+        95.88 / (8128.0 / sum + 100.0)
+    }
+}
+
+pub fn tnd_out(triangle: u8, noise: u8, dmc: u8) -> f32 {
+    let sum = triangle as f32 / 8227.0 + noise as f32 / 12241.0 + dmc as f32 / 22638.0;
+
+    if sum == 0.0 {
+        0.0
+    } else {
+        159.79 / (1.0 / sum + 100.0)
+    }
+}
+
+// Final sample in the 0.0..~1.0 range for the 2A03 levels plus the cartridge expansion audio
+pub fn mix(pulse1: u8, pulse2: u8, triangle: u8, noise: u8, dmc: u8, expansion: f32) -> f32 {
+    pulse_out(pulse1, pulse2) + tnd_out(triangle, noise, dmc) + expansion * EXPANSION_STEP
+}
@@ -0,0 +1,5 @@
+pub mod mixer;
+pub mod namco163;
+pub mod sunsoft5b;
+pub mod vrc6;
+pub mod vrc7;
@@ -0,0 +1,136 @@
+// Namco 163 sound: up to 8 wavetable channels reading 4-bit samples from the chip's
+// 128 bytes of internal RAM. Only one channel is updated every 15 CPU cycles and the
+// DAC outputs that channel alone, so more enabled channels means a lower update rate.
+
+pub const INTERNAL_RAM_SIZE: usize = 128;
+
+const CYCLES_PER_CHANNEL: u8 = 15;
+const CHANNEL_REGISTERS: usize = 0x40;
+const OUTPUT_LEVEL: f32 = 0.125; // Boards use different resistors, this puts a full channel near a 2A03 pulse
+
+#[derive(Debug, Clone)]
+pub struct Namco163Audio {
+    pub ram: [u8; INTERNAL_RAM_SIZE],
+    address: u8, // Bits 0-6 address, bit 7 auto-increment
+    divider: u8,
+    current: usize, // Channel being updated, 7 is the last channel ($78-$7F)
+    disabled: bool,
+    output: f32,
+    dirty: bool, // RAM written through $4800 since the last `take_dirty`
+}
+
+impl Default for Namco163Audio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Namco163Audio {
+    pub fn new() -> Self {
+        Namco163Audio {
+            ram: [0; INTERNAL_RAM_SIZE],
+            address: 0,
+            divider: 0,
+            current: 7,
+            disabled: false,
+            output: 0.0,
+            dirty: false,
+        }
+    }
+
+    // $F800, shared with the mapper's PRG RAM write protect
+    pub fn set_address(&mut self, value: u8) {
+        self.address = value;
+    }
+
+    // $4800 data port, the address auto-increments after each access
+    pub fn read_data(&mut self) -> u8 {
+        let value = self.peek_data();
+        self.increment_address();
+        value
+    }
+
+    pub fn peek_data(&self) -> u8 {
+        self.ram[(self.address & 0x7F) as usize]
+    }
+
+    pub fn write_data(&mut self, value: u8) {
+        let index = (self.address & 0x7F) as usize;
+        self.dirty |= self.ram[index] != value;
+        self.ram[index] = value;
+        self.increment_address();
+    }
+
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+
+    fn increment_address(&mut self) {
+        if self.address & 0x80 != 0 {
+            self.address = 0x80 | (self.address.wrapping_add(1) & 0x7F);
+        }
+    }
+
+    // $E000 bit 6
+    pub fn set_disabled(&mut self, disabled: bool) {
+        self.disabled = disabled;
+    }
+
+    // Number of enabled channels, from bits 4-6 of $7F
+    pub fn enabled_channels(&self) -> usize {
+        ((self.ram[0x7F] >> 4) & 0x07) as usize + 1
+    }
+
+    pub fn clock(&mut self) {
+        if self.disabled {
+            return;
+        }
+
+        self.divider += 1;
+        if self.divider < CYCLES_PER_CHANNEL {
+            return;
+        }
+        self.divider = 0;
+
+        self.output = self.update_channel(self.current) as f32 * OUTPUT_LEVEL;
+
+        // Channels are serviced from 7 downwards, wrapping after the last enabled one
+        let first = 8 - self.enabled_channels();
+        self.current = if self.current <= first {
+            7
+        } else {
+            self.current - 1
+        };
+    }
+
+    fn update_channel(&mut self, channel: usize) -> i16 {
+        let base = CHANNEL_REGISTERS + channel * 8;
+        let registers = &mut self.ram[base..base + 8];
+
+        let frequency = u32::from_le_bytes([registers[0], registers[2], registers[4] & 0x03, 0]);
+        let phase = u32::from_le_bytes([registers[1], registers[3], registers[5], 0]);
+        let length = 256 - (registers[4] & 0xFC) as u32;
+        let phase = (phase + frequency) % (length << 16);
+
+        let [phase_low, phase_mid, phase_high, _] = phase.to_le_bytes();
+        registers[1] = phase_low;
+        registers[3] = phase_mid;
+        registers[5] = phase_high;
+
+        let sample_address = ((phase >> 16) + registers[6] as u32) & 0xFF;
+        let volume = (registers[7] & 0x0F) as i16;
+        let byte = self.ram[(sample_address >> 1) as usize];
+        let sample = if sample_address & 0x01 == 0 {
+            byte & 0x0F
+        } else {
+            byte >> 4
+        };
+
+        (sample as i16 - 8) * volume
+    }
+
+    // In 2A03 pulse volume steps, centered around zero
+    pub fn output(&self) -> f32 {
+        self.output
+    }
+}
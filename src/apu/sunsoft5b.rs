@@ -0,0 +1,215 @@
+// Sunsoft 5B sound: a YM2149F (AY-3-8910 family) with three square wave tone channels,
+// a shared noise generator and a shared envelope generator.
+// The chip divides the CPU clock by 16 before it reaches the tone, noise and envelope counters.
+
+pub const CHANNELS: usize = 3;
+
+const PRESCALER: u8 = 16;
+pub const FULL_VOLUME_LEVEL: f32 = 30.0; // Full volume channel mixes at about twice a full 2A03 pulse
+
+#[derive(Debug, Default, Clone)]
+struct Tone {
+    period: u16,
+    counter: u16,
+    output: bool,
+}
+
+impl Tone {
+    fn clock(&mut self) {
+        self.counter += 1;
+        if self.counter >= self.period.max(1) {
+            self.counter = 0;
+            self.output = !self.output;
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Sunsoft5bAudio {
+    registers: [u8; 16],
+    address: u8,
+    tones: [Tone; CHANNELS],
+    prescaler: u8,
+    noise_counter: u8,
+    noise_toggle: bool,
+    noise_shift: u32,
+    envelope_counter: u16,
+    envelope_step: u8,
+    envelope_attack: bool,
+    envelope_holding: bool,
+    output: f32,
+}
+
+impl Default for Sunsoft5bAudio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sunsoft5bAudio {
+    pub fn new() -> Self {
+        Sunsoft5bAudio {
+            registers: [0; 16],
+            address: 0,
+            tones: Default::default(),
+            prescaler: 0,
+            noise_counter: 0,
+            noise_toggle: false,
+            noise_shift: 1,
+            envelope_counter: 0,
+            envelope_step: 0,
+            envelope_attack: false,
+            envelope_holding: false,
+            output: 0.0,
+        }
+    }
+
+    // $C000-$DFFF
+    pub fn write_address(&mut self, value: u8) {
+        self.address = value;
+    }
+
+    // $E000-$FFFF, addresses with the upper nibble set are ignored
+    pub fn write_data(&mut self, value: u8) {
+        if self.address > 0x0F {
+            return;
+        }
+
+        self.registers[self.address as usize] = value;
+
+        match self.address {
+            0x00..=0x05 => {
+                let channel = (self.address >> 1) as usize;
+                let low = self.registers[channel * 2] as u16;
+                let high = (self.registers[channel * 2 + 1] & 0x0F) as u16;
+                self.tones[channel].period = (high << 8) | low;
+            }
+            0x0D => {
+                self.envelope_counter = 0;
+                self.envelope_step = 0;
+                self.envelope_attack = value & 0x04 != 0;
+                self.envelope_holding = false;
+            }
+            _ => {}
+        }
+    }
+
+    fn noise_period(&self) -> u8 {
+        (self.registers[0x06] & 0x1F).max(1)
+    }
+
+    fn envelope_period(&self) -> u16 {
+        u16::from_le_bytes([self.registers[0x0B], self.registers[0x0C]]).max(1)
+    }
+
+    // 17-bit LFSR, clocked at half the tone rate
+    fn clock_noise(&mut self) {
+        self.noise_toggle = !self.noise_toggle;
+        if !self.noise_toggle {
+            return;
+        }
+
+        self.noise_counter += 1;
+        if self.noise_counter >= self.noise_period() {
+            self.noise_counter = 0;
+            let feedback = (self.noise_shift ^ (self.noise_shift >> 3)) & 0x01;
+            self.noise_shift = (self.noise_shift >> 1) | (feedback << 16);
+        }
+    }
+
+    // 32 steps per envelope cycle
+    fn clock_envelope(&mut self) {
+        if self.envelope_holding {
+            return;
+        }
+
+        self.envelope_counter += 1;
+        if self.envelope_counter < self.envelope_period() {
+            return;
+        }
+        self.envelope_counter = 0;
+
+        if self.envelope_step < 31 {
+            self.envelope_step += 1;
+            return;
+        }
+
+        // End of a cycle: CONT (bit 3) and HOLD (bit 0) decide whether the envelope repeats
+        let shape = self.registers[0x0D];
+        if shape & 0x08 == 0 || shape & 0x01 != 0 {
+            self.envelope_holding = true;
+        } else {
+            self.envelope_step = 0;
+            if shape & 0x02 != 0 {
+                self.envelope_attack = !self.envelope_attack; // ALT flips the direction every cycle
+            }
+        }
+    }
+
+    // Current envelope level in 1.5 dB steps, 0..=31
+    fn envelope_level(&self) -> u8 {
+        let shape = self.registers[0x0D];
+
+        if self.envelope_holding {
+            // Without CONT the envelope ends silent, HOLD keeps the last level (inverted by ALT)
+            if shape & 0x08 == 0 {
+                return 0;
+            }
+            let last = if self.envelope_attack { 31 } else { 0 };
+            return if shape & 0x02 != 0 { 31 - last } else { last };
+        }
+
+        if self.envelope_attack {
+            self.envelope_step
+        } else {
+            31 - self.envelope_step
+        }
+    }
+
+    pub fn clock(&mut self) {
+        self.prescaler += 1;
+        if self.prescaler < PRESCALER {
+            return;
+        }
+        self.prescaler = 0;
+
+        self.tones.iter_mut().for_each(Tone::clock);
+        self.clock_noise();
+        self.clock_envelope();
+        self.output = self.mix();
+    }
+
+    fn mix(&self) -> f32 {
+        let mixer = self.registers[0x07];
+        let noise = self.noise_shift & 0x01 != 0;
+
+        (0..CHANNELS)
+            .map(|channel| {
+                let tone_enabled = mixer & (0x01 << channel) == 0;
+                let noise_enabled = mixer & (0x08 << channel) == 0;
+                let high =
+                    (self.tones[channel].output || !tone_enabled) && (noise || !noise_enabled);
+
+                let volume = self.registers[0x08 + channel];
+                let level = if volume & 0x10 != 0 {
+                    self.envelope_level()
+                } else if volume & 0x0F == 0 {
+                    0
+                } else {
+                    (volume & 0x0F) * 2 + 1
+                };
+
+                if high && level > 0 {
+                    FULL_VOLUME_LEVEL * 10f32.powf((level as f32 - 31.0) * 1.5 / 20.0)
+                } else {
+                    0.0
+                }
+            })
+            .sum()
+    }
+
+    // In 2A03 pulse volume steps
+    pub fn output(&self) -> f32 {
+        self.output
+    }
+}
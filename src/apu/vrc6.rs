@@ -0,0 +1,160 @@
+// Konami VRC6 sound: two pulse channels with eight duty settings and a sawtooth channel.
+// The pulses use a 4-bit volume and the sawtooth the top 5 bits of its accumulator, all
+// on the same scale as the 2A03 pulses, so the output is already in pulse volume steps.
+
+#[derive(Debug, Default, Clone)]
+pub struct Vrc6Pulse {
+    volume: u8,
+    duty: u8,
+    ignore_duty: bool,
+    period: u16,
+    enabled: bool,
+    timer: u16,
+    step: u8,
+}
+
+impl Vrc6Pulse {
+    // $9000/$A000: MDDD VVVV
+    pub fn write_control(&mut self, value: u8) {
+        self.ignore_duty = value & 0x80 != 0;
+        self.duty = (value >> 4) & 0x07;
+        self.volume = value & 0x0F;
+    }
+
+    pub fn write_period_low(&mut self, value: u8) {
+        self.period = (self.period & 0x0F00) | value as u16;
+    }
+
+    // $9002/$A002: E... PPPP, disabling the channel restarts the duty cycle
+    pub fn write_period_high(&mut self, value: u8) {
+        self.period = (self.period & 0x00FF) | (((value & 0x0F) as u16) << 8);
+        self.enabled = value & 0x80 != 0;
+
+        if !self.enabled {
+            self.step = 15;
+        }
+    }
+
+    fn clock(&mut self, shift: u8) {
+        if !self.enabled {
+            return;
+        }
+
+        if self.timer == 0 {
+            self.timer = self.period >> shift;
+            self.step = self.step.wrapping_sub(1) & 0x0F;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.enabled && (self.ignore_duty || self.step <= self.duty) {
+            self.volume
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Vrc6Sawtooth {
+    rate: u8,
+    period: u16,
+    enabled: bool,
+    timer: u16,
+    step: u8,
+    accumulator: u8,
+}
+
+impl Vrc6Sawtooth {
+    // $B000: ..AA AAAA
+    pub fn write_rate(&mut self, value: u8) {
+        self.rate = value & 0x3F;
+    }
+
+    pub fn write_period_low(&mut self, value: u8) {
+        self.period = (self.period & 0x0F00) | value as u16;
+    }
+
+    pub fn write_period_high(&mut self, value: u8) {
+        self.period = (self.period & 0x00FF) | (((value & 0x0F) as u16) << 8);
+        self.enabled = value & 0x80 != 0;
+
+        if !self.enabled {
+            self.step = 0;
+            self.accumulator = 0;
+        }
+    }
+
+    // The rate is added on every second clock and the accumulator restarts after 14 clocks
+    fn clock(&mut self, shift: u8) {
+        if !self.enabled {
+            return;
+        }
+
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+
+        self.timer = self.period >> shift;
+        self.step = (self.step + 1) % 14;
+
+        if self.step == 0 {
+            self.accumulator = 0;
+        } else if self.step & 0x01 == 0 {
+            self.accumulator = self.accumulator.wrapping_add(self.rate);
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.enabled {
+            self.accumulator >> 3
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Vrc6Audio {
+    pub pulse1: Vrc6Pulse,
+    pub pulse2: Vrc6Pulse,
+    pub sawtooth: Vrc6Sawtooth,
+    halt: bool,
+    shift: u8,
+}
+
+impl Vrc6Audio {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // $9003: bit 0 halts every oscillator, bit 1 and bit 2 speed them up 16x and 256x
+    pub fn write_frequency_control(&mut self, value: u8) {
+        self.halt = value & 0x01 != 0;
+        self.shift = if value & 0x02 != 0 {
+            4
+        } else if value & 0x04 != 0 {
+            8
+        } else {
+            0
+        };
+    }
+
+    pub fn clock(&mut self) {
+        if self.halt {
+            return;
+        }
+
+        self.pulse1.clock(self.shift);
+        self.pulse2.clock(self.shift);
+        self.sawtooth.clock(self.shift);
+    }
+
+    // 0..=61, in 2A03 pulse volume steps
+    pub fn output(&self) -> f32 {
+        (self.pulse1.output() + self.pulse2.output() + self.sawtooth.output()) as f32
+    }
+}
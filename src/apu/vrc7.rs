@@ -0,0 +1,403 @@
+// Konami VRC7 sound: a 6-channel cut down YM2413 (OPLL).
+// Every channel is a modulator and a carrier operator playing one of 15 built-in
+// instruments or the custom instrument programmed in registers $00-$07.
+// The chip produces a new sample every 36 CPU cycles (3.58 MHz / 72).
+
+use std::f32::consts::PI;
+
+pub const CHANNELS: usize = 6;
+
+const CYCLES_PER_SAMPLE: u8 = 36;
+const SAMPLE_RATE: f32 = 49716.0;
+const PHASE_BITS: u32 = 19;
+const ENVELOPE_MAX: f32 = 127.0; // Attenuation in 0.375 dB steps, 127 is silence
+const AM_DEPTH: f32 = 12.8; // 4.8 dB
+const AM_FREQUENCY: f32 = 3.7;
+const VIBRATO_DEPTH: f32 = 7.0 / 1200.0; // +-7 cents
+const VIBRATO_FREQUENCY: f32 = 6.4;
+const CHANNEL_LEVEL: f32 = 15.0; // A channel at full level peaks like a full volume 2A03 pulse
+
+// Built-in instruments 1-15, instrument 0 is the custom patch
+const PATCHES: [[u8; 8]; 15] = [
+    [0x03, 0x21, 0x05, 0x06, 0xE8, 0x81, 0x42, 0x27], // Buzzy bell
+    [0x13, 0x41, 0x14, 0x0D, 0xD8, 0xF6, 0x23, 0x12], // Guitar
+    [0x11, 0x11, 0x08, 0x08, 0xFA, 0xB2, 0x20, 0x12], // Wurly
+    [0x31, 0x61, 0x0C, 0x07, 0xA8, 0x64, 0x61, 0x27], // Flute
+    [0x32, 0x21, 0x1E, 0x06, 0xE1, 0x76, 0x01, 0x28], // Clarinet
+    [0x02, 0x01, 0x06, 0x00, 0xA3, 0xE2, 0xF4, 0xF4], // Synth
+    [0x21, 0x61, 0x1D, 0x07, 0x82, 0x81, 0x11, 0x07], // Trumpet
+    [0x23, 0x21, 0x22, 0x17, 0xA2, 0x72, 0x01, 0x17], // Organ
+    [0x35, 0x11, 0x25, 0x00, 0x40, 0x73, 0x72, 0x01], // Bells
+    [0xB5, 0x01, 0x0F, 0x0F, 0xA8, 0xA5, 0x51, 0x02], // Vibes
+    [0x17, 0xC1, 0x24, 0x07, 0xF8, 0xF8, 0x22, 0x12], // Vibraphone
+    [0x71, 0x23, 0x11, 0x06, 0x65, 0x74, 0x18, 0x16], // Tutti
+    [0x01, 0x02, 0xD3, 0x05, 0xC9, 0x95, 0x03, 0x02], // Fretless
+    [0x61, 0x63, 0x0C, 0x00, 0x94, 0xC0, 0x33, 0xF6], // Synth bass
+    [0x21, 0x72, 0x0D, 0x00, 0xC1, 0xD5, 0x56, 0x06], // Sweep
+];
+
+// Frequency multipliers, doubled so that the 1/2 multiplier stays an integer
+const MULTIPLIERS: [u32; 16] = [1, 2, 4, 6, 8, 10, 12, 14, 16, 18, 20, 20, 24, 24, 30, 30];
+
+// Key scale level attenuation for 3 dB/octave in dB, indexed by the top 4 bits of the F-number
+const KSL_TABLE: [f32; 16] = [
+    0.0, 9.0, 12.0, 13.875, 15.0, 16.125, 16.875, 17.625, 18.0, 18.75, 19.125, 19.5, 19.875, 20.25,
+    20.625, 21.0,
+];
+
+#[derive(Debug, Clone, Copy, Default)]
+struct OperatorPatch {
+    am: bool,
+    vibrato: bool,
+    sustained: bool,
+    key_scale_rate: bool,
+    multiplier: u8,
+    key_scale_level: u8,
+    rectified: bool,
+    attack: u8,
+    decay: u8,
+    sustain_level: u8,
+    release: u8,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Patch {
+    operators: [OperatorPatch; 2], // Modulator, carrier
+    total_level: u8,
+    feedback: u8,
+}
+
+impl Patch {
+    fn decode(bytes: &[u8; 8]) -> Self {
+        let operator = |index: usize| OperatorPatch {
+            am: bytes[index] & 0x80 != 0,
+            vibrato: bytes[index] & 0x40 != 0,
+            sustained: bytes[index] & 0x20 != 0,
+            key_scale_rate: bytes[index] & 0x10 != 0,
+            multiplier: bytes[index] & 0x0F,
+            key_scale_level: bytes[2 + index] >> 6,
+            rectified: bytes[3] & (0x08 << index) != 0,
+            attack: bytes[4 + index] >> 4,
+            decay: bytes[4 + index] & 0x0F,
+            sustain_level: bytes[6 + index] >> 4,
+            release: bytes[6 + index] & 0x0F,
+        };
+
+        Patch {
+            operators: [operator(0), operator(1)],
+            total_level: bytes[2] & 0x3F,
+            feedback: bytes[3] & 0x07,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum EnvelopeStage {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    #[default]
+    Off,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Operator {
+    phase: u32,
+    envelope: f32,
+    stage: EnvelopeStage,
+    output: [f32; 2], // Last two outputs, used for the modulator feedback
+}
+
+impl Default for Operator {
+    fn default() -> Self {
+        Operator {
+            phase: 0,
+            envelope: ENVELOPE_MAX,
+            stage: EnvelopeStage::Off,
+            output: [0.0; 2],
+        }
+    }
+}
+
+// Per-sample attenuation change for a 4-bit rate, scaled by the key scale
+fn rate_step(rate: u8, key_scale: u8) -> f32 {
+    if rate == 0 {
+        return 0.0;
+    }
+
+    let rate = (rate * 4 + key_scale).min(63);
+    let (shift, fraction) = ((rate >> 2) as i32, (rate & 0x03) as f32);
+
+    (4.0 + fraction) / 4.0 * 2f32.powi(shift - 14)
+}
+
+impl Operator {
+    fn key_on(&mut self) {
+        self.phase = 0;
+        self.stage = EnvelopeStage::Attack;
+    }
+
+    fn key_off(&mut self) {
+        if self.stage != EnvelopeStage::Off {
+            self.stage = EnvelopeStage::Release;
+        }
+    }
+
+    fn step_envelope(&mut self, patch: &OperatorPatch, key_scale: u8, channel_sustain: bool) {
+        let key_scale = if patch.key_scale_rate {
+            key_scale
+        } else {
+            key_scale >> 2
+        };
+
+        match self.stage {
+            EnvelopeStage::Attack => {
+                let step = rate_step(patch.attack, key_scale);
+                if patch.attack == 15 || step >= 2.0 {
+                    self.envelope = 0.0;
+                } else {
+                    self.envelope -= (self.envelope + 1.0) * step / 2.0;
+                }
+
+                if self.envelope <= 0.0 {
+                    self.envelope = 0.0;
+                    self.stage = EnvelopeStage::Decay;
+                }
+            }
+            EnvelopeStage::Decay => {
+                let sustain_level = patch.sustain_level as f32 * 8.0; // 3 dB steps
+                self.envelope += rate_step(patch.decay, key_scale);
+
+                if self.envelope >= sustain_level {
+                    self.envelope = sustain_level;
+                    self.stage = EnvelopeStage::Sustain;
+                }
+            }
+            // Sustained instruments hold, percussive ones keep fading at the release rate
+            EnvelopeStage::Sustain if !patch.sustained => {
+                self.envelope += rate_step(patch.release, key_scale);
+            }
+            EnvelopeStage::Sustain | EnvelopeStage::Off => {}
+            EnvelopeStage::Release => {
+                let rate = if channel_sustain {
+                    5
+                } else if patch.sustained {
+                    patch.release
+                } else {
+                    7
+                };
+                self.envelope += rate_step(rate, key_scale);
+            }
+        }
+
+        if self.envelope >= ENVELOPE_MAX {
+            self.envelope = ENVELOPE_MAX;
+            if self.stage != EnvelopeStage::Attack {
+                self.stage = EnvelopeStage::Off;
+            }
+        }
+    }
+
+    // `modulation` is the phase offset in cycles, `attenuation` in 0.375 dB steps
+    fn render(&mut self, patch: &OperatorPatch, modulation: f32, attenuation: f32) -> f32 {
+        let total = self.envelope + attenuation;
+        let phase = self.phase as f32 / (1 << PHASE_BITS) as f32 + modulation;
+        let wave = (2.0 * PI * phase).sin();
+
+        let output = if total >= ENVELOPE_MAX || (patch.rectified && wave < 0.0) {
+            0.0
+        } else {
+            wave * 10f32.powf(-total * 0.375 / 20.0)
+        };
+
+        self.output = [output, self.output[0]];
+        output
+    }
+
+    fn advance_phase(&mut self, patch: &OperatorPatch, fnum: u16, block: u8, vibrato: f32) {
+        let increment = (((fnum as u32) << block) * MULTIPLIERS[patch.multiplier as usize]) >> 1;
+        let increment = if patch.vibrato {
+            (increment as f32 * vibrato) as u32
+        } else {
+            increment
+        };
+
+        self.phase = (self.phase + increment) & ((1 << PHASE_BITS) - 1);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Channel {
+    fnum: u16,
+    block: u8,
+    key_on: bool,
+    sustain: bool,
+    instrument: u8,
+    volume: u8,
+    operators: [Operator; 2],
+}
+
+impl Channel {
+    // Key scale used by the envelope rates: octave and the top F-number bit
+    fn key_scale(&self) -> u8 {
+        (self.block << 1) | (self.fnum >> 8) as u8
+    }
+
+    // Key scale level attenuation in 0.375 dB steps
+    fn key_scale_attenuation(&self, key_scale_level: u8) -> f32 {
+        if key_scale_level == 0 {
+            return 0.0;
+        }
+
+        let db = (KSL_TABLE[(self.fnum >> 5) as usize] - 3.0 * (7 - self.block) as f32).max(0.0);
+        let per_octave = [0.0, 0.5, 1.0, 2.0][key_scale_level as usize];
+
+        db * per_octave / 0.375
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Vrc7Audio {
+    custom_patch: [u8; 8],
+    channels: [Channel; CHANNELS],
+    address: u8,
+    divider: u8,
+    am_phase: f32,
+    vibrato_phase: f32,
+    silenced: bool,
+    output: f32,
+}
+
+impl Default for Vrc7Audio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vrc7Audio {
+    pub fn new() -> Self {
+        Vrc7Audio {
+            custom_patch: [0; 8],
+            channels: [Channel::default(); CHANNELS],
+            address: 0,
+            divider: 0,
+            am_phase: 0.0,
+            vibrato_phase: 0.0,
+            silenced: false,
+            output: 0.0,
+        }
+    }
+
+    // $9010
+    pub fn write_address(&mut self, value: u8) {
+        self.address = value;
+    }
+
+    // $9030
+    pub fn write_data(&mut self, value: u8) {
+        match self.address {
+            0x00..=0x07 => self.custom_patch[self.address as usize] = value,
+            0x10..=0x15 => {
+                let channel = &mut self.channels[(self.address & 0x0F) as usize];
+                channel.fnum = (channel.fnum & 0x100) | value as u16;
+            }
+            0x20..=0x25 => {
+                let channel = &mut self.channels[(self.address & 0x0F) as usize];
+                let key_on = value & 0x10 != 0;
+
+                channel.fnum = (channel.fnum & 0xFF) | (((value & 0x01) as u16) << 8);
+                channel.block = (value >> 1) & 0x07;
+                channel.sustain = value & 0x20 != 0;
+
+                if key_on && !channel.key_on {
+                    channel.operators.iter_mut().for_each(Operator::key_on);
+                } else if !key_on && channel.key_on {
+                    channel.operators.iter_mut().for_each(Operator::key_off);
+                }
+                channel.key_on = key_on;
+            }
+            0x30..=0x35 => {
+                let channel = &mut self.channels[(self.address & 0x0F) as usize];
+                channel.instrument = value >> 4;
+                channel.volume = value & 0x0F;
+            }
+            _ => {}
+        }
+    }
+
+    // $E000 bit 6 holds the sound chip in reset
+    pub fn set_silenced(&mut self, silenced: bool) {
+        if silenced && !self.silenced {
+            self.channels = [Channel::default(); CHANNELS];
+            self.output = 0.0;
+        }
+        self.silenced = silenced;
+    }
+
+    fn patch(&self, instrument: u8) -> Patch {
+        match instrument {
+            0 => Patch::decode(&self.custom_patch),
+            _ => Patch::decode(&PATCHES[instrument as usize - 1]),
+        }
+    }
+
+    pub fn clock(&mut self) {
+        if self.silenced {
+            return;
+        }
+
+        self.divider += 1;
+        if self.divider == CYCLES_PER_SAMPLE {
+            self.divider = 0;
+            self.output = self.render_sample();
+        }
+    }
+
+    fn render_sample(&mut self) -> f32 {
+        self.am_phase = (self.am_phase + AM_FREQUENCY / SAMPLE_RATE).fract();
+        self.vibrato_phase = (self.vibrato_phase + VIBRATO_FREQUENCY / SAMPLE_RATE).fract();
+
+        let am = AM_DEPTH * (1.0 - (2.0 * self.am_phase - 1.0).abs());
+        let vibrato = 2f32.powf(VIBRATO_DEPTH * (2.0 * PI * self.vibrato_phase).sin());
+
+        let mut mix = 0.0;
+        for index in 0..CHANNELS {
+            let patch = self.patch(self.channels[index].instrument);
+            let channel = &mut self.channels[index];
+            let key_scale = channel.key_scale();
+
+            for (operator, operator_patch) in channel.operators.iter_mut().zip(patch.operators) {
+                operator.step_envelope(&operator_patch, key_scale, channel.sustain);
+                operator.advance_phase(&operator_patch, channel.fnum, channel.block, vibrato);
+            }
+
+            let [modulator_patch, carrier_patch] = patch.operators;
+            let am_for = |operator: &OperatorPatch| if operator.am { am } else { 0.0 };
+            let modulator_attenuation = patch.total_level as f32 * 2.0
+                + channel.key_scale_attenuation(modulator_patch.key_scale_level)
+                + am_for(&modulator_patch);
+            let carrier_attenuation = channel.volume as f32 * 8.0
+                + channel.key_scale_attenuation(carrier_patch.key_scale_level)
+                + am_for(&carrier_patch);
+
+            // Feedback swings the modulator by up to 4 pi using the average of its last two outputs
+            let [modulator, carrier] = &mut channel.operators;
+            let feedback = match patch.feedback {
+                0 => 0.0,
+                shift => (modulator.output[0] + modulator.output[1]) / (1 << (7 - shift)) as f32,
+            };
+            let modulation = modulator.render(&modulator_patch, feedback, modulator_attenuation);
+
+            // A full level modulator swings the carrier by 8 pi
+            mix += carrier.render(&carrier_patch, modulation * 4.0, carrier_attenuation);
+        }
+
+        mix
+    }
+
+    // In 2A03 pulse volume steps, centered around zero
+    pub fn output(&self) -> f32 {
+        self.output * CHANNEL_LEVEL
+    }
+}
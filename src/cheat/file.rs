@@ -0,0 +1,245 @@
+// Cheat files. Both formats use the `.cht` extension:
+//
+// FCEUX: one patch per line, `[S][C][:]AAAA:VV[:CC]:Name` in hex. `S` makes it a substitution
+// instead of a freeze, `C` adds the compare value and a `:` right before the address marks the
+// cheat as disabled.
+//
+// libretro: `cheats = N` then `cheatI_desc`, `cheatI_code` and `cheatI_enable` keys per cheat,
+// several codes in one cheat joined with `+`. Other keys are ignored.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use super::{Cheat, Cheats, Patch, PatchKind};
+use crate::sys::errors::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatFormat {
+    Fceux,
+    Libretro,
+}
+
+impl CheatFormat {
+    // libretro files always start with the cheat count
+    pub fn detect(text: &str) -> Self {
+        let first = text.lines().map(str::trim).find(|line| !line.is_empty());
+        match first.and_then(|line| line.split_once('=')) {
+            Some((key, _)) if key.trim() == "cheats" => CheatFormat::Libretro,
+            _ => CheatFormat::Fceux,
+        }
+    }
+}
+
+pub fn parse(text: &str, format: CheatFormat) -> Result<Cheats, Error> {
+    match format {
+        CheatFormat::Fceux => parse_fceux(text),
+        CheatFormat::Libretro => parse_libretro(text),
+    }
+}
+
+pub fn write(cheats: &Cheats, format: CheatFormat) -> String {
+    match format {
+        CheatFormat::Fceux => write_fceux(cheats),
+        CheatFormat::Libretro => write_libretro(cheats),
+    }
+}
+
+pub fn read_cheat_file<P: AsRef<Path>>(path: P) -> Result<Cheats, Error> {
+    let text = fs::read_to_string(path).map_err(|_| Error::ErrorReadingCheatFile)?;
+    parse(&text, CheatFormat::detect(&text))
+}
+
+pub fn write_cheat_file<P: AsRef<Path>>(
+    path: P,
+    cheats: &Cheats,
+    format: CheatFormat,
+) -> Result<(), Error> {
+    fs::write(path, write(cheats, format)).map_err(|_| Error::ErrorWritingCheatFile)
+}
+
+pub fn parse_fceux(text: &str) -> Result<Cheats, Error> {
+    let hex =
+        |digits: &str| u16::from_str_radix(digits, 16).map_err(|_| Error::ErrorInvalidCheatCode);
+    let byte =
+        |digits: &str| u8::from_str_radix(digits, 16).map_err(|_| Error::ErrorInvalidCheatCode);
+
+    let mut cheats = Cheats::default();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let substitute = line.starts_with('S');
+        let line = line.strip_prefix('S').unwrap_or(line);
+        let compare = line.starts_with('C');
+        let line = line.strip_prefix('C').unwrap_or(line);
+        let enabled = !line.starts_with(':');
+        let line = line.strip_prefix(':').unwrap_or(line);
+
+        let fields: Vec<&str> = line.splitn(if compare { 4 } else { 3 }, ':').collect();
+        let (address, value) = match fields[..] {
+            [address, value, ..] => (hex(address)?, byte(value)?),
+            _ => return Err(Error::ErrorInvalidCheatCode),
+        };
+        let compare = match compare {
+            true => Some(byte(fields.get(2).ok_or(Error::ErrorInvalidCheatCode)?)?),
+            false => None,
+        };
+        let name = fields.get(if compare.is_some() { 3 } else { 2 });
+
+        let patch = match substitute {
+            true => Patch::substitute(address, value, compare),
+            false => Patch::freeze(address, value),
+        };
+        cheats.add(Cheat {
+            name: name.map_or("", |name| name.trim_end()).to_string(),
+            patches: vec![patch],
+            enabled,
+        });
+    }
+    Ok(cheats)
+}
+
+// A cheat with several patches takes one line each
+pub fn write_fceux(cheats: &Cheats) -> String {
+    let mut text = String::new();
+    for cheat in cheats.iter() {
+        for patch in &cheat.patches {
+            if patch.kind == PatchKind::Substitute {
+                text.push('S');
+            }
+            if patch.compare.is_some() {
+                text.push('C');
+            }
+            if !cheat.enabled {
+                text.push(':');
+            }
+
+            text += &format!("{:04x}:{:02x}:", patch.address, patch.value);
+            if let Some(compare) = patch.compare {
+                text += &format!("{:02x}:", compare);
+            }
+            text += &cheat.name;
+            text.push('\n');
+        }
+    }
+    text
+}
+
+pub fn parse_libretro(text: &str) -> Result<Cheats, Error> {
+    let mut entries: BTreeMap<usize, (String, String, bool)> = BTreeMap::new();
+
+    for line in text.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = value.trim().trim_matches('"');
+        let Some((index, field)) = key
+            .trim()
+            .strip_prefix("cheat")
+            .and_then(|key| key.split_once('_'))
+        else {
+            continue;
+        };
+        let Ok(index) = index.parse() else {
+            continue;
+        };
+
+        let entry = entries.entry(index).or_default();
+        match field {
+            "desc" => entry.0 = value.to_string(),
+            "code" => entry.1 = value.to_string(),
+            "enable" => entry.2 = value == "true",
+            _ => {}
+        }
+    }
+
+    entries
+        .into_values()
+        .map(|(name, code, enabled)| {
+            let mut cheat = Cheat::new(&name, &code)?;
+            cheat.enabled = enabled;
+            Ok(cheat)
+        })
+        .collect()
+}
+
+pub fn write_libretro(cheats: &Cheats) -> String {
+    let mut text = format!("cheats = {}\n", cheats.len());
+    for (index, cheat) in cheats.iter().enumerate() {
+        text += &format!(
+            "\ncheat{index}_desc = \"{}\"\ncheat{index}_code = \"{}\"\ncheat{index}_enable = {}\n",
+            cheat.name,
+            cheat.code(),
+            cheat.enabled
+        );
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FCEUX: &str = "\
+S91d9:ad:Infinite lives
+:0075:09:Start with 9 lives
+SC:c000:60:4c:Skip intro
+";
+
+    const LIBRETRO: &str = "\
+cheats = 2
+
+cheat0_desc = \"Infinite lives\"
+cheat0_code = \"SXIOPO+0075:09\"
+cheat0_enable = true
+
+cheat1_desc = \"Skip intro\"
+cheat1_code = \"C000?4C:60\"
+cheat1_enable = false
+cheat1_handler = \"1\"
+";
+
+    #[test]
+    fn test_fceux_round_trip() {
+        let cheats = parse_fceux(FCEUX).unwrap();
+        assert_eq!(cheats.len(), 3);
+
+        let lives = cheats.get(0).unwrap();
+        assert_eq!(lives.name, "Infinite lives");
+        assert_eq!(lives.patches, [Patch::substitute(0x91D9, 0xAD, None)]);
+        assert!(lives.enabled);
+
+        let start = cheats.get(1).unwrap();
+        assert_eq!(start.patches, [Patch::freeze(0x0075, 0x09)]);
+        assert!(!start.enabled);
+
+        let intro = cheats.get(2).unwrap();
+        assert_eq!(intro.patches, [Patch::substitute(0xC000, 0x60, Some(0x4C))]);
+        assert!(!intro.enabled);
+
+        assert_eq!(write_fceux(&cheats), FCEUX);
+        assert_eq!(CheatFormat::detect(FCEUX), CheatFormat::Fceux);
+        assert!(parse_fceux("0075:zz:Bad").is_err());
+    }
+
+    #[test]
+    fn test_libretro_round_trip() {
+        assert_eq!(CheatFormat::detect(LIBRETRO), CheatFormat::Libretro);
+        let cheats = parse_libretro(LIBRETRO).unwrap();
+        assert_eq!(cheats.len(), 2);
+        assert_eq!(cheats.get(0).unwrap().patches.len(), 2);
+        assert!(!cheats.get(1).unwrap().enabled);
+
+        let again = parse_libretro(&write_libretro(&cheats)).unwrap();
+        assert_eq!(
+            again.iter().collect::<Vec<_>>(),
+            cheats.iter().collect::<Vec<_>>()
+        );
+
+        // Across formats
+        let fceux = parse_fceux(&write_fceux(&cheats)).unwrap();
+        assert_eq!(fceux.len(), 3);
+        assert_eq!(
+            fceux.get(2).unwrap().patches,
+            cheats.get(1).unwrap().patches
+        );
+    }
+}
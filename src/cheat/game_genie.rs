@@ -0,0 +1,107 @@
+// Game Genie codes: 6 letters for an address in $8000-$FFFF and the value read there, 8 letters
+// add a compare value so only the bank holding the expected byte is patched. Each letter is a
+// nibble whose bits are scattered over the address, value and compare; bit 3 of the third
+// letter tells 6 from 8 letter codes.
+
+use super::{Patch, PatchKind};
+
+const LETTERS: &[u8; 16] = b"APZLGITYEOXUKSVN";
+
+// `None` when `code` is not a Game Genie code
+pub fn decode(code: &str) -> Option<Patch> {
+    if code.len() != 6 && code.len() != 8 {
+        return None;
+    }
+
+    let mut n = [0u16; 8];
+    for (nibble, letter) in n.iter_mut().zip(code.bytes()) {
+        let letter = letter.to_ascii_uppercase();
+        *nibble = LETTERS.iter().position(|&l| l == letter)? as u16;
+    }
+
+    let address = 0x8000
+        | (n[3] & 7) << 12
+        | (n[5] & 7) << 8
+        | (n[4] & 8) << 8
+        | (n[2] & 7) << 4
+        | (n[1] & 8) << 4
+        | (n[4] & 7)
+        | (n[3] & 8);
+    let value = (n[1] & 7) << 4 | (n[0] & 8) << 4 | (n[0] & 7);
+
+    let patch = match code.len() {
+        6 => Patch::substitute(address, (value | n[5] & 8) as u8, None),
+        _ => {
+            let compare = (n[7] & 7) << 4 | (n[6] & 8) << 4 | (n[6] & 7) | (n[5] & 8);
+            Patch::substitute(address, (value | n[7] & 8) as u8, Some(compare as u8))
+        }
+    };
+    Some(patch)
+}
+
+// `None` for patches a Game Genie can't make: freezes and reads below $8000
+pub fn encode(patch: &Patch) -> Option<String> {
+    if patch.kind != PatchKind::Substitute || patch.address < 0x8000 {
+        return None;
+    }
+
+    let address = patch.address;
+    let value = patch.value as u16;
+    let mut n = vec![
+        (value & 7) | (value >> 4 & 8),
+        (value >> 4 & 7) | (address >> 4 & 8),
+        (address >> 4 & 7),
+        (address >> 12 & 7) | (address & 8),
+        (address & 7) | (address >> 8 & 8),
+        (address >> 8 & 7) | (value & 8),
+    ];
+
+    if let Some(compare) = patch.compare {
+        let compare = compare as u16;
+        n[2] |= 8;
+        n[5] = (address >> 8 & 7) | (compare & 8);
+        n.push((compare & 7) | (compare >> 4 & 8));
+        n.push((compare >> 4 & 7) | (value & 8));
+    }
+
+    Some(
+        n.iter()
+            .map(|&nibble| LETTERS[nibble as usize] as char)
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_known_codes() {
+        assert_eq!(
+            decode("SXIOPO"),
+            Some(Patch::substitute(0x91D9, 0xAD, None))
+        );
+        assert_eq!(
+            decode("gossip"),
+            Some(Patch::substitute(0xD1DD, 0x14, None))
+        );
+        assert_eq!(
+            decode("ZEXPYGLA"),
+            Some(Patch::substitute(0x94A7, 0x02, Some(0x03)))
+        );
+        assert_eq!(decode("SXIOP"), None);
+        assert_eq!(decode("SXIOPB"), None);
+    }
+
+    #[test]
+    fn test_encode_round_trips() {
+        assert_eq!(encode(&decode("SXIOPO").unwrap()).unwrap(), "SXIOPO");
+        assert_eq!(encode(&decode("ZEXPYGLA").unwrap()).unwrap(), "ZEXPYGLA");
+
+        for (address, value, compare) in [(0x8000, 0x00, None), (0xFFFF, 0xFF, Some(0x80))] {
+            let patch = Patch::substitute(address, value, compare);
+            assert_eq!(decode(&encode(&patch).unwrap()), Some(patch));
+        }
+        assert_eq!(encode(&Patch::freeze(0x0075, 0x09)), None);
+    }
+}
@@ -0,0 +1,260 @@
+// Cheat engine. Substitutions (Game Genie codes and FCEUX "S" cheats) replace the value the CPU
+// reads from an address, optionally only while the cartridge still returns the compare value
+// so other banks mapped there are left alone. Freezes (Pro Action Replay and raw RAM cheats)
+// write their value back once per frame.
+
+pub mod file;
+pub mod game_genie;
+pub mod search;
+
+use crate::sys::errors::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchKind {
+    Substitute,
+    Freeze,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Patch {
+    pub kind: PatchKind,
+    pub address: u16,
+    pub value: u8,
+    pub compare: Option<u8>, // Substitutions only
+}
+
+impl Patch {
+    pub fn substitute(address: u16, value: u8, compare: Option<u8>) -> Self {
+        Patch {
+            kind: PatchKind::Substitute,
+            address,
+            value,
+            compare,
+        }
+    }
+
+    pub fn freeze(address: u16, value: u8) -> Self {
+        Patch {
+            kind: PatchKind::Freeze,
+            address,
+            value,
+            compare: None,
+        }
+    }
+
+    // Game Genie (`SXIOPO`, `ZEXPYGLA`), Pro Action Replay (`00007509`, the middle four digits
+    // are the address) or raw `AAAA:VV`, `AAAA?CC:VV` codes. Raw codes in ROM substitute,
+    // in RAM they freeze
+    pub fn parse(code: &str) -> Result<Self, Error> {
+        let code = code.trim();
+        if let Some(patch) = game_genie::decode(code) {
+            return Ok(patch);
+        }
+
+        let hex = |digits: &str| match digits.len() {
+            1..=4 => u16::from_str_radix(digits, 16).ok(),
+            _ => None,
+        };
+        let byte = |digits: &str| hex(digits).and_then(|value| u8::try_from(value).ok());
+
+        let patch = match code.split_once(':') {
+            Some((target, value)) => {
+                let value = byte(value);
+                match target.split_once('?') {
+                    Some((address, compare)) => Some(Patch::substitute(
+                        hex(address).ok_or(Error::ErrorInvalidCheatCode)?,
+                        value.ok_or(Error::ErrorInvalidCheatCode)?,
+                        Some(byte(compare).ok_or(Error::ErrorInvalidCheatCode)?),
+                    )),
+                    None => hex(target)
+                        .zip(value)
+                        .map(|(address, value)| match address {
+                            0x8000..=0xFFFF => Patch::substitute(address, value, None),
+                            _ => Patch::freeze(address, value),
+                        }),
+                }
+            }
+            None if code.len() == 8 && code.is_ascii() => hex(&code[2..6])
+                .zip(byte(&code[6..]))
+                .filter(|_| byte(&code[..2]).is_some())
+                .map(|(address, value)| Patch::freeze(address, value)),
+            None => None,
+        };
+        patch.ok_or(Error::ErrorInvalidCheatCode)
+    }
+
+    // Game Genie letters where possible so the code can be typed into other emulators
+    pub fn code(&self) -> String {
+        if let Some(code) = game_genie::encode(self) {
+            return code;
+        }
+
+        match self.compare {
+            Some(compare) => format!("{:04X}?{:02X}:{:02X}", self.address, compare, self.value),
+            None => format!("{:04X}:{:02X}", self.address, self.value),
+        }
+    }
+
+    fn substituted(&self, address: u16, value: u8) -> Option<u8> {
+        let hit = self.kind == PatchKind::Substitute
+            && self.address == address
+            && self.compare.is_none_or(|compare| compare == value);
+        hit.then_some(self.value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    pub name: String,
+    pub patches: Vec<Patch>,
+    pub enabled: bool,
+}
+
+impl Cheat {
+    // Codes that belong together are joined with `+`, as in libretro cheat files
+    pub fn new(name: &str, codes: &str) -> Result<Self, Error> {
+        let patches = codes
+            .split('+')
+            .map(Patch::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Cheat {
+            name: name.to_string(),
+            patches,
+            enabled: true,
+        })
+    }
+
+    pub fn code(&self) -> String {
+        let codes: Vec<String> = self.patches.iter().map(Patch::code).collect();
+        codes.join("+")
+    }
+}
+
+// The cheats of the running game, each switched on and off on its own
+#[derive(Debug, Clone, Default)]
+pub struct Cheats {
+    cheats: Vec<Cheat>,
+}
+
+impl Cheats {
+    pub fn len(&self) -> usize {
+        self.cheats.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cheats.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Cheat> {
+        self.cheats.iter()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Cheat> {
+        self.cheats.get(index)
+    }
+
+    // Returns the cheat's index
+    pub fn add(&mut self, cheat: Cheat) -> usize {
+        self.cheats.push(cheat);
+        self.cheats.len() - 1
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Cheat> {
+        (index < self.cheats.len()).then(|| self.cheats.remove(index))
+    }
+
+    pub fn clear(&mut self) {
+        self.cheats.clear();
+    }
+
+    // `false` when there is no such cheat
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        match self.cheats.get_mut(index) {
+            Some(cheat) => {
+                cheat.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // The new state, `None` when there is no such cheat
+    pub fn toggle(&mut self, index: usize) -> Option<bool> {
+        let cheat = self.cheats.get_mut(index)?;
+        cheat.enabled = !cheat.enabled;
+        Some(cheat.enabled)
+    }
+
+    fn active(&self) -> impl Iterator<Item = &Patch> {
+        self.cheats
+            .iter()
+            .filter(|cheat| cheat.enabled)
+            .flat_map(|cheat| &cheat.patches)
+    }
+
+    // What the CPU sees instead of `value` at `address`
+    pub fn patch_read(&self, address: u16, value: u8) -> u8 {
+        self.active()
+            .find_map(|patch| patch.substituted(address, value))
+            .unwrap_or(value)
+    }
+
+    // Addresses and values to write back every frame
+    pub fn freezes(&self) -> impl Iterator<Item = (u16, u8)> {
+        self.active()
+            .filter(|patch| patch.kind == PatchKind::Freeze)
+            .map(|patch| (patch.address, patch.value))
+    }
+}
+
+impl FromIterator<Cheat> for Cheats {
+    fn from_iter<I: IntoIterator<Item = Cheat>>(iter: I) -> Self {
+        Cheats {
+            cheats: iter.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_codes() {
+        assert_eq!(
+            Patch::parse("SXIOPO"),
+            Ok(Patch::substitute(0x91D9, 0xAD, None))
+        );
+        assert_eq!(Patch::parse("00007509"), Ok(Patch::freeze(0x0075, 0x09)));
+        assert_eq!(Patch::parse("075:9"), Ok(Patch::freeze(0x0075, 0x09)));
+        assert_eq!(
+            Patch::parse("C123?00:EA"),
+            Ok(Patch::substitute(0xC123, 0xEA, Some(0x00)))
+        );
+        assert_eq!(Patch::parse("0075:100"), Err(Error::ErrorInvalidCheatCode));
+        assert_eq!(Patch::parse("0075"), Err(Error::ErrorInvalidCheatCode));
+
+        assert_eq!(Patch::freeze(0x0075, 0x09).code(), "0075:09");
+        assert_eq!(Patch::substitute(0x91D9, 0xAD, None).code(), "SXIOPO");
+    }
+
+    #[test]
+    fn test_substitute_with_compare_and_toggles() {
+        let mut cheats = Cheats::default();
+        let lives = cheats.add(Cheat::new("Lives", "SXIOPO+0075:09").unwrap());
+        cheats.add(Cheat::new("Bank", "C000?4C:60").unwrap());
+
+        assert_eq!(cheats.patch_read(0x91D9, 0xCE), 0xAD);
+        assert_eq!(cheats.patch_read(0xC000, 0x4C), 0x60);
+        assert_eq!(cheats.patch_read(0xC000, 0x20), 0x20);
+        assert_eq!(cheats.freezes().collect::<Vec<_>>(), [(0x0075, 0x09)]);
+
+        assert_eq!(cheats.toggle(lives), Some(false));
+        assert_eq!(cheats.patch_read(0x91D9, 0xCE), 0xCE);
+        assert_eq!(cheats.freezes().count(), 0);
+        assert!(cheats.set_enabled(lives, true));
+        assert!(!cheats.set_enabled(5, true));
+        assert_eq!(cheats.get(lives).unwrap().code(), "SXIOPO+0075:09");
+    }
+}
@@ -0,0 +1,321 @@
+// RAM search, the usual way to find the address of lives, health or a timer: snapshot the CPU
+// RAM and the PRG RAM, let the game run a bit, keep only the addresses whose value relates to
+// the last snapshot in a given way (changed, went up by one, is below 10...) and repeat until
+// few are left. Values are read as 8 or 16-bit (little endian), signed or unsigned.
+
+use crate::memory::{Bus, RAM_SIZE};
+use crate::sys::errors::Error;
+
+const PRG_RAM_START: u16 = 0x6000;
+const PRG_RAM_WINDOW: usize = 0x2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Width {
+    #[default]
+    Byte,
+    Word,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct View {
+    pub width: Width,
+    pub signed: bool,
+}
+
+impl View {
+    // `None` past the end of the memory, words need both bytes
+    fn read(&self, snapshot: &Snapshot, addr: u16) -> Option<i64> {
+        let low = snapshot.read(addr)?;
+        let value = match (self.width, self.signed) {
+            (Width::Byte, false) => low as i64,
+            (Width::Byte, true) => low as i8 as i64,
+            (Width::Word, signed) => {
+                let word = u16::from_le_bytes([low, snapshot.read(addr.wrapping_add(1))?]);
+                if signed {
+                    word as i16 as i64
+                } else {
+                    word as i64
+                }
+            }
+        };
+        Some(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessOrEqual,
+    GreaterOrEqual,
+}
+
+// What the current value is compared with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Previous,
+    Value(i64),
+    Delta(i64), // The previous value plus this
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Filter {
+    pub comparison: Comparison,
+    pub operand: Operand,
+}
+
+impl Filter {
+    // `eq`, `ne`, `lt`, `gt`, `le` or `ge`, then nothing to compare with the previous value, a
+    // number (`10`, `-5`, `$0A` or `0x0A`) or a change from the previous value (`d+1`, `d-1`)
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut words = text.split_whitespace();
+        let comparison = match words.next() {
+            Some("eq") => Comparison::Equal,
+            Some("ne") => Comparison::NotEqual,
+            Some("lt") => Comparison::Less,
+            Some("gt") => Comparison::Greater,
+            Some("le") => Comparison::LessOrEqual,
+            Some("ge") => Comparison::GreaterOrEqual,
+            _ => return Err(Error::ErrorInvalidSearchFilter),
+        };
+
+        let number = |text: &str| {
+            let (negative, digits) = match text.strip_prefix('-') {
+                Some(digits) => (true, digits),
+                None => (false, text.strip_prefix('+').unwrap_or(text)),
+            };
+            let parsed = match digits.strip_prefix('$').or(digits.strip_prefix("0x")) {
+                Some(hex) => i64::from_str_radix(hex, 16),
+                None => digits.parse(),
+            };
+            match parsed {
+                Ok(value) if !digits.starts_with(['+', '-']) => {
+                    Ok(if negative { -value } else { value })
+                }
+                _ => Err(Error::ErrorInvalidSearchFilter),
+            }
+        };
+        let operand = match words.next() {
+            None => Operand::Previous,
+            Some(word) => match word.strip_prefix('d') {
+                Some(delta) if delta.starts_with(['+', '-']) => Operand::Delta(number(delta)?),
+                _ => Operand::Value(number(word)?),
+            },
+        };
+
+        match words.next() {
+            Some(_) => Err(Error::ErrorInvalidSearchFilter),
+            None => Ok(Filter {
+                comparison,
+                operand,
+            }),
+        }
+    }
+
+    pub fn matches(&self, current: i64, previous: i64) -> bool {
+        let other = match self.operand {
+            Operand::Previous => previous,
+            Operand::Value(value) => value,
+            Operand::Delta(delta) => previous.saturating_add(delta),
+        };
+
+        match self.comparison {
+            Comparison::Equal => current == other,
+            Comparison::NotEqual => current != other,
+            Comparison::Less => current < other,
+            Comparison::Greater => current > other,
+            Comparison::LessOrEqual => current <= other,
+            Comparison::GreaterOrEqual => current >= other,
+        }
+    }
+}
+
+// Copy of the searchable memory: CPU RAM at $0000-$07FF and the PRG RAM visible at $6000-$7FFF
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    ram: [u8; RAM_SIZE],
+    prg_ram: Vec<u8>,
+}
+
+impl Snapshot {
+    pub fn take(bus: &Bus) -> Self {
+        let prg_ram = bus
+            .cartridge_ram()
+            .map_or(&[][..], |prg_ram| prg_ram.data());
+        Snapshot {
+            ram: bus.ram,
+            prg_ram: prg_ram[..prg_ram.len().min(PRG_RAM_WINDOW)].to_vec(),
+        }
+    }
+
+    pub fn read(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x0000..=0x07FF => Some(self.ram[addr as usize]),
+            PRG_RAM_START.. => self.prg_ram.get((addr - PRG_RAM_START) as usize).copied(),
+            _ => None,
+        }
+    }
+
+    fn addresses(&self) -> impl Iterator<Item = u16> + use<> {
+        let prg_ram_end = PRG_RAM_START + self.prg_ram.len() as u16;
+        (0..RAM_SIZE as u16).chain(PRG_RAM_START..prg_ram_end)
+    }
+}
+
+pub struct RamSearch {
+    view: View,
+    previous: Snapshot,
+    candidates: Vec<u16>,
+}
+
+impl RamSearch {
+    // Every address is a candidate until the first filter
+    pub fn new(bus: &Bus, view: View) -> Self {
+        let previous = Snapshot::take(bus);
+        let candidates = previous
+            .addresses()
+            .filter(|&addr| view.read(&previous, addr).is_some())
+            .collect();
+
+        RamSearch {
+            view,
+            previous,
+            candidates,
+        }
+    }
+
+    pub fn view(&self) -> View {
+        self.view
+    }
+
+    // Keeps the candidates, words that would run past the end of the memory are dropped
+    pub fn set_view(&mut self, view: View) {
+        self.view = view;
+        let previous = &self.previous;
+        self.candidates
+            .retain(|&addr| view.read(previous, addr).is_some());
+    }
+
+    pub fn candidates(&self) -> &[u16] {
+        &self.candidates
+    }
+
+    // Value at `addr` when the last snapshot was taken
+    pub fn previous(&self, addr: u16) -> Option<i64> {
+        self.view.read(&self.previous, addr)
+    }
+
+    // Takes a new snapshot and keeps the candidates that pass, returns how many are left
+    pub fn filter(&mut self, bus: &Bus, filter: Filter) -> usize {
+        let current = Snapshot::take(bus);
+        let (view, previous) = (self.view, &self.previous);
+
+        self.candidates.retain(|&addr| {
+            match (view.read(&current, addr), view.read(previous, addr)) {
+                (Some(current), Some(previous)) => filter.matches(current, previous),
+                _ => false,
+            }
+        });
+
+        self.previous = current;
+        self.candidates.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::prg_ram::PrgRam;
+    use crate::memory::BusInterface;
+    use crate::sys::interfaces::RomData;
+
+    fn filter(text: &str) -> Filter {
+        Filter::parse(text).unwrap()
+    }
+
+    #[test]
+    fn test_parse_filters() {
+        assert_eq!(
+            filter("ne"),
+            Filter {
+                comparison: Comparison::NotEqual,
+                operand: Operand::Previous
+            }
+        );
+        assert_eq!(filter("eq d+1").operand, Operand::Delta(1));
+        assert_eq!(filter("eq d-2").operand, Operand::Delta(-2));
+        assert_eq!(filter("eq -2").operand, Operand::Value(-2));
+        assert_eq!(filter("eq +$10").operand, Operand::Value(16));
+        assert_eq!(filter("lt $0A").operand, Operand::Value(10));
+        assert_eq!(filter("ge 0x10").operand, Operand::Value(16));
+        assert!(Filter::parse("eq 1 2").is_err());
+        assert!(Filter::parse("about 1").is_err());
+        assert!(Filter::parse("lt ten").is_err());
+        assert!(Filter::parse("eq d1").is_err());
+        assert!(Filter::parse("eq --1").is_err());
+
+        // Huge deltas saturate instead of overflowing
+        assert!(!filter("eq d+9223372036854775807").matches(0, 1));
+        assert!(filter("le d-9223372036854775807").matches(i64::MIN, -2));
+    }
+
+    #[test]
+    fn test_narrow_down_a_counter() {
+        let mut bus = Bus::new(RomData::default());
+        bus.load_prg_ram(PrgRam::with_size(0x2000, false));
+        bus.write(0x0042, 3);
+        bus.write(0x0100, 3);
+        bus.write(0x6010, 7);
+
+        let mut search = RamSearch::new(&bus, View::default());
+        assert_eq!(search.candidates().len(), RAM_SIZE + 0x2000);
+
+        // Lives went down by one, something else changed too
+        bus.write(0x0042, 2);
+        bus.write(0x0100, 9);
+        assert_eq!(search.filter(&bus, filter("eq d-1")), 1);
+        assert_eq!(search.candidates(), [0x0042]);
+        assert_eq!(search.previous(0x0042), Some(2));
+
+        assert_eq!(search.filter(&bus, filter("lt 3")), 1);
+        bus.write(0x0042, 5);
+        assert_eq!(search.filter(&bus, filter("eq")), 0);
+
+        // PRG RAM is searched too
+        let mut search = RamSearch::new(&bus, View::default());
+        bus.write(0x6010, 8);
+        search.filter(&bus, filter("gt"));
+        assert_eq!(search.candidates(), [0x6010]);
+    }
+
+    #[test]
+    fn test_signed_words() {
+        let mut bus = Bus::new(RomData::default());
+        let view = View {
+            width: Width::Word,
+            signed: true,
+        };
+        let mut search = RamSearch::new(&bus, view);
+        assert_eq!(search.candidates().len(), RAM_SIZE - 1);
+
+        // -2 as a little endian word
+        bus.write(0x0200, 0xFE);
+        bus.write(0x0201, 0xFF);
+        search.filter(&bus, filter("lt 0"));
+        assert_eq!(search.candidates(), [0x01FF, 0x0200]);
+        assert_eq!(search.previous(0x0200), Some(-2));
+
+        // Negative constants compare as values, $01FF reads $FE00
+        assert_eq!(search.filter(&bus, filter("ge -512")), 2);
+        assert_eq!(search.filter(&bus, filter("eq -2")), 1);
+        assert_eq!(search.candidates(), [0x0200]);
+
+        search.set_view(View {
+            width: Width::Byte,
+            signed: false,
+        });
+        assert_eq!(search.previous(0x0200), Some(0xFE));
+    }
+}
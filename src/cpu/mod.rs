@@ -36,6 +36,13 @@ impl Cpu {
         self.status = StatusFlags::empty();
     }
 
+    // Reset button: the cartridge sees it on the bus (multicarts go back to their menu or step
+    // to the next game) and the CPU restarts
+    pub fn reset_console(&mut self, bus: &mut Bus) {
+        bus.reset();
+        self.reset();
+    }
+
     // Set the accumulator register and update the status flags accordingly
     pub fn set_a(&mut self, value: u8) {
         self.a = self.status.set_register_flags(value);
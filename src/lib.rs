@@ -1,3 +1,8 @@
+pub mod apu;
+pub mod cheat;
 pub mod cpu;
+pub mod mapper;
 pub mod memory;
+pub mod ppu;
+pub mod scheduler;
 pub mod sys;
@@ -1,16 +1,202 @@
-use std::path::Path;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
 
+use rust_emulas::cheat::file::read_cheat_file;
+use rust_emulas::cheat::search::{Filter, RamSearch, View, Width};
+use rust_emulas::cpu::Cpu;
+use rust_emulas::mapper::registry;
+use rust_emulas::memory::device::{DEBUG_CONSOLE_PORT, DebugConsole};
+use rust_emulas::memory::dump::MemorySpace;
+use rust_emulas::memory::power_on::PowerOnState;
 use rust_emulas::memory::{Bus, BusInterface};
+use rust_emulas::scheduler::SyncMode;
+use rust_emulas::sys::errors::Error;
 use rust_emulas::sys::interfaces::ROMFs;
 use rust_emulas::sys::rom_file::ROM;
+use rust_emulas::sys::save::{self, SaveFile};
+
+fn list_mappers() {
+    for board in registry::supported_boards() {
+        match board.submapper {
+            Some(submapper) => println!("{:>3}.{:<2} {}", board.mapper, submapper, board.name),
+            None => println!("{:>3}    {}", board.mapper, board.name),
+        }
+    }
+}
+
+const RAM_SEARCH_HELP: &str = "\
+new [8|16] [u|s]                   start over, 8 or 16-bit, unsigned or signed
+frame [N]                          run N frames (1)
+reset                              press the console's reset button
+eq|ne|lt|gt|le|ge [VALUE|d+N|d-N]  keep the addresses whose value compares with the
+                                   previous one, VALUE or the previous one plus N
+list [N]                           show up to N candidates (20)
+quit";
+
+// Every frame goes through here so battery memory reaches the save file while the game runs
+fn run_frames(bus: &mut Bus, save_file: &mut SaveFile, frames: usize) {
+    for _ in 0..frames {
+        bus.run_frame();
+        save_file.flush(bus).expect("Failed to write save file");
+    }
+}
+
+// Cheat finder on stdin, one command per line
+fn ram_search(cpu: &mut Cpu, bus: &mut Bus, save_file: &mut SaveFile) {
+    let mut search = RamSearch::new(bus, View::default());
+    println!("{}", RAM_SEARCH_HELP);
+
+    for line in io::stdin().lock().lines().map_while(Result::ok) {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("new") => {
+                let mut view = View::default();
+                for word in words {
+                    match word {
+                        "16" => view.width = Width::Word,
+                        "s" => view.signed = true,
+                        _ => {}
+                    }
+                }
+                search = RamSearch::new(bus, view);
+                println!("{} candidates", search.candidates().len());
+            }
+            Some("frame") => {
+                let frames = words.next().and_then(|n| n.parse().ok()).unwrap_or(1);
+                run_frames(bus, save_file, frames);
+            }
+            Some("reset") => cpu.reset_console(bus),
+            Some("list") => {
+                let count = words.next().and_then(|n| n.parse().ok()).unwrap_or(20);
+                for &addr in search.candidates().iter().take(count) {
+                    println!(
+                        "${:04X}: {}",
+                        addr,
+                        search.previous(addr).unwrap_or_default()
+                    );
+                }
+            }
+            Some("quit") => break,
+            Some(_) => match Filter::parse(&line) {
+                Ok(filter) => println!("{} candidates", search.filter(bus, filter)),
+                Err(error) => println!("{}\n{}", error, RAM_SEARCH_HELP),
+            },
+            None => {}
+        }
+        let _ = io::stdout().flush();
+    }
+}
+
+// `dump` subcommand: run the ROM for some frames then print or save its memory
+fn dump(
+    bus: &mut Bus,
+    save_file: &mut SaveFile,
+    frames: usize,
+    spaces: &[MemorySpace],
+    raw_dir: Option<&Path>,
+) {
+    run_frames(bus, save_file, frames);
+
+    let spaces = if spaces.is_empty() {
+        &MemorySpace::ALL[..]
+    } else {
+        spaces
+    };
+    for space in spaces {
+        match raw_dir {
+            Some(dir) => space
+                .write_raw(bus, dir)
+                .expect("Failed to write memory dump"),
+            None => println!("{}", space.hexdump(bus)),
+        }
+    }
+}
 
 fn main() {
-    let rom_path: &Path = Path::new("./mamaco.nes");
+    let mut args = std::env::args().skip(1);
+    let mut rom_path = None;
+    let mut save_dir = None;
+    let mut sync_mode = SyncMode::Lockstep;
+    let mut debug_console = false;
+    let mut cheat_file = None;
+    let mut search = false;
+    let mut power_on = PowerOnState::Zeros;
+    let mut dump_mode = false;
+    let mut dump_frames = 0;
+    let mut dump_spaces = Vec::new();
+    let mut dump_dir = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--list-mappers" => {
+                list_mappers();
+                return;
+            }
+            "--save-dir" => save_dir = args.next().map(PathBuf::from),
+            "--catch-up" => sync_mode = SyncMode::CatchUp,
+            "--debug-console" => debug_console = true,
+            "--cheats" => cheat_file = args.next().map(PathBuf::from),
+            "--ram-search" => search = true,
+            "--power-on" => {
+                let state = args.next().unwrap_or_default();
+                power_on = PowerOnState::parse(&state).unwrap_or_else(|error| {
+                    eprintln!("{}", error);
+                    std::process::exit(1);
+                });
+            }
+            "dump" => dump_mode = true,
+            "--frames" => {
+                let count = args.next().unwrap_or_default();
+                dump_frames = count.parse().unwrap_or_else(|_| {
+                    eprintln!("{}", Error::ErrorInvalidFrameCount);
+                    std::process::exit(1);
+                });
+            }
+            "--space" => {
+                let name = args.next().unwrap_or_default();
+                dump_spaces.push(MemorySpace::parse(&name).unwrap_or_else(|error| {
+                    eprintln!("{}", error);
+                    std::process::exit(1);
+                }));
+            }
+            "--raw" => dump_dir = args.next().map(PathBuf::from),
+            _ => rom_path = Some(arg),
+        }
+    }
+
+    let rom_path = rom_path.unwrap_or_else(|| "./mamaco.nes".to_string());
+    let rom_path: &Path = Path::new(&rom_path);
     let rom = ROM::new(&rom_path).expect("Failed to load ROM");
-    let prg_rom = &rom.format.prg_rom;
-    let bus: &mut Bus = &mut Bus::new(&prg_rom);
-    rom.write_rom_memory(bus)
-        .expect("Failed to write ROM to bus");
+    let bus: &mut Bus = &mut Bus::new(rom.format.prg_rom.clone());
+    // Printed so a random run can be repeated with `--power-on random:<seed>`
+    println!("Power-on state: {}", power_on);
+    bus.set_power_on_state(power_on);
+    if let Err(error) = rom.write_rom_memory(bus) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+    bus.set_region(rom.format.region);
+    bus.set_sync_mode(sync_mode);
+    if debug_console {
+        bus.register_device(
+            DEBUG_CONSOLE_PORT..=DEBUG_CONSOLE_PORT,
+            DebugConsole::new(true),
+        )
+        .expect("Failed to map the debug console");
+    }
+
+    if let Some(cheat_file) = cheat_file {
+        bus.cheats = read_cheat_file(cheat_file).expect("Failed to read cheat file");
+    }
+
+    let mut save_file = SaveFile::new(
+        save::save_path(rom_path, save_dir.as_deref()),
+        save::FLUSH_INTERVAL,
+    );
+    save_file.load(bus).expect("Failed to read save file");
+    if let Err(error) = rom.write_trainer(bus) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
 
     // Assuming Bus has a method to get a reference to its memory, e.g., bus.memory()
     println!(
@@ -19,4 +205,19 @@ fn main() {
     );
     println!("Size of ROM: {}", size_of::<ROM>());
     println!("Align of ROM: {}", align_of::<ROM>());
+
+    if search {
+        ram_search(&mut Cpu::new(), bus, &mut save_file);
+    }
+    if dump_mode {
+        dump(
+            bus,
+            &mut save_file,
+            dump_frames,
+            &dump_spaces,
+            dump_dir.as_deref(),
+        );
+    }
+
+    save_file.save(bus).expect("Failed to write save file");
 }
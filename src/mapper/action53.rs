@@ -0,0 +1,214 @@
+// Action 53 (iNES mapper 28), the homebrew multicart board. A register select at
+// $5000-$5FFF picks which of four registers the next $8000-$FFFF write lands in:
+// $00 CHR bank, $01 inner PRG bank, $80 mode, $81 outer PRG bank.
+// The mode sets the mirroring, the PRG layout (32 KiB, or 16 KiB with the first or last
+// bank of the outer bank fixed) and the game size that splits bank bits between the
+// inner and outer registers.
+
+use crate::sys::interfaces::{INes, MirroringType, RomData};
+
+use super::Mapper;
+use super::bank::{BankWindow, SIZE_8K, SIZE_16K, SIZE_32K};
+use super::chr::Chr;
+use super::{named_registers, window_registers};
+
+const CHR_RAM_SIZE: usize = 0x8000; // 4 banks of 8 KiB
+
+pub struct Action53 {
+    prg_rom: RomData,
+    chr: Chr,
+    select: u8,
+    chr_bank: u8,
+    inner_bank: u8,
+    mode: u8,
+    outer_bank: u8,
+    prg_window: BankWindow,
+    chr_window: BankWindow,
+}
+
+impl Action53 {
+    pub fn new(ines: &INes) -> Self {
+        let chr = Chr::with_ram_size(ines, CHR_RAM_SIZE);
+
+        let mut action53 = Action53 {
+            prg_window: BankWindow::new(ines.prg_rom.len(), SIZE_32K, SIZE_16K),
+            chr_window: BankWindow::new(chr.len(), SIZE_8K, SIZE_8K),
+            prg_rom: ines.prg_rom.clone(),
+            chr,
+            select: 0,
+            chr_bank: 0,
+            inner_bank: 0,
+            mode: 0,
+            outer_bank: 0xFF, // Boots into the last 32 KiB where the menu lives
+        };
+        action53.update_banks();
+        action53
+    }
+
+    // 16 KiB bank for the half of $8000-$FFFF selected by CPU A14
+    fn prg_bank(&self, a14: usize) -> usize {
+        let outer = (self.outer_bank as usize) << 1;
+        let game_size = (self.mode >> 4) & 0x03;
+        let prg_mode = (self.mode >> 2) & 0x03;
+
+        let inner = match (prg_mode, a14) {
+            // 32 KiB: the inner register picks a pair of banks
+            (0 | 1, _) => ((self.inner_bank as usize) << 1) | a14,
+            // UNROM-like layouts fix one half to the outer bank
+            (2, 0) => return outer,
+            (3, 1) => return outer | 1,
+            _ => self.inner_bank as usize,
+        };
+
+        // Game size 0 is 32 KiB, each step doubles it
+        let mask = (2 << game_size) - 1;
+        (outer & !mask) | (inner & mask)
+    }
+
+    fn update_banks(&mut self) {
+        self.prg_window.select(0, SIZE_16K, self.prg_bank(0));
+        self.prg_window.select(1, SIZE_16K, self.prg_bank(1));
+        self.chr_window.select(0, SIZE_8K, self.chr_bank as usize);
+    }
+
+    // Single-screen modes take the page from bit 4 of CHR and inner bank writes
+    fn write_one_screen(&mut self, value: u8) {
+        if self.mode & 0x02 == 0 {
+            self.mode = (self.mode & !0x01) | ((value >> 4) & 0x01);
+        }
+    }
+}
+
+impl Mapper for Action53 {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_window.index(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x5000..=0x5FFF => self.select = value & 0x81,
+            0x8000..=0xFFFF => match self.select {
+                0x00 => {
+                    self.chr_bank = value & 0x03;
+                    self.write_one_screen(value);
+                }
+                0x01 => {
+                    self.inner_bank = value & 0x0F;
+                    self.write_one_screen(value);
+                }
+                0x80 => self.mode = value & 0x3F,
+                _ => self.outer_bank = value,
+            },
+            _ => return,
+        }
+        self.update_banks();
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        self.chr[self.chr_window.index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        self.chr.write(self.chr_window.index(addr), value);
+    }
+
+    fn chr(&self) -> Option<&Chr> {
+        Some(&self.chr)
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut Chr> {
+        Some(&mut self.chr)
+    }
+
+    fn registers(&self) -> Vec<(String, u16)> {
+        let mut registers = window_registers("prg", &self.prg_window);
+        registers.extend(window_registers("chr", &self.chr_window));
+        registers.extend(named_registers([
+            ("select", self.select as u16),
+            ("chr_bank", self.chr_bank as u16),
+            ("inner_bank", self.inner_bank as u16),
+            ("mode", self.mode as u16),
+            ("outer_bank", self.outer_bank as u16),
+        ]));
+        registers
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        match self.mode & 0x03 {
+            0 => MirroringType::SingleScreenLower,
+            1 => MirroringType::SingleScreenUpper,
+            2 => MirroringType::Vertical,
+            _ => MirroringType::Horizontal,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::test_rom::test_rom;
+
+    fn board() -> Action53 {
+        Action53::new(&test_rom(28).prg(SIZE_16K, 32).build())
+    }
+
+    fn write_register(action53: &mut Action53, register: u8, value: u8) {
+        action53.cpu_write(0x5000, register);
+        action53.cpu_write(0x8000, value);
+    }
+
+    #[test]
+    fn test_power_on_last_32k() {
+        let action53 = board();
+        assert_eq!(action53.cpu_peek(0x8000), Some(30));
+        assert_eq!(action53.cpu_peek(0xC000), Some(31));
+    }
+
+    #[test]
+    fn test_prg_modes_within_outer_bank() {
+        let mut action53 = board();
+
+        // 64 KiB game in the second 64 KiB of the ROM, 32 KiB mode
+        write_register(&mut action53, 0x81, 0x02);
+        write_register(&mut action53, 0x80, 0x10);
+        write_register(&mut action53, 0x01, 0x01);
+        assert_eq!(action53.cpu_read(0x8000), Some(6));
+        assert_eq!(action53.cpu_read(0xC000), Some(7));
+
+        // UNROM layout, $C000 fixed to the last bank of a 128 KiB game
+        write_register(&mut action53, 0x81, 0x07);
+        write_register(&mut action53, 0x80, 0x2C);
+        write_register(&mut action53, 0x01, 0x02);
+        assert_eq!(action53.cpu_read(0x8000), Some(10));
+        assert_eq!(action53.cpu_read(0xC000), Some(15));
+
+        // Fixed $8000, bank 3 of the same game at $C000
+        write_register(&mut action53, 0x81, 0x04);
+        write_register(&mut action53, 0x80, 0x28);
+        write_register(&mut action53, 0x01, 0x03);
+        assert_eq!(action53.cpu_read(0x8000), Some(8));
+        assert_eq!(action53.cpu_read(0xC000), Some(11));
+    }
+
+    #[test]
+    fn test_mirroring_and_chr_ram_banks() {
+        let mut action53 = board();
+
+        write_register(&mut action53, 0x80, 0x02);
+        assert_eq!(action53.mirroring(), MirroringType::Vertical);
+
+        write_register(&mut action53, 0x80, 0x00);
+        write_register(&mut action53, 0x00, 0x11);
+        assert_eq!(action53.mirroring(), MirroringType::SingleScreenUpper);
+
+        action53.ppu_write(0x0000, 0x42);
+        write_register(&mut action53, 0x00, 0x02);
+        assert_eq!(action53.mirroring(), MirroringType::SingleScreenLower);
+        assert_eq!(action53.ppu_read(0x0000), 0x00);
+        write_register(&mut action53, 0x00, 0x01);
+        assert_eq!(action53.ppu_read(0x0000), 0x42);
+    }
+}
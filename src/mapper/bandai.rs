@@ -0,0 +1,350 @@
+// Bandai FCG-1/FCG-2 and LZ93D50 boards (iNES mappers 16, 153, 157 and 159).
+// The FCG exposes its registers at $6000-$7FFF and loads the IRQ counter directly,
+// the LZ93D50 moves them to $8000-$FFFF, loads a latch instead and bit-bangs a serial
+// EEPROM through register $D for saves.
+
+use crate::sys::interfaces::{INes, MirroringType, RomData};
+
+use super::bank::{BankWindow, SIZE_1K, SIZE_8K, SIZE_16K, SIZE_32K};
+use super::eeprom::{EepromChip, I2cEeprom};
+use super::{CHR_RAM_SIZE, Mapper, chr::Chr, named_registers, prg_ram::PrgRam, window_registers};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BandaiBoard {
+    Fcg,         // 16.4
+    Lz93d50,     // 16.5, 16.0, 159
+    Lz93d50Sram, // 153, 8 KiB battery backed PRG RAM and 512 KiB PRG
+    Datach,      // 157, CHR RAM, the barcode reader is not emulated
+}
+
+pub struct Bandai {
+    board: BandaiBoard,
+    prg_rom: RomData,
+    prg_ram: PrgRam,
+    chr: Chr,
+    fcg_registers: bool,     // Registers answer at $6000-$7FFF
+    lz93d50_registers: bool, // Registers answer at $8000-$FFFF
+    chr_banks: [u8; 8],
+    prg_bank: u8,
+    prg_window: BankWindow,
+    chr_window: BankWindow,
+    mirroring: MirroringType,
+    control: u8, // Register $D
+    irq_enabled: bool,
+    irq_counter: u16,
+    irq_latch: u16,
+    irq_pending: bool,
+    eeprom: Option<I2cEeprom>,
+}
+
+impl Bandai {
+    pub fn new(ines: &INes) -> Self {
+        let (board, eeprom) = match (ines.mapper, ines.submapper) {
+            (16, 4) => (BandaiBoard::Fcg, None),
+            (153, _) => (BandaiBoard::Lz93d50Sram, None),
+            (157, _) => (BandaiBoard::Datach, Some(EepromChip::C24C02)),
+            (159, _) => (BandaiBoard::Lz93d50, Some(EepromChip::C24C01)),
+            _ => (BandaiBoard::Lz93d50, Some(EepromChip::C24C02)),
+        };
+
+        // Legacy mapper 16 dumps do not say which chip they use, so both register ranges answer
+        let (fcg_registers, lz93d50_registers) = match (ines.mapper, ines.submapper) {
+            (16, 0) => (true, true),
+            (16, 4) => (true, false),
+            _ => (false, true),
+        };
+
+        let chr = match board {
+            BandaiBoard::Lz93d50Sram | BandaiBoard::Datach => Chr::ram(CHR_RAM_SIZE),
+            _ => Chr::new(ines),
+        };
+        let prg_ram = match board {
+            BandaiBoard::Lz93d50Sram => PrgRam::new(ines),
+            _ => PrgRam::default(),
+        };
+
+        let mut bandai = Bandai {
+            board,
+            prg_window: BankWindow::new(ines.prg_rom.len(), SIZE_32K, SIZE_16K),
+            chr_window: BankWindow::new(chr.len(), SIZE_8K, SIZE_1K),
+            prg_rom: ines.prg_rom.clone(),
+            prg_ram,
+            chr,
+            fcg_registers,
+            lz93d50_registers,
+            chr_banks: [0; 8],
+            prg_bank: 0,
+            mirroring: ines.mirroring.clone(),
+            control: 0,
+            irq_enabled: false,
+            irq_counter: 0,
+            irq_latch: 0,
+            irq_pending: false,
+            eeprom: eeprom.map(I2cEeprom::new),
+        };
+        bandai.update_banks();
+        bandai
+    }
+
+    // The chip fixes bank $F at $C000, mapper 153 selects a 256 KiB outer bank with bit 0
+    // of the CHR registers, the other boards bank CHR in 1 KiB slots
+    fn update_banks(&mut self) {
+        let outer = match self.board {
+            BandaiBoard::Lz93d50Sram => self.chr_banks[0..4]
+                .iter()
+                .fold(0, |outer, bank| outer | (bank & 0x01) as usize),
+            _ => 0,
+        };
+        self.prg_window
+            .select(0, SIZE_16K, (outer << 4) | self.prg_bank as usize);
+        self.prg_window.select(1, SIZE_16K, (outer << 4) | 0x0F);
+
+        if !matches!(self.board, BandaiBoard::Lz93d50Sram | BandaiBoard::Datach) {
+            for (slot, bank) in self.chr_banks.iter().enumerate() {
+                self.chr_window.select(slot, SIZE_1K, *bank as usize);
+            }
+        }
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.board == BandaiBoard::Lz93d50Sram && self.control & 0x20 != 0
+    }
+
+    // `lz93d50` tells which register range was written, the IRQ registers behave differently
+    fn write_register(&mut self, addr: u16, value: u8, lz93d50: bool) {
+        match addr & 0x0F {
+            register @ 0x00..=0x07 => self.chr_banks[register as usize] = value,
+            0x08 => self.prg_bank = value & 0x0F,
+            0x09 => {
+                self.mirroring = match value & 0x03 {
+                    0 => MirroringType::Vertical,
+                    1 => MirroringType::Horizontal,
+                    2 => MirroringType::SingleScreenLower,
+                    _ => MirroringType::SingleScreenUpper,
+                }
+            }
+            0x0A => {
+                self.irq_enabled = value & 0x01 != 0;
+                self.irq_pending = false;
+                if lz93d50 {
+                    self.irq_counter = self.irq_latch;
+                }
+            }
+            0x0B if lz93d50 => self.irq_latch = (self.irq_latch & 0xFF00) | value as u16,
+            0x0C if lz93d50 => self.irq_latch = (self.irq_latch & 0x00FF) | ((value as u16) << 8),
+            0x0B => self.irq_counter = (self.irq_counter & 0xFF00) | value as u16,
+            0x0C => self.irq_counter = (self.irq_counter & 0x00FF) | ((value as u16) << 8),
+            0x0D => {
+                self.control = value;
+                // Bit 5 drives SCL and bit 6 SDA
+                if let Some(eeprom) = self.eeprom.as_mut() {
+                    eeprom.write_lines(value & 0x20 != 0, value & 0x40 != 0);
+                }
+            }
+            _ => {}
+        }
+        self.update_banks();
+    }
+}
+
+impl Mapper for Bandai {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => self.prg_ram.read(addr),
+            // EEPROM data comes back on bit 4
+            0x6000..=0x7FFF => self
+                .eeprom
+                .as_ref()
+                .map(|eeprom| (eeprom.sda_out() as u8) << 4),
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_window.index(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => self.prg_ram.write(addr, value),
+            0x6000..=0x7FFF if self.fcg_registers => self.write_register(addr, value, false),
+            0x8000..=0xFFFF if self.lz93d50_registers => self.write_register(addr, value, true),
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        self.chr[self.chr_window.index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        self.chr.write(self.chr_window.index(addr), value);
+    }
+
+    fn chr(&self) -> Option<&Chr> {
+        Some(&self.chr)
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut Chr> {
+        Some(&mut self.chr)
+    }
+
+    fn registers(&self) -> Vec<(String, u16)> {
+        let mut registers = window_registers("prg", &self.prg_window);
+        registers.extend(window_registers("chr", &self.chr_window));
+        registers.extend(named_registers([
+            ("prg_bank", self.prg_bank as u16),
+            ("control", self.control as u16),
+            ("irq_enabled", self.irq_enabled as u16),
+            ("irq_counter", self.irq_counter),
+            ("irq_latch", self.irq_latch),
+        ]));
+        registers
+    }
+
+    fn prg_ram(&self) -> Option<&PrgRam> {
+        Some(&self.prg_ram)
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut PrgRam> {
+        Some(&mut self.prg_ram)
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        self.mirroring.clone()
+    }
+
+    // The counter decrements every CPU cycle while enabled and fires when it reaches zero
+    fn cpu_clock(&mut self) {
+        if !self.irq_enabled {
+            return;
+        }
+
+        self.irq_counter = self.irq_counter.wrapping_sub(1);
+        if self.irq_counter == 0 {
+            self.irq_pending = true;
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn battery_ram(&self) -> Option<Vec<u8>> {
+        match &self.eeprom {
+            Some(eeprom) => Some(eeprom.data().to_vec()),
+            None => self.prg_ram.battery_ram(),
+        }
+    }
+
+    fn load_battery_ram(&mut self, data: &[u8]) {
+        match self.eeprom.as_mut() {
+            Some(eeprom) => eeprom.load(data),
+            None => self.prg_ram.load(data),
+        }
+    }
+
+    fn take_battery_ram_dirty(&mut self) -> bool {
+        match self.eeprom.as_mut() {
+            Some(eeprom) => eeprom.take_dirty(),
+            None => self.prg_ram.take_dirty(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::test_rom::test_rom;
+
+    fn board(mapper: u8, submapper: u8, prg_banks: u8) -> Bandai {
+        let ines = test_rom(mapper)
+            .submapper(submapper)
+            .prg(SIZE_16K, prg_banks as usize)
+            .chr(SIZE_1K, 256)
+            .battery(true);
+        Bandai::new(&ines.build())
+    }
+
+    #[test]
+    fn test_register_ranges_per_submapper() {
+        let mut fcg = board(16, 4, 16);
+        fcg.cpu_write(0x8008, 0x03);
+        assert_eq!(fcg.cpu_read(0x8000), Some(0));
+        fcg.cpu_write(0x6008, 0x03);
+        fcg.cpu_write(0x6007, 0x42);
+        assert_eq!(fcg.cpu_read(0x8000), Some(3));
+        assert_eq!(fcg.cpu_read(0xC000), Some(15));
+        assert_eq!(fcg.ppu_read(0x1C00), 0x42);
+
+        let mut lz93d50 = board(16, 5, 16);
+        lz93d50.cpu_write(0x6008, 0x03);
+        assert_eq!(lz93d50.cpu_read(0x8000), Some(0));
+        lz93d50.cpu_write(0xFFF8, 0x03);
+        assert_eq!(lz93d50.cpu_read(0x8000), Some(3));
+    }
+
+    #[test]
+    fn test_irq_counter_vs_latch() {
+        // FCG writes the counter directly
+        let mut fcg = board(16, 4, 16);
+        fcg.cpu_write(0x600B, 0x02);
+        fcg.cpu_write(0x600A, 0x01);
+        fcg.cpu_clock();
+        assert!(!fcg.irq_pending());
+        fcg.cpu_clock();
+        assert!(fcg.irq_pending());
+
+        // LZ93D50 copies the latch when the IRQ is enabled
+        let mut lz93d50 = board(159, 0, 16);
+        lz93d50.cpu_write(0x800B, 0x02);
+        lz93d50.cpu_clock();
+        lz93d50.cpu_write(0x800A, 0x01);
+        lz93d50.cpu_clock();
+        assert!(!lz93d50.irq_pending());
+        lz93d50.cpu_clock();
+        assert!(lz93d50.irq_pending());
+    }
+
+    #[test]
+    fn test_eeprom_through_register_d() {
+        let mut lz93d50 = board(16, 5, 16);
+        let mut lines = |scl: bool, sda: bool| {
+            let value = ((scl as u8) << 5) | ((sda as u8) << 6) | 0x80;
+            lz93d50.cpu_write(0x800D, value);
+        };
+
+        // Start condition followed by the 24C02 device address for a write
+        lines(true, true);
+        lines(true, false);
+        lines(false, false);
+        for bit in (0..8).rev() {
+            let sda = 0xA0 & (1 << bit) != 0;
+            lines(false, sda);
+            lines(true, sda);
+            lines(false, sda);
+        }
+        lines(false, true);
+        lines(true, true);
+
+        // The EEPROM acknowledges by pulling SDA (bit 4) low
+        assert_eq!(lz93d50.cpu_read(0x6000).unwrap() & 0x10, 0x00);
+        assert_eq!(lz93d50.battery_ram().unwrap().len(), 256);
+    }
+
+    #[test]
+    fn test_mapper_153_outer_bank_and_sram() {
+        let mut lz93d50 = board(153, 0, 32);
+        lz93d50.cpu_write(0x8008, 0x02);
+        assert_eq!(lz93d50.cpu_read(0x8000), Some(2));
+        assert_eq!(lz93d50.cpu_read(0xC000), Some(15));
+
+        lz93d50.cpu_write(0x8000, 0x01);
+        assert_eq!(lz93d50.cpu_read(0x8000), Some(18));
+        assert_eq!(lz93d50.cpu_read(0xC000), Some(31));
+
+        lz93d50.cpu_write(0x6000, 0x55);
+        assert_eq!(lz93d50.cpu_read(0x6000), None);
+        lz93d50.cpu_write(0x800D, 0x20);
+        lz93d50.cpu_write(0x6000, 0x55);
+        assert_eq!(lz93d50.cpu_read(0x6000), Some(0x55));
+        assert_eq!(lz93d50.battery_ram().unwrap()[0], 0x55);
+    }
+}
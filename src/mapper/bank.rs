@@ -0,0 +1,142 @@
+// Bank switching windows. A window covers a stretch of the CPU or PPU address space cut into
+// equal slots (1, 2, 4, 8, 16 or 32 KiB), each slot pointing at a bank of the ROM/RAM behind it.
+// Bank numbers wrap like the missing upper address lines do, and sizes that are not a power of
+// two (a 256 KiB plus a 128 KiB chip, say) mirror the excess onto the smaller upper chip.
+
+pub const SIZE_1K: usize = 0x0400;
+pub const SIZE_2K: usize = 0x0800;
+pub const SIZE_4K: usize = 0x1000;
+pub const SIZE_8K: usize = 0x2000;
+pub const SIZE_16K: usize = 0x4000;
+pub const SIZE_32K: usize = 0x8000;
+
+const MAX_SLOTS: usize = 16;
+
+#[derive(Debug, Clone)]
+pub struct BankWindow {
+    data_len: usize,
+    slot_size: usize,
+    slot_count: usize,
+    slots: [usize; MAX_SLOTS], // Offset of each slot in the data
+}
+
+impl BankWindow {
+    // `window_size` bytes of address space over `data_len` bytes, slot N starts on bank N.
+    // Windows are aligned to their size, like $8000-$FFFF or $0000-$1FFF
+    pub fn new(data_len: usize, window_size: usize, slot_size: usize) -> Self {
+        assert!(window_size.is_power_of_two() && slot_size.is_power_of_two());
+        assert!(slot_size <= window_size && window_size / slot_size <= MAX_SLOTS);
+
+        let mut window = BankWindow {
+            data_len,
+            slot_size,
+            slot_count: window_size / slot_size,
+            slots: [0; MAX_SLOTS],
+        };
+        for slot in 0..window.slot_count {
+            window.select(slot, slot_size, slot);
+        }
+        window
+    }
+
+    // Map `bank`, counted in `bank_size` units, at `slot` and the slots it spans after it
+    pub fn select(&mut self, slot: usize, bank_size: usize, bank: usize) {
+        let base = mirror_bank(bank, self.bank_count(bank_size)) * bank_size;
+
+        for (offset, index) in (slot..slot + bank_size / self.slot_size).enumerate() {
+            self.slots[index] = base + offset * self.slot_size;
+        }
+    }
+
+    // Banks of `bank_size` bytes in the data, a partial bank at the end counts as one
+    pub fn bank_count(&self, bank_size: usize) -> usize {
+        self.data_len.div_ceil(bank_size).max(1)
+    }
+
+    pub fn last_bank(&self, bank_size: usize) -> usize {
+        self.bank_count(bank_size) - 1
+    }
+
+    // Bank mapped at each slot, counted in slot sized units
+    pub fn banks(&self) -> Vec<usize> {
+        self.slots[..self.slot_count]
+            .iter()
+            .map(|offset| offset / self.slot_size)
+            .collect()
+    }
+
+    // Offset in the data for an address inside the window
+    pub fn index(&self, addr: u16) -> usize {
+        let offset = addr as usize & (self.slot_size * self.slot_count - 1);
+        let index = self.slots[offset / self.slot_size] + (offset & (self.slot_size - 1));
+
+        // Data smaller than a slot mirrors inside it
+        index % self.data_len.max(1)
+    }
+}
+
+// Wrap a bank number onto `count` banks the way the cartridge address lines decode it
+pub fn mirror_bank(bank: usize, count: usize) -> usize {
+    let mask = count.next_power_of_two() - 1;
+    let bank = bank & mask;
+
+    if bank < count {
+        return bank;
+    }
+
+    // The upper chip is smaller than the lower one and repeats inside its half
+    let half = (mask + 1) >> 1;
+    half + mirror_bank(bank - half, count - half)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slots_and_bank_sizes() {
+        let mut window = BankWindow::new(16 * SIZE_8K, SIZE_32K, SIZE_8K);
+        assert_eq!(window.index(0xA123), SIZE_8K + 0x0123);
+
+        window.select(0, SIZE_16K, 3);
+        assert_eq!(window.index(0x8000), 6 * SIZE_8K);
+        assert_eq!(window.index(0xBFFF), 8 * SIZE_8K - 1);
+
+        window.select(2, SIZE_8K, window.last_bank(SIZE_8K));
+        assert_eq!(window.index(0xC000), 15 * SIZE_8K);
+        assert_eq!(window.index(0xE000), 3 * SIZE_8K);
+    }
+
+    #[test]
+    fn test_power_of_two_sizes_wrap() {
+        let mut window = BankWindow::new(4 * SIZE_16K, SIZE_32K, SIZE_16K);
+        window.select(0, SIZE_16K, 6);
+        assert_eq!(window.index(0x8000), 2 * SIZE_16K);
+        window.select(0, SIZE_32K, 3);
+        assert_eq!(window.index(0xC000), 3 * SIZE_16K);
+    }
+
+    #[test]
+    fn test_odd_sizes_mirror_the_upper_chip() {
+        // 384 KiB, a 256 KiB chip followed by a 128 KiB one
+        assert_eq!(mirror_bank(23, 24), 23);
+        assert_eq!(mirror_bank(25, 24), 17);
+        assert_eq!(mirror_bank(31, 24), 23);
+        assert_eq!(mirror_bank(33, 24), 1);
+        assert_eq!(mirror_bank(3, 3), 2);
+
+        let mut window = BankWindow::new(3 * SIZE_16K, SIZE_16K, SIZE_16K);
+        window.select(0, SIZE_16K, 3);
+        assert_eq!(window.index(0x8000), 2 * SIZE_16K);
+    }
+
+    #[test]
+    fn test_data_smaller_than_a_slot() {
+        // 16 KiB NROM in a 32 KiB slot and 2 KiB of CHR RAM in an 8 KiB one
+        let window = BankWindow::new(SIZE_16K, SIZE_32K, SIZE_32K);
+        assert_eq!(window.index(0xC123), 0x0123);
+
+        let window = BankWindow::new(SIZE_2K, SIZE_8K, SIZE_8K);
+        assert_eq!(window.index(0x1801), 0x0001);
+    }
+}
@@ -0,0 +1,124 @@
+// Camerica/Codemasters BF909x boards (iNES mapper 71). UNROM-like: a write to $C000-$FFFF
+// selects the 16 KiB bank at $8000 and the last bank is fixed at $C000. The BF9097 used by
+// Fire Hawk (submapper 1) adds single-screen mirroring on bit 4 of writes to $9000-$9FFF,
+// other boards ignore that range so it is always decoded.
+
+use crate::sys::interfaces::{INes, MirroringType, RomData};
+
+use super::bank::{BankWindow, SIZE_8K, SIZE_16K, SIZE_32K};
+use super::{Mapper, chr::Chr, window_registers};
+
+pub struct Camerica {
+    prg_rom: RomData,
+    chr: Chr,
+    prg_window: BankWindow,
+    chr_window: BankWindow,
+    mirroring: MirroringType,
+}
+
+impl Camerica {
+    pub fn new(ines: &INes) -> Self {
+        let chr = Chr::new(ines);
+        let mirroring = match ines.submapper {
+            1 => MirroringType::SingleScreenLower,
+            _ => ines.mirroring.clone(),
+        };
+
+        let mut prg_window = BankWindow::new(ines.prg_rom.len(), SIZE_32K, SIZE_16K);
+        prg_window.select(0, SIZE_16K, 0);
+        prg_window.select(1, SIZE_16K, prg_window.last_bank(SIZE_16K));
+
+        Camerica {
+            prg_rom: ines.prg_rom.clone(),
+            prg_window,
+            chr_window: BankWindow::new(chr.len(), SIZE_8K, SIZE_8K),
+            chr,
+            mirroring,
+        }
+    }
+}
+
+impl Mapper for Camerica {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_window.index(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x9000..=0x9FFF => {
+                self.mirroring = match value & 0x10 {
+                    0 => MirroringType::SingleScreenLower,
+                    _ => MirroringType::SingleScreenUpper,
+                }
+            }
+            0xC000..=0xFFFF => self.prg_window.select(0, SIZE_16K, (value & 0x0F) as usize),
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        self.chr[self.chr_window.index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        self.chr.write(self.chr_window.index(addr), value);
+    }
+
+    fn chr(&self) -> Option<&Chr> {
+        Some(&self.chr)
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut Chr> {
+        Some(&mut self.chr)
+    }
+
+    fn registers(&self) -> Vec<(String, u16)> {
+        let mut registers = window_registers("prg", &self.prg_window);
+        registers.extend(window_registers("chr", &self.chr_window));
+        registers
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        self.mirroring.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::test_rom::test_rom;
+
+    fn board(submapper: u8) -> Camerica {
+        let ines = test_rom(71)
+            .submapper(submapper)
+            .prg(SIZE_16K, 8)
+            .mirroring(MirroringType::Vertical);
+        Camerica::new(&ines.build())
+    }
+
+    #[test]
+    fn test_prg_banks() {
+        let mut camerica = board(0);
+        camerica.cpu_write(0xC000, 0x03);
+        assert_eq!(camerica.cpu_read(0x8000), Some(3));
+        assert_eq!(camerica.cpu_read(0xC000), Some(7));
+
+        // $8000-$BFFF writes are not the bank register
+        camerica.cpu_write(0x8000, 0x05);
+        assert_eq!(camerica.cpu_read(0x8000), Some(3));
+        assert_eq!(camerica.mirroring(), MirroringType::Vertical);
+    }
+
+    #[test]
+    fn test_fire_hawk_mirroring() {
+        let mut camerica = board(1);
+        assert_eq!(camerica.mirroring(), MirroringType::SingleScreenLower);
+        camerica.cpu_write(0x9000, 0x10);
+        assert_eq!(camerica.mirroring(), MirroringType::SingleScreenUpper);
+        camerica.cpu_write(0x9FFF, 0x00);
+        assert_eq!(camerica.mirroring(), MirroringType::SingleScreenLower);
+    }
+}
@@ -0,0 +1,87 @@
+// Pattern table memory behind a mapper's CHR windows. CHR ROM is shared with every other
+// instance running the same cartridge, CHR RAM belongs to this one.
+
+use std::ops::Deref;
+
+use crate::sys::interfaces::{INes, RomData};
+
+use super::CHR_RAM_SIZE;
+
+#[derive(Debug, Clone)]
+pub enum Chr {
+    Rom(RomData),
+    Ram(Vec<u8>),
+}
+
+impl Chr {
+    // CHR ROM from the cartridge, or 8 KiB of CHR RAM when the header declares none
+    pub fn new(ines: &INes) -> Self {
+        Self::with_ram_size(ines, CHR_RAM_SIZE)
+    }
+
+    // For boards that ship more CHR RAM than the usual 8 KiB
+    pub fn with_ram_size(ines: &INes, ram_size: usize) -> Self {
+        if ines.chr_rom.is_empty() {
+            Self::ram(ram_size)
+        } else {
+            Chr::Rom(ines.chr_rom.clone())
+        }
+    }
+
+    pub fn ram(size: usize) -> Self {
+        Chr::Ram(vec![0; size])
+    }
+
+    pub fn is_ram(&self) -> bool {
+        matches!(self, Chr::Ram(_))
+    }
+
+    // `None` for CHR ROM
+    pub fn ram_mut(&mut self) -> Option<&mut [u8]> {
+        match self {
+            Chr::Rom(_) => None,
+            Chr::Ram(ram) => Some(ram),
+        }
+    }
+
+    // Writes to CHR ROM are ignored
+    pub fn write(&mut self, index: usize, value: u8) {
+        if let Chr::Ram(ram) = self {
+            ram[index] = value;
+        }
+    }
+}
+
+impl Deref for Chr {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Chr::Rom(rom) => rom,
+            Chr::Ram(ram) => ram,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rom_is_shared_and_read_only() {
+        let ines = INes {
+            chr_rom: vec![0x11; 0x2000].into(),
+            ..Default::default()
+        };
+        let mut chr = Chr::new(&ines);
+        chr.write(0x0010, 0x22);
+        assert_eq!(chr[0x0010], 0x11);
+        assert!(!chr.is_ram());
+        assert!(matches!(&chr, Chr::Rom(rom) if RomData::ptr_eq(rom, &ines.chr_rom)));
+
+        let mut chr = Chr::new(&INes::default());
+        chr.write(0x0010, 0x22);
+        assert_eq!(chr[0x0010], 0x22);
+        assert_eq!(chr.len(), CHR_RAM_SIZE);
+    }
+}
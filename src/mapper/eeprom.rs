@@ -0,0 +1,372 @@
+// Bit-level model of the serial EEPROMs used for saves on Bandai boards.
+// The 24C02 follows the standard I2C protocol with a device address byte before the
+// word address, while the older X24C01 takes a 7-bit word address plus the R/W bit
+// right after the start condition, and clocks its address and data bytes LSB first.
+// The master drives SCL/SDA through `write_lines`.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EepromChip {
+    C24C01, // 128 bytes
+    C24C02, // 256 bytes
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Idle,
+    DeviceAddress,
+    WordAddress,
+    Write,
+    Read,
+}
+
+#[derive(Debug, Clone)]
+pub struct I2cEeprom {
+    chip: EepromChip,
+    data: Vec<u8>,
+    state: State,
+    next_state: State,
+    address: u8,
+    shift: u8,
+    bit_index: u8, // Clocks seen in the current byte, the 9th is the acknowledge
+    master_ack: bool,
+    scl: bool,
+    sda: bool,
+    output: bool, // SDA as driven by the EEPROM, high when released
+    dirty: bool,
+}
+
+impl I2cEeprom {
+    pub fn new(chip: EepromChip) -> Self {
+        let size = match chip {
+            EepromChip::C24C01 => 128,
+            EepromChip::C24C02 => 256,
+        };
+
+        I2cEeprom {
+            chip,
+            data: vec![0xFF; size],
+            state: State::Idle,
+            next_state: State::Idle,
+            address: 0,
+            shift: 0,
+            bit_index: 0,
+            master_ack: false,
+            scl: false,
+            sda: false,
+            output: true,
+            dirty: false,
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn load(&mut self, data: &[u8]) {
+        let len = data.len().min(self.data.len());
+        self.data[..len].copy_from_slice(&data[..len]);
+        self.dirty = false;
+    }
+
+    // Whether a write changed the contents since the last call
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+
+    // Level of the SDA line driven by the EEPROM
+    pub fn sda_out(&self) -> bool {
+        self.output
+    }
+
+    pub fn write_lines(&mut self, scl: bool, sda: bool) {
+        let (previous_scl, previous_sda) = (self.scl, self.sda);
+        self.scl = scl;
+        self.sda = sda;
+
+        if previous_scl && scl && previous_sda != sda {
+            // SDA changing while SCL is high is a start (falling) or stop (rising) condition
+            if sda { self.stop() } else { self.start() }
+        } else if !previous_scl && scl {
+            self.clock_rising(sda);
+        } else if previous_scl && !scl {
+            self.clock_falling();
+        }
+    }
+
+    fn start(&mut self) {
+        self.state = match self.chip {
+            EepromChip::C24C01 => State::WordAddress,
+            EepromChip::C24C02 => State::DeviceAddress,
+        };
+        self.bit_index = 0;
+        self.shift = 0;
+        self.output = true;
+    }
+
+    fn stop(&mut self) {
+        self.state = State::Idle;
+        self.output = true;
+    }
+
+    // Bits are counted on the rising edge, when the receiving side samples SDA
+    fn clock_rising(&mut self, sda: bool) {
+        match (self.state, self.bit_index) {
+            (State::Idle, _) => {}
+            (State::Read, 8) => self.master_ack = !sda,
+            (_, 0..=7) => {
+                self.shift = match self.chip {
+                    EepromChip::C24C01 => (self.shift >> 1) | ((sda as u8) << 7),
+                    EepromChip::C24C02 => (self.shift << 1) | sda as u8,
+                }
+            }
+            _ => {}
+        }
+
+        if self.state != State::Idle {
+            self.bit_index += 1;
+        }
+    }
+
+    // The EEPROM only changes its SDA output while SCL is low
+    fn clock_falling(&mut self) {
+        match (self.state, self.bit_index) {
+            (State::Idle, _) => {}
+            (State::Read, 0..=7) => self.output = self.read_bit(),
+            // Release SDA so the master can acknowledge, a NACK ends the read
+            (State::Read, 8) => self.output = true,
+            (State::Read, _) => {
+                if self.master_ack {
+                    self.address = self.next_address(self.address);
+                    self.bit_index = 0;
+                    self.output = self.read_bit();
+                } else {
+                    self.stop();
+                }
+            }
+            (_, 8) => {
+                let acknowledged = self.receive_byte();
+                self.output = !acknowledged;
+            }
+            (_, 9) => {
+                self.state = self.next_state;
+                self.bit_index = 0;
+                self.shift = 0;
+                self.output = if self.state == State::Read {
+                    self.read_bit()
+                } else {
+                    true
+                };
+            }
+            _ => {}
+        }
+    }
+
+    // Handle a complete byte from the master, returns whether it is acknowledged
+    fn receive_byte(&mut self) -> bool {
+        let byte = self.shift;
+
+        self.next_state = match (self.chip, self.state) {
+            (EepromChip::C24C02, State::DeviceAddress) => {
+                if byte & 0xF0 != 0xA0 {
+                    self.state = State::Idle;
+                    return false;
+                }
+                if byte & 0x01 != 0 {
+                    State::Read
+                } else {
+                    State::WordAddress
+                }
+            }
+            (EepromChip::C24C02, State::WordAddress) => {
+                self.address = byte;
+                State::Write
+            }
+            // A0-A6 then R/W
+            (EepromChip::C24C01, State::WordAddress) => {
+                self.address = byte & 0x7F;
+                if byte & 0x80 != 0 {
+                    State::Read
+                } else {
+                    State::Write
+                }
+            }
+            (_, State::Write) => {
+                let index = self.address as usize % self.data.len();
+                self.dirty |= self.data[index] != byte;
+                self.data[index] = byte;
+                self.address = self.next_page_address(self.address);
+                State::Write
+            }
+            (_, state) => state,
+        };
+
+        true
+    }
+
+    fn read_bit(&self) -> bool {
+        let byte = self.data[self.address as usize % self.data.len()];
+        let mask = match self.chip {
+            EepromChip::C24C01 => 0x01 << self.bit_index,
+            EepromChip::C24C02 => 0x80 >> self.bit_index,
+        };
+        byte & mask != 0
+    }
+
+    // Sequential reads walk the whole array
+    fn next_address(&self, address: u8) -> u8 {
+        ((address as usize + 1) % self.data.len()) as u8
+    }
+
+    // Writes wrap inside the page (4 bytes on the 24C01, 8 bytes on the 24C02)
+    fn next_page_address(&self, address: u8) -> u8 {
+        let page_mask = match self.chip {
+            EepromChip::C24C01 => 0x03,
+            EepromChip::C24C02 => 0x07,
+        };
+
+        (address & !page_mask) | (address.wrapping_add(1) & page_mask)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Master<'a> {
+        eeprom: &'a mut I2cEeprom,
+        lsb_first: bool, // X24C01 bit order
+    }
+
+    impl Master<'_> {
+        fn start(&mut self) {
+            self.eeprom.write_lines(false, true);
+            self.eeprom.write_lines(true, true);
+            self.eeprom.write_lines(true, false);
+            self.eeprom.write_lines(false, false);
+        }
+
+        fn stop(&mut self) {
+            self.eeprom.write_lines(false, false);
+            self.eeprom.write_lines(true, false);
+            self.eeprom.write_lines(true, true);
+        }
+
+        // Returns true when the EEPROM acknowledged the byte
+        fn send(&mut self, byte: u8) -> bool {
+            let bits: Vec<u8> = match self.lsb_first {
+                true => (0..8).collect(),
+                false => (0..8).rev().collect(),
+            };
+            for bit in bits {
+                let sda = byte & (1 << bit) != 0;
+                self.eeprom.write_lines(false, sda);
+                self.eeprom.write_lines(true, sda);
+                self.eeprom.write_lines(false, sda);
+            }
+
+            self.eeprom.write_lines(false, true);
+            self.eeprom.write_lines(true, true);
+            let ack = !self.eeprom.sda_out();
+            self.eeprom.write_lines(false, true);
+            ack
+        }
+
+        fn receive(&mut self, ack: bool) -> u8 {
+            let mut byte = 0;
+            for _ in 0..8 {
+                self.eeprom.write_lines(false, true);
+                self.eeprom.write_lines(true, true);
+                let sda = self.eeprom.sda_out() as u8;
+                byte = match self.lsb_first {
+                    true => (byte >> 1) | (sda << 7),
+                    false => (byte << 1) | sda,
+                };
+                self.eeprom.write_lines(false, true);
+            }
+
+            self.eeprom.write_lines(false, !ack);
+            self.eeprom.write_lines(true, !ack);
+            self.eeprom.write_lines(false, !ack);
+            byte
+        }
+    }
+
+    #[test]
+    fn test_24c02_write_and_sequential_read() {
+        let mut eeprom = I2cEeprom::new(EepromChip::C24C02);
+        let mut master = Master {
+            eeprom: &mut eeprom,
+            lsb_first: false,
+        };
+
+        master.start();
+        assert!(master.send(0xA0));
+        assert!(master.send(0x10));
+        assert!(master.send(0x12));
+        assert!(master.send(0x34));
+        master.stop();
+
+        master.start();
+        assert!(master.send(0xA0));
+        assert!(master.send(0x10));
+        master.start();
+        assert!(master.send(0xA1));
+        assert_eq!(master.receive(true), 0x12);
+        assert_eq!(master.receive(false), 0x34);
+        master.stop();
+
+        assert_eq!(&eeprom.data()[0x10..0x12], &[0x12, 0x34]);
+        assert!(eeprom.take_dirty());
+        assert!(!eeprom.take_dirty());
+    }
+
+    #[test]
+    fn test_24c02_ignores_other_devices_and_wraps_pages() {
+        let mut eeprom = I2cEeprom::new(EepromChip::C24C02);
+        let mut master = Master {
+            eeprom: &mut eeprom,
+            lsb_first: false,
+        };
+
+        master.start();
+        assert!(!master.send(0xB0));
+        master.stop();
+
+        master.start();
+        master.send(0xA0);
+        master.send(0x07);
+        master.send(0x01);
+        master.send(0x02); // Wraps to $00, not $08
+        master.stop();
+
+        assert_eq!(eeprom.data()[0x07], 0x01);
+        assert_eq!(eeprom.data()[0x00], 0x02);
+        assert_eq!(eeprom.data()[0x08], 0xFF);
+    }
+
+    #[test]
+    fn test_24c01_address_byte_includes_rw() {
+        let mut eeprom = I2cEeprom::new(EepromChip::C24C01);
+        let mut master = Master {
+            eeprom: &mut eeprom,
+            lsb_first: true,
+        };
+
+        // Address $07 then R/W in bit 7, the second byte wraps inside the 4-byte page
+        master.start();
+        assert!(master.send(0x07));
+        assert!(master.send(0x5A));
+        assert!(master.send(0x81));
+        master.stop();
+
+        master.start();
+        assert!(master.send(0x07 | 0x80));
+        assert_eq!(master.receive(true), 0x5A);
+        assert_eq!(master.receive(false), 0xFF);
+        master.stop();
+
+        assert_eq!(eeprom.data()[0x07], 0x5A);
+        assert_eq!(eeprom.data()[0x04], 0x81);
+        assert_eq!(eeprom.data()[0x08], 0xFF);
+    }
+}
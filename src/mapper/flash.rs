@@ -0,0 +1,176 @@
+// Command interface of the SST39SF040 parallel flash used by self-flashing homebrew boards.
+// Commands are unlocked by writing $AA to $5555 then $55 to $2AAA; programming can only
+// clear bits and erases set whole 4 KiB sectors (or the chip) back to $FF.
+
+const UNLOCK_ADDRESS_1: usize = 0x5555;
+const UNLOCK_ADDRESS_2: usize = 0x2AAA;
+const SECTOR_SIZE: usize = 0x1000;
+
+const MANUFACTURER_ID: u8 = 0xBF;
+const DEVICE_ID: u8 = 0xB7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Idle,
+    Unlock1,
+    Unlock2,
+    Program,
+    EraseUnlock,
+    EraseUnlock1,
+    EraseUnlock2,
+}
+
+#[derive(Debug, Clone)]
+pub struct Sst39Sf040 {
+    data: Vec<u8>,
+    state: State,
+    software_id: bool,
+    dirty: bool,   // Set once the contents differ from the loaded image
+    changed: bool, // Programmed or erased since the last `take_changed`
+}
+
+impl Sst39Sf040 {
+    pub fn new(data: Vec<u8>) -> Self {
+        Sst39Sf040 {
+            data,
+            state: State::Idle,
+            software_id: false,
+            dirty: false,
+            changed: false,
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    // Replace the contents with a previously saved image of the same size
+    pub fn load(&mut self, data: &[u8]) {
+        if data.len() == self.data.len() {
+            self.data.copy_from_slice(data);
+        }
+    }
+
+    pub fn read(&self, addr: usize) -> u8 {
+        if self.software_id {
+            return match addr & 0x01 {
+                0 => MANUFACTURER_ID,
+                _ => DEVICE_ID,
+            };
+        }
+
+        self.data[addr % self.data.len()]
+    }
+
+    // `addr` is the chip address, only A0-A14 take part in the unlock sequences
+    pub fn write(&mut self, addr: usize, value: u8) {
+        let command = addr & 0x7FFF;
+
+        self.state = match (self.state, command, value) {
+            (State::Program, _, _) => {
+                let index = addr % self.data.len();
+                self.data[index] &= value;
+                self.dirty = true;
+                self.changed = true;
+                State::Idle
+            }
+            (_, _, 0xF0) => {
+                self.software_id = false;
+                State::Idle
+            }
+            (State::Idle, UNLOCK_ADDRESS_1, 0xAA) => State::Unlock1,
+            (State::Unlock1, UNLOCK_ADDRESS_2, 0x55) => State::Unlock2,
+            (State::Unlock2, UNLOCK_ADDRESS_1, 0xA0) => State::Program,
+            (State::Unlock2, UNLOCK_ADDRESS_1, 0x80) => State::EraseUnlock,
+            (State::Unlock2, UNLOCK_ADDRESS_1, 0x90) => {
+                self.software_id = true;
+                State::Idle
+            }
+            (State::EraseUnlock, UNLOCK_ADDRESS_1, 0xAA) => State::EraseUnlock1,
+            (State::EraseUnlock1, UNLOCK_ADDRESS_2, 0x55) => State::EraseUnlock2,
+            (State::EraseUnlock2, UNLOCK_ADDRESS_1, 0x10) => {
+                self.data.fill(0xFF);
+                self.dirty = true;
+                self.changed = true;
+                State::Idle
+            }
+            (State::EraseUnlock2, _, 0x30) => {
+                let start = (addr % self.data.len()) & !(SECTOR_SIZE - 1);
+                let end = (start + SECTOR_SIZE).min(self.data.len());
+                self.data[start..end].fill(0xFF);
+                self.dirty = true;
+                self.changed = true;
+                State::Idle
+            }
+            // Anything else aborts the sequence
+            _ => State::Idle,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unlock(flash: &mut Sst39Sf040, command: u8) {
+        flash.write(0x5555, 0xAA);
+        flash.write(0x2AAA, 0x55);
+        flash.write(0x5555, command);
+    }
+
+    #[test]
+    fn test_program_only_clears_bits() {
+        let mut flash = Sst39Sf040::new(vec![0xFF; 0x10000]);
+
+        flash.write(0x1234, 0x00);
+        assert_eq!(flash.read(0x1234), 0xFF);
+        assert!(!flash.dirty());
+
+        unlock(&mut flash, 0xA0);
+        flash.write(0x1234, 0x5A);
+        assert_eq!(flash.read(0x1234), 0x5A);
+
+        unlock(&mut flash, 0xA0);
+        flash.write(0x1234, 0xF0);
+        assert_eq!(flash.read(0x1234), 0x50);
+        assert!(flash.dirty());
+    }
+
+    #[test]
+    fn test_sector_and_chip_erase() {
+        let mut flash = Sst39Sf040::new(vec![0x00; 0x10000]);
+
+        unlock(&mut flash, 0x80);
+        flash.write(0x5555, 0xAA);
+        flash.write(0x2AAA, 0x55);
+        flash.write(0x3456, 0x30);
+        assert_eq!(flash.read(0x2FFF), 0x00);
+        assert_eq!(flash.read(0x3000), 0xFF);
+        assert_eq!(flash.read(0x3FFF), 0xFF);
+        assert_eq!(flash.read(0x4000), 0x00);
+
+        unlock(&mut flash, 0x80);
+        unlock(&mut flash, 0x10);
+        assert!(flash.data().iter().all(|&byte| byte == 0xFF));
+    }
+
+    #[test]
+    fn test_software_id() {
+        let mut flash = Sst39Sf040::new(vec![0x00; 0x10000]);
+
+        unlock(&mut flash, 0x90);
+        assert_eq!(flash.read(0x0000), MANUFACTURER_ID);
+        assert_eq!(flash.read(0x0001), DEVICE_ID);
+
+        flash.write(0x0000, 0xF0);
+        assert_eq!(flash.read(0x0000), 0x00);
+    }
+}
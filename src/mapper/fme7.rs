@@ -0,0 +1,277 @@
+// Sunsoft FME-7 and Sunsoft 5A/5B (iNES mapper 69).
+// Registers are written through a command port ($8000) and a parameter port ($A000),
+// the 5B adds its sound chip behind $C000 (register select) and $E000 (data).
+
+use crate::apu::sunsoft5b::Sunsoft5bAudio;
+use crate::sys::interfaces::{INes, MirroringType, RomData};
+
+use super::bank::{BankWindow, SIZE_1K, SIZE_8K, SIZE_32K};
+use super::{Mapper, chr::Chr, named_registers, prg_ram::PrgRam, window_registers};
+
+pub struct Fme7 {
+    prg_rom: RomData,
+    prg_ram: PrgRam,
+    chr: Chr,
+    command: u8,
+    ram_control: u8, // Command 8, also the ROM bank at $6000
+    rom_6000_window: BankWindow,
+    prg_window: BankWindow,
+    chr_window: BankWindow,
+    mirroring: MirroringType,
+    irq_enabled: bool,
+    irq_counter_enabled: bool,
+    irq_counter: u16,
+    irq_pending: bool,
+    audio: Sunsoft5bAudio,
+}
+
+impl Fme7 {
+    pub fn new(ines: &INes) -> Self {
+        let chr = Chr::new(ines);
+
+        let mut rom_6000_window = BankWindow::new(ines.prg_rom.len(), SIZE_8K, SIZE_8K);
+        rom_6000_window.select(0, SIZE_8K, 0);
+        let mut prg_window = BankWindow::new(ines.prg_rom.len(), SIZE_32K, SIZE_8K);
+        for slot in 0..3 {
+            prg_window.select(slot, SIZE_8K, 0);
+        }
+        prg_window.select(3, SIZE_8K, prg_window.last_bank(SIZE_8K));
+        let mut chr_window = BankWindow::new(chr.len(), SIZE_8K, SIZE_1K);
+        for slot in 0..8 {
+            chr_window.select(slot, SIZE_1K, 0);
+        }
+
+        Fme7 {
+            prg_rom: ines.prg_rom.clone(),
+            prg_ram: PrgRam::new(ines),
+            chr,
+            command: 0,
+            ram_control: 0,
+            rom_6000_window,
+            prg_window,
+            chr_window,
+            mirroring: MirroringType::Vertical,
+            irq_enabled: false,
+            irq_counter_enabled: false,
+            irq_counter: 0,
+            irq_pending: false,
+            audio: Sunsoft5bAudio::new(),
+        }
+    }
+
+    // Command 8: bit 7 enables RAM, bit 6 selects RAM instead of ROM at $6000-$7FFF
+    fn ram_selected(&self) -> bool {
+        self.ram_control & 0x40 != 0
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_control & 0x80 != 0
+    }
+
+    fn write_parameter(&mut self, value: u8) {
+        match self.command {
+            0x00..=0x07 => self
+                .chr_window
+                .select(self.command as usize, SIZE_1K, value as usize),
+            0x08 => {
+                self.ram_control = value;
+                self.rom_6000_window
+                    .select(0, SIZE_8K, (value & 0x3F) as usize);
+            }
+            0x09..=0x0B => {
+                let slot = (self.command - 0x09) as usize;
+                self.prg_window
+                    .select(slot, SIZE_8K, (value & 0x3F) as usize);
+            }
+            0x0C => {
+                self.mirroring = match value & 0x03 {
+                    0 => MirroringType::Vertical,
+                    1 => MirroringType::Horizontal,
+                    2 => MirroringType::SingleScreenLower,
+                    _ => MirroringType::SingleScreenUpper,
+                }
+            }
+            0x0D => {
+                self.irq_enabled = value & 0x01 != 0;
+                self.irq_counter_enabled = value & 0x80 != 0;
+                self.irq_pending = false;
+            }
+            0x0E => self.irq_counter = (self.irq_counter & 0xFF00) | value as u16,
+            _ => self.irq_counter = (self.irq_counter & 0x00FF) | ((value as u16) << 8),
+        }
+    }
+}
+
+impl Mapper for Fme7 {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF if self.ram_selected() && self.ram_enabled() => self.prg_ram.read(addr),
+            0x6000..=0x7FFF if self.ram_selected() => None,
+            0x6000..=0x7FFF => Some(self.prg_rom[self.rom_6000_window.index(addr)]),
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_window.index(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.ram_selected() && self.ram_enabled() => {
+                self.prg_ram.write(addr, value)
+            }
+            0x8000..=0x9FFF => self.command = value & 0x0F,
+            0xA000..=0xBFFF => self.write_parameter(value),
+            0xC000..=0xDFFF => self.audio.write_address(value),
+            0xE000..=0xFFFF => self.audio.write_data(value),
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        self.chr[self.chr_window.index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        self.chr.write(self.chr_window.index(addr), value);
+    }
+
+    fn chr(&self) -> Option<&Chr> {
+        Some(&self.chr)
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut Chr> {
+        Some(&mut self.chr)
+    }
+
+    fn registers(&self) -> Vec<(String, u16)> {
+        let mut registers = window_registers("prg", &self.prg_window);
+        registers.extend(window_registers("chr", &self.chr_window));
+        registers.extend(named_registers([
+            ("command", self.command as u16),
+            ("ram_control", self.ram_control as u16),
+            ("irq_enabled", self.irq_enabled as u16),
+            ("irq_counter_enabled", self.irq_counter_enabled as u16),
+            ("irq_counter", self.irq_counter),
+        ]));
+        registers
+    }
+
+    fn prg_ram(&self) -> Option<&PrgRam> {
+        Some(&self.prg_ram)
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut PrgRam> {
+        Some(&mut self.prg_ram)
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        self.mirroring.clone()
+    }
+
+    // The 16-bit counter decrements every CPU cycle and fires when it wraps from $0000 to $FFFF
+    fn cpu_clock(&mut self) {
+        if self.irq_counter_enabled {
+            self.irq_counter = self.irq_counter.wrapping_sub(1);
+            if self.irq_counter == 0xFFFF && self.irq_enabled {
+                self.irq_pending = true;
+            }
+        }
+
+        self.audio.clock();
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.audio.output()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apu::sunsoft5b::FULL_VOLUME_LEVEL;
+    use crate::mapper::test_rom::test_rom;
+
+    fn board() -> Fme7 {
+        Fme7::new(&test_rom(69).prg(SIZE_8K, 32).chr(SIZE_1K, 256).build())
+    }
+
+    fn command(fme7: &mut Fme7, command: u8, parameter: u8) {
+        fme7.cpu_write(0x8000, command);
+        fme7.cpu_write(0xA000, parameter);
+    }
+
+    #[test]
+    fn test_prg_and_chr_banking() {
+        let mut fme7 = board();
+        command(&mut fme7, 0x09, 0x04);
+        command(&mut fme7, 0x0B, 0x1E);
+        command(&mut fme7, 0x07, 0xC3);
+        assert_eq!(fme7.cpu_read(0x8000), Some(4));
+        assert_eq!(fme7.cpu_read(0xC000), Some(30));
+        assert_eq!(fme7.cpu_read(0xE000), Some(31));
+        assert_eq!(fme7.ppu_read(0x1C00), 0xC3);
+    }
+
+    #[test]
+    fn test_prg_ram_and_rom_at_6000() {
+        let mut fme7 = board();
+        command(&mut fme7, 0x08, 0x05);
+        assert_eq!(fme7.cpu_read(0x6000), Some(5));
+
+        command(&mut fme7, 0x08, 0xC0);
+        fme7.cpu_write(0x6123, 0x77);
+        assert_eq!(fme7.cpu_read(0x6123), Some(0x77));
+
+        // Selected but disabled RAM ignores writes
+        command(&mut fme7, 0x08, 0x40);
+        fme7.cpu_write(0x6123, 0x11);
+        command(&mut fme7, 0x08, 0xC0);
+        assert_eq!(fme7.cpu_read(0x6123), Some(0x77));
+    }
+
+    #[test]
+    fn test_irq_fires_on_counter_wrap() {
+        let mut fme7 = board();
+        command(&mut fme7, 0x0E, 0x02);
+        command(&mut fme7, 0x0F, 0x00);
+        command(&mut fme7, 0x0D, 0x81);
+
+        fme7.cpu_clock();
+        fme7.cpu_clock();
+        assert!(!fme7.irq_pending());
+        fme7.cpu_clock();
+        assert!(fme7.irq_pending());
+
+        command(&mut fme7, 0x0D, 0x81);
+        assert!(!fme7.irq_pending());
+    }
+
+    #[test]
+    fn test_5b_tone_channel() {
+        let mut fme7 = board();
+        let mut audio = |register: u8, value: u8| {
+            fme7.cpu_write(0xC000, register);
+            fme7.cpu_write(0xE000, value);
+        };
+        audio(0x00, 0x02); // Channel A period 2
+        audio(0x07, 0x3E); // Only tone A enabled
+        audio(0x08, 0x0F);
+
+        let samples: Vec<f32> = (0..16 * 8)
+            .map(|_| {
+                fme7.cpu_clock();
+                fme7.audio_output()
+            })
+            .collect();
+        let toggles = samples.windows(2).filter(|pair| pair[0] != pair[1]).count();
+        assert_eq!(toggles, 4);
+        assert!(
+            samples
+                .iter()
+                .any(|sample| *sample >= FULL_VOLUME_LEVEL - 0.5)
+        );
+    }
+}
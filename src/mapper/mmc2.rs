@@ -0,0 +1,235 @@
+// Nintendo MMC2 (iNES mapper 9) and MMC4 (iNES mapper 10).
+// Each pattern table has two CHR banks and a latch picking between them. The latch flips
+// when the PPU fetches tile $FD or $FE from that table, which games use to change banks
+// mid-frame without any CPU work.
+
+use crate::sys::interfaces::{INes, MirroringType, RomData};
+
+use super::bank::{BankWindow, SIZE_4K, SIZE_8K, SIZE_16K, SIZE_32K};
+use super::{Mapper, chr::Chr, named_registers, prg_ram::PrgRam, window_registers};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mmc2Chip {
+    Mmc2,
+    Mmc4,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Latch {
+    Fd,
+    Fe,
+}
+
+pub struct Mmc2 {
+    chip: Mmc2Chip,
+    prg_rom: RomData,
+    prg_ram: PrgRam,
+    chr: Chr,
+    chr_banks: [[u8; 2]; 2], // [pattern table][FD, FE]
+    latches: [Latch; 2],
+    mirroring: MirroringType,
+    prg_window: BankWindow,
+    chr_window: BankWindow,
+}
+
+impl Mmc2 {
+    pub fn new(ines: &INes) -> Self {
+        let chr = Chr::new(ines);
+        let chip = match ines.mapper {
+            10 => Mmc2Chip::Mmc4,
+            _ => Mmc2Chip::Mmc2,
+        };
+
+        let mut mmc2 = Mmc2 {
+            chip,
+            prg_ram: match chip {
+                Mmc2Chip::Mmc4 => PrgRam::new(ines),
+                Mmc2Chip::Mmc2 => PrgRam::default(),
+            },
+            chr_banks: [[0; 2]; 2],
+            latches: [Latch::Fe; 2],
+            mirroring: ines.mirroring.clone(),
+            prg_window: BankWindow::new(ines.prg_rom.len(), SIZE_32K, SIZE_8K),
+            chr_window: BankWindow::new(chr.len(), SIZE_8K, SIZE_4K),
+            prg_rom: ines.prg_rom.clone(),
+            chr,
+        };
+        mmc2.write_prg_bank(0);
+        mmc2.update_chr_banks();
+        mmc2
+    }
+
+    // MMC2 switches 8 KiB at $8000 and fixes the last three banks, MMC4 switches 16 KiB
+    fn write_prg_bank(&mut self, bank: usize) {
+        match self.chip {
+            Mmc2Chip::Mmc2 => {
+                let last = self.prg_window.last_bank(SIZE_8K);
+                self.prg_window.select(0, SIZE_8K, bank);
+                for slot in 1..4 {
+                    self.prg_window
+                        .select(slot, SIZE_8K, (last + slot).saturating_sub(3));
+                }
+            }
+            Mmc2Chip::Mmc4 => {
+                self.prg_window.select(0, SIZE_16K, bank);
+                self.prg_window
+                    .select(2, SIZE_16K, self.prg_window.last_bank(SIZE_16K));
+            }
+        }
+    }
+
+    fn update_chr_banks(&mut self) {
+        for table in 0..2 {
+            let bank = self.chr_banks[table][self.latches[table] as usize];
+            self.chr_window.select(table, SIZE_4K, bank as usize);
+        }
+    }
+}
+
+impl Mapper for Mmc2 {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match (self.chip, addr) {
+            (Mmc2Chip::Mmc4, 0x6000..=0x7FFF) => self.prg_ram.read(addr),
+            (_, 0x8000..=0xFFFF) => Some(self.prg_rom[self.prg_window.index(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match (self.chip, addr) {
+            (Mmc2Chip::Mmc4, 0x6000..=0x7FFF) => self.prg_ram.write(addr, value),
+            (_, 0xA000..=0xAFFF) => self.write_prg_bank((value & 0x0F) as usize),
+            (_, 0xB000..=0xEFFF) => {
+                let register = ((addr - 0xB000) >> 12) as usize;
+                self.chr_banks[register >> 1][register & 0x01] = value & 0x1F;
+                self.update_chr_banks();
+            }
+            (_, 0xF000..=0xFFFF) => {
+                self.mirroring = if value & 0x01 == 0 {
+                    MirroringType::Vertical
+                } else {
+                    MirroringType::Horizontal
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        self.chr[self.chr_window.index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        self.chr.write(self.chr_window.index(addr), value);
+    }
+
+    fn chr(&self) -> Option<&Chr> {
+        Some(&self.chr)
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut Chr> {
+        Some(&mut self.chr)
+    }
+
+    fn registers(&self) -> Vec<(String, u16)> {
+        let mut registers = window_registers("prg", &self.prg_window);
+        registers.extend(window_registers("chr", &self.chr_window));
+        registers.extend(named_registers([
+            ("latch0", self.latches[0] as u16),
+            ("latch1", self.latches[1] as u16),
+        ]));
+        registers
+    }
+
+    fn prg_ram(&self) -> Option<&PrgRam> {
+        Some(&self.prg_ram)
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut PrgRam> {
+        Some(&mut self.prg_ram)
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        self.mirroring.clone()
+    }
+
+    // The latch switches after the fetch of $xFD8/$xFE8, the MMC2 only matches a single
+    // address for the left pattern table while the MMC4 matches the whole 8 byte row
+    fn ppu_address(&mut self, addr: u16) {
+        let exact_match = self.chip == Mmc2Chip::Mmc2 && addr < 0x1000;
+        let row = if exact_match { addr } else { addr & 0xFFF8 };
+
+        let latch = match row & 0x0FFF {
+            0x0FD8 => Latch::Fd,
+            0x0FE8 => Latch::Fe,
+            _ => return,
+        };
+        self.latches[((addr >> 12) & 0x01) as usize] = latch;
+        self.update_chr_banks();
+    }
+
+    fn watches_ppu_bus(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::test_rom::test_rom;
+
+    fn board(mapper: u8) -> Mmc2 {
+        let ines = test_rom(mapper)
+            .prg(SIZE_8K, 16)
+            .chr(SIZE_4K, 32)
+            .battery(true);
+        Mmc2::new(&ines.build())
+    }
+
+    #[test]
+    fn test_mmc2_prg_layout() {
+        let mut mmc2 = board(9);
+        mmc2.cpu_write(0xA000, 0x05);
+        assert_eq!(mmc2.cpu_read(0x8000), Some(5));
+        assert_eq!(mmc2.cpu_read(0xA000), Some(13));
+        assert_eq!(mmc2.cpu_read(0xC000), Some(14));
+        assert_eq!(mmc2.cpu_read(0xE000), Some(15));
+    }
+
+    #[test]
+    fn test_latches_switch_after_fd_fe_fetches() {
+        let mut mmc2 = board(9);
+        mmc2.cpu_write(0xB000, 1); // $0000 FD
+        mmc2.cpu_write(0xC000, 2); // $0000 FE
+        mmc2.cpu_write(0xD000, 3); // $1000 FD
+        mmc2.cpu_write(0xE000, 4); // $1000 FE
+        assert_eq!(mmc2.ppu_read(0x0000), 2);
+        assert_eq!(mmc2.ppu_read(0x1000), 4);
+
+        // MMC2 only latches on exactly $0FD8 for the left table
+        mmc2.ppu_address(0x0FD9);
+        assert_eq!(mmc2.ppu_read(0x0000), 2);
+        mmc2.ppu_address(0x0FD8);
+        assert_eq!(mmc2.ppu_read(0x0000), 1);
+
+        mmc2.ppu_address(0x1FDB);
+        assert_eq!(mmc2.ppu_read(0x1000), 3);
+        mmc2.ppu_address(0x1FEF);
+        assert_eq!(mmc2.ppu_read(0x1000), 4);
+    }
+
+    #[test]
+    fn test_mmc4_prg_ram_and_latch_ranges() {
+        let mut mmc4 = board(10);
+        mmc4.cpu_write(0xA000, 0x02);
+        assert_eq!(mmc4.cpu_read(0x8000), Some(4));
+        assert_eq!(mmc4.cpu_read(0xC000), Some(14));
+
+        mmc4.cpu_write(0xB000, 7);
+        mmc4.ppu_address(0x0FDD);
+        assert_eq!(mmc4.ppu_read(0x0000), 7);
+
+        mmc4.cpu_write(0x6000, 0x99);
+        assert_eq!(mmc4.battery_ram().unwrap()[0], 0x99);
+    }
+}
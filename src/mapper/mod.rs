@@ -0,0 +1,198 @@
+pub mod action53;
+pub mod bandai;
+pub mod bank;
+pub mod camerica;
+pub mod chr;
+pub mod eeprom;
+pub mod flash;
+pub mod fme7;
+pub mod mmc2;
+pub mod multicart;
+pub mod namco108;
+pub mod namco163;
+pub mod nina;
+pub mod nrom;
+pub mod prg_ram;
+pub mod registry;
+pub mod sachen8259;
+pub mod unrom512;
+pub mod vrc2_4;
+pub mod vrc6;
+pub mod vrc7;
+pub mod vrc_irq;
+
+use crate::sys::interfaces::MirroringType;
+
+use bank::BankWindow;
+
+use chr::Chr;
+use prg_ram::PrgRam;
+
+pub use registry::new_mapper;
+
+pub const PRG_RAM_SIZE: usize = 8192; // 8 KiB = 0x7FFF - 0x6000 + 1
+pub const CHR_RAM_SIZE: usize = 8192; // 8 KiB = 0x1FFF - 0x0000 + 1
+
+// Where the PPU fetches an address in $0000-$2FFF from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VramSource {
+    Cartridge,
+    Ciram(u8), // Console nametable RAM page, 2 and 3 are the extra four-screen RAM
+}
+
+// Cartridge hardware seen by the CPU ($4020-$FFFF) and by the PPU ($0000-$1FFF)
+pub trait Mapper {
+    // Reads without side effects, for debuggers, disassemblers and tracers. `None` where the
+    // board drives nothing and the CPU sees open bus
+    fn cpu_peek(&self, addr: u16) -> Option<u8>;
+    fn cpu_write(&mut self, addr: u16, value: u8);
+    fn ppu_read(&self, addr: u16) -> u8;
+    fn ppu_write(&mut self, addr: u16, value: u8);
+    fn mirroring(&self) -> MirroringType;
+
+    // CPU reads, boards whose registers change state when read override this
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        self.cpu_peek(addr)
+    }
+
+    // Pattern tables come from the cartridge and nametables from CIRAM laid out by `mirroring`,
+    // boards that map CIRAM or CHR anywhere in $0000-$2FFF override this
+    fn vram_source(&self, addr: u16) -> VramSource {
+        match addr & 0x3FFF {
+            0x0000..=0x1FFF => VramSource::Cartridge,
+            _ => VramSource::Ciram(self.mirroring().ciram_page(addr)),
+        }
+    }
+
+    // Called when the console's reset button is pressed, not at power-on
+    fn reset(&mut self) {}
+
+    // Called once per CPU cycle, drives IRQ counters and prescalers
+    fn cpu_clock(&mut self) {}
+
+    // Called after every PPU fetch with its address, for boards that watch the PPU bus
+    fn ppu_address(&mut self, _addr: u16) {}
+
+    // Boards that override `ppu_address` keep the bus in lockstep, catch-up sync skips the
+    // dots the PPU fetches on and would never report them
+    fn watches_ppu_bus(&self) -> bool {
+        false
+    }
+
+    fn irq_pending(&self) -> bool {
+        false
+    }
+
+    // Expansion audio, in 2A03 pulse volume steps (see `apu::mixer`)
+    fn audio_output(&self) -> f32 {
+        0.0
+    }
+
+    // PRG RAM at $6000-$7FFF, boards that have it get the battery handling below for free
+    fn prg_ram(&self) -> Option<&PrgRam> {
+        None
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut PrgRam> {
+        None
+    }
+
+    // Pattern table memory, `None` for boards that compute their CHR
+    fn chr(&self) -> Option<&Chr> {
+        None
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut Chr> {
+        None
+    }
+
+    // Register values by name, for debuggers and memory dumps
+    fn registers(&self) -> Vec<(String, u16)> {
+        Vec::new()
+    }
+
+    // Battery backed memory to persist between sessions, `None` for boards without a battery
+    fn battery_ram(&self) -> Option<Vec<u8>> {
+        self.prg_ram().and_then(PrgRam::battery_ram)
+    }
+
+    fn load_battery_ram(&mut self, data: &[u8]) {
+        if let Some(prg_ram) = self.prg_ram_mut() {
+            prg_ram.load(data);
+        }
+    }
+
+    // Whether battery memory changed since the last call, for periodic save flushes
+    fn take_battery_ram_dirty(&mut self) -> bool {
+        self.prg_ram_mut().is_some_and(PrgRam::take_dirty)
+    }
+}
+
+// `Mapper::registers` entries for the bank at each slot of `window`: `prg0`, `prg1`...
+pub(crate) fn window_registers(name: &str, window: &BankWindow) -> Vec<(String, u16)> {
+    let banks = window.banks().into_iter().enumerate();
+    banks
+        .map(|(slot, bank)| (format!("{}{}", name, slot), bank as u16))
+        .collect()
+}
+
+// `Mapper::registers` entries for plain fields
+pub(crate) fn named_registers<const N: usize>(fields: [(&str, u16); N]) -> Vec<(String, u16)> {
+    fields
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect()
+}
+
+// Mapper test fixtures: ROMs whose banks are filled with their own number, so a read tells
+// which bank is mapped, and a builder for the header fields the boards look at
+#[cfg(test)]
+pub(crate) mod test_rom {
+    use crate::sys::interfaces::{INes, MirroringType, RomData};
+
+    pub fn banked_rom(bank_size: usize, count: usize) -> RomData {
+        (0..count)
+            .flat_map(|bank| vec![bank as u8; bank_size])
+            .collect()
+    }
+
+    pub struct TestRom(INes);
+
+    pub fn test_rom(mapper: u8) -> TestRom {
+        TestRom(INes {
+            mapper,
+            ..Default::default()
+        })
+    }
+
+    impl TestRom {
+        pub fn submapper(mut self, submapper: u8) -> Self {
+            self.0.submapper = submapper;
+            self
+        }
+
+        pub fn prg(mut self, bank_size: usize, count: usize) -> Self {
+            self.0.prg_rom = banked_rom(bank_size, count);
+            self
+        }
+
+        pub fn chr(mut self, bank_size: usize, count: usize) -> Self {
+            self.0.chr_rom = banked_rom(bank_size, count);
+            self
+        }
+
+        pub fn battery(mut self, battery: bool) -> Self {
+            self.0.battery = battery;
+            self
+        }
+
+        pub fn mirroring(mut self, mirroring: MirroringType) -> Self {
+            self.0.mirroring = mirroring;
+            self
+        }
+
+        pub fn build(self) -> INes {
+            self.0
+        }
+    }
+}
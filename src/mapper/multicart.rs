@@ -0,0 +1,406 @@
+// Pirate and licensed multicart boards (iNES mappers 15, 41, 57, 58, 60, 225, 226 and 227).
+// They all end up mapping NROM/UNROM style layouts: an outer bank picks the game and a
+// few bits inside it pick the 16/32 KiB PRG and 8 KiB CHR banks. Most of them clear their
+// latches on reset so the menu comes back, mapper 60 instead steps to the next game.
+
+use crate::sys::interfaces::{INes, MirroringType, RomData};
+
+use super::bank::{BankWindow, SIZE_8K, SIZE_16K, SIZE_32K};
+use super::{Mapper, chr::Chr, named_registers, prg_ram::PrgRam, window_registers};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MulticartBoard {
+    K1029,      // 15, 100-in-1 Contra Function 16
+    Caltron,    // 41, Caltron 6-in-1
+    Gk57,       // 57, GK 6-in-1 / 54-in-1
+    Gk58,       // 58, GK 68-in-1 / Study & Game 32-in-1
+    ResetBased, // 60, reset-based 4-in-1
+    Bmc225,     // 225, 52/64-in-1
+    Bmc226,     // 226, 76-in-1
+    Bmc227,     // 227, 1200-in-1
+}
+
+pub struct Multicart {
+    board: MulticartBoard,
+    prg_rom: RomData,
+    prg_ram: PrgRam,
+    chr: Chr,
+    chr_writable: bool,
+    registers: [u16; 2], // Raw register or address latches, the meaning depends on the board
+    reset_count: u8,
+    prg_window: BankWindow,
+    chr_window: BankWindow,
+    mirroring: MirroringType,
+    nibble_ram: [u8; 4], // Mapper 225, 4-bit RAM at $5800-$5FFF
+}
+
+impl Multicart {
+    pub fn new(ines: &INes) -> Self {
+        let board = match ines.mapper {
+            15 => MulticartBoard::K1029,
+            41 => MulticartBoard::Caltron,
+            57 => MulticartBoard::Gk57,
+            58 => MulticartBoard::Gk58,
+            60 => MulticartBoard::ResetBased,
+            225 => MulticartBoard::Bmc225,
+            226 => MulticartBoard::Bmc226,
+            _ => MulticartBoard::Bmc227,
+        };
+        let chr = Chr::new(ines);
+        let prg_ram = match board {
+            MulticartBoard::K1029 => PrgRam::new(ines),
+            _ => PrgRam::default(),
+        };
+
+        let chr_len = chr.len();
+        let chr_writable = chr.is_ram();
+
+        let mut multicart = Multicart {
+            board,
+            prg_rom: ines.prg_rom.clone(),
+            prg_ram,
+            chr,
+            chr_writable,
+            registers: [0; 2],
+            reset_count: 0,
+            prg_window: BankWindow::new(ines.prg_rom.len(), SIZE_32K, SIZE_8K),
+            chr_window: BankWindow::new(chr_len, SIZE_8K, SIZE_8K),
+            mirroring: ines.mirroring.clone(),
+            nibble_ram: [0; 4],
+        };
+        multicart.update();
+        multicart
+    }
+
+    fn set_prg_16k(&mut self, low: usize, high: usize) {
+        self.prg_window.select(0, SIZE_16K, low);
+        self.prg_window.select(2, SIZE_16K, high);
+    }
+
+    fn set_prg_32k(&mut self, bank: usize) {
+        self.prg_window.select(0, SIZE_32K, bank);
+    }
+
+    fn set_mirroring(&mut self, horizontal: bool) {
+        self.mirroring = match horizontal {
+            true => MirroringType::Horizontal,
+            false => MirroringType::Vertical,
+        };
+    }
+
+    // Recompute the banks from the latches
+    fn update(&mut self) {
+        let [low, high] = self.registers.map(|register| register as usize);
+
+        match self.board {
+            // `low` is the mode from A0-A1, `high` the written value
+            MulticartBoard::K1029 => {
+                let bank = high & 0x3F;
+                match low {
+                    0 => self.set_prg_16k(bank & !0x01, bank | 0x01),
+                    1 => self.set_prg_16k(bank, bank | 0x07),
+                    2 => {
+                        for slot in 0..4 {
+                            self.prg_window
+                                .select(slot, SIZE_8K, (bank << 1) | (high >> 7));
+                        }
+                    }
+                    _ => self.set_prg_16k(bank, bank),
+                }
+                self.set_mirroring(high & 0x40 != 0);
+                // The NROM modes write-protect CHR RAM
+                self.chr_writable = self.chr.is_ram() && (low == 1 || low == 2);
+            }
+            // `low` is the $6000 address latch, `high` the inner CHR bank
+            MulticartBoard::Caltron => {
+                self.set_prg_32k(low & 0x07);
+                self.chr_window
+                    .select(0, SIZE_8K, ((low >> 1) & 0x0C) | (high & 0x03));
+                self.set_mirroring(low & 0x20 != 0);
+            }
+            // `low` is the $8000 register, `high` the $8800 register
+            MulticartBoard::Gk57 => {
+                let bank = (high >> 5) & 0x07;
+                match high & 0x10 {
+                    0 => self.set_prg_16k(bank, bank),
+                    _ => self.set_prg_32k(bank >> 1),
+                }
+                self.chr_window
+                    .select(0, SIZE_8K, ((low & 0x40) >> 3) | ((low | high) & 0x07));
+                self.set_mirroring(high & 0x08 != 0);
+            }
+            // Address latch
+            MulticartBoard::Gk58 => {
+                let bank = low & 0x07;
+                match low & 0x40 {
+                    0 => self.set_prg_32k(bank >> 1),
+                    _ => self.set_prg_16k(bank, bank),
+                }
+                self.chr_window.select(0, SIZE_8K, (low >> 3) & 0x07);
+                self.set_mirroring(low & 0x80 != 0);
+            }
+            MulticartBoard::ResetBased => {
+                let game = self.reset_count as usize;
+                self.set_prg_16k(game, game);
+                self.chr_window.select(0, SIZE_8K, game);
+            }
+            // Address latch, A14 is the outer bank for both PRG and CHR
+            MulticartBoard::Bmc225 => {
+                let outer = (low >> 8) & 0x40;
+                let bank = ((low >> 6) & 0x3F) | outer;
+                match low & 0x1000 {
+                    0 => self.set_prg_32k(bank >> 1),
+                    _ => self.set_prg_16k(bank, bank),
+                }
+                self.chr_window.select(0, SIZE_8K, (low & 0x3F) | outer);
+                self.set_mirroring(low & 0x2000 != 0);
+            }
+            // `low` is the $8000 register, `high` the $8001 register
+            MulticartBoard::Bmc226 => {
+                let bank = (low & 0x1F) | ((low & 0x80) >> 2) | ((high & 0x01) << 6);
+                match low & 0x20 {
+                    0 => self.set_prg_32k(bank >> 1),
+                    _ => self.set_prg_16k(bank, bank),
+                }
+                // Bit 6 set is vertical on this board
+                self.set_mirroring(low & 0x40 == 0);
+            }
+            // Address latch: S (A0) 32 KiB, O (A7) NROM or UNROM, L (A9) last bank
+            MulticartBoard::Bmc227 => {
+                let bank = ((low >> 2) & 0x1F) | ((low & 0x100) >> 3);
+                let size_32k = low & 0x01 != 0;
+                let last = low & 0x200 != 0;
+
+                match (low & 0x80 != 0, size_32k, last) {
+                    (true, true, _) => self.set_prg_32k(bank >> 1),
+                    (true, false, _) => self.set_prg_16k(bank, bank),
+                    (false, true, true) => self.set_prg_16k(bank & 0x3E, bank | 0x07),
+                    (false, true, false) => self.set_prg_16k(bank & 0x3E, bank & 0x38),
+                    (false, false, true) => self.set_prg_16k(bank, bank | 0x07),
+                    (false, false, false) => self.set_prg_16k(bank, bank & 0x38),
+                }
+                self.set_mirroring(low & 0x02 != 0);
+            }
+        }
+    }
+}
+
+impl Mapper for Multicart {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x5800..=0x5FFF if self.board == MulticartBoard::Bmc225 => {
+                Some(self.nibble_ram[addr as usize & 0x03])
+            }
+            0x6000..=0x7FFF => self.prg_ram.read(addr),
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_window.index(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match (self.board, addr) {
+            (MulticartBoard::Bmc225, 0x5800..=0x5FFF) => {
+                self.nibble_ram[addr as usize & 0x03] = value & 0x0F
+            }
+            (MulticartBoard::K1029, 0x6000..=0x7FFF) => self.prg_ram.write(addr, value),
+            (MulticartBoard::K1029, 0x8000..=0xFFFF) => {
+                self.registers = [addr & 0x03, value as u16];
+            }
+            (MulticartBoard::Caltron, 0x6000..=0x67FF) => self.registers[0] = addr & 0x3F,
+            // The inner CHR bank only listens while bit 2 of the outer latch is set
+            (MulticartBoard::Caltron, 0x8000..=0xFFFF) if self.registers[0] & 0x04 != 0 => {
+                self.registers[1] = value as u16;
+            }
+            (MulticartBoard::Gk57, 0x8000..=0xFFFF) => {
+                self.registers[(addr >> 11) as usize & 0x01] = value as u16;
+            }
+            (
+                MulticartBoard::Gk58 | MulticartBoard::Bmc225 | MulticartBoard::Bmc227,
+                0x8000..=0xFFFF,
+            ) => {
+                self.registers[0] = addr;
+            }
+            (MulticartBoard::Bmc226, 0x8000..=0xFFFF) => {
+                self.registers[addr as usize & 0x01] = value as u16;
+            }
+            _ => return,
+        }
+
+        self.update();
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        self.chr[self.chr_window.index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_writable {
+            self.chr.write(self.chr_window.index(addr), value);
+        }
+    }
+
+    fn chr(&self) -> Option<&Chr> {
+        Some(&self.chr)
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut Chr> {
+        Some(&mut self.chr)
+    }
+
+    fn registers(&self) -> Vec<(String, u16)> {
+        let mut registers = window_registers("prg", &self.prg_window);
+        registers.extend(window_registers("chr", &self.chr_window));
+        registers.extend(named_registers([
+            ("register0", self.registers[0]),
+            ("register1", self.registers[1]),
+            ("reset_count", self.reset_count as u16),
+        ]));
+        registers
+    }
+
+    fn prg_ram(&self) -> Option<&PrgRam> {
+        Some(&self.prg_ram)
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut PrgRam> {
+        Some(&mut self.prg_ram)
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        self.mirroring.clone()
+    }
+
+    fn reset(&mut self) {
+        match self.board {
+            MulticartBoard::ResetBased => self.reset_count = (self.reset_count + 1) & 0x03,
+            _ => self.registers = [0; 2],
+        }
+
+        self.update();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::test_rom::test_rom;
+
+    fn board(mapper: u8, prg_16k_banks: usize, chr_banks: usize) -> Multicart {
+        let ines = test_rom(mapper)
+            .prg(SIZE_16K, prg_16k_banks)
+            .chr(SIZE_8K, chr_banks);
+        Multicart::new(&ines.build())
+    }
+
+    fn prg_16k(multicart: &Multicart) -> [u8; 2] {
+        [0x8000, 0xC000].map(|addr| multicart.cpu_peek(addr).unwrap())
+    }
+
+    #[test]
+    fn test_mapper_15_modes() {
+        let mut k1029 = board(15, 64, 0);
+        k1029.cpu_write(0x8000, 0x05);
+        assert_eq!(prg_16k(&k1029), [4, 5]);
+        k1029.cpu_write(0x8001, 0x05 | 0x40);
+        assert_eq!(prg_16k(&k1029), [5, 7]);
+        assert_eq!(k1029.mirroring(), MirroringType::Horizontal);
+        k1029.cpu_write(0x8003, 0x09);
+        assert_eq!(prg_16k(&k1029), [9, 9]);
+
+        // 8 KiB mode, bit 7 picks the half of the 16 KiB bank
+        k1029.cpu_write(0x8002, 0x83);
+        assert_eq!(k1029.cpu_read(0x8000), Some(3));
+        assert_eq!(k1029.cpu_read(0xE000), Some(3));
+        assert_eq!(k1029.prg_window.index(0xA000), 7 * SIZE_8K);
+
+        // CHR RAM is only writable in the UNROM and 8 KiB modes
+        k1029.cpu_write(0x8000, 0x00);
+        k1029.ppu_write(0x0000, 0x42);
+        assert_eq!(k1029.ppu_read(0x0000), 0x00);
+        k1029.cpu_write(0x8001, 0x00);
+        k1029.ppu_write(0x0000, 0x42);
+        assert_eq!(k1029.ppu_read(0x0000), 0x42);
+    }
+
+    #[test]
+    fn test_mapper_41_inner_chr_gated() {
+        let mut caltron = board(41, 16, 16);
+        caltron.cpu_write(0x6000 | 0x20 | 0x08 | 0x01, 0);
+        assert_eq!(prg_16k(&caltron), [2, 3]);
+        assert_eq!(caltron.mirroring(), MirroringType::Horizontal);
+        caltron.cpu_write(0x8000, 0x03);
+        assert_eq!(caltron.ppu_read(0x0000), 4);
+
+        caltron.cpu_write(0x6000 | 0x08 | 0x04, 0);
+        caltron.cpu_write(0x8000, 0x03);
+        assert_eq!(prg_16k(&caltron), [8, 9]);
+        assert_eq!(caltron.ppu_read(0x0000), 7);
+
+        // Reset brings the menu back
+        caltron.reset();
+        assert_eq!(prg_16k(&caltron), [0, 1]);
+        assert_eq!(caltron.ppu_read(0x0000), 0);
+    }
+
+    #[test]
+    fn test_mapper_57_and_58() {
+        let mut gk57 = board(57, 8, 16);
+        gk57.cpu_write(0x8800, 0x40 | 0x08 | 0x01);
+        gk57.cpu_write(0x8000, 0x40 | 0x02);
+        assert_eq!(prg_16k(&gk57), [2, 2]);
+        assert_eq!(gk57.ppu_read(0x0000), 11);
+        assert_eq!(gk57.mirroring(), MirroringType::Horizontal);
+        gk57.cpu_write(0x8800, 0x60 | 0x10);
+        assert_eq!(prg_16k(&gk57), [2, 3]);
+
+        let mut gk58 = board(58, 8, 8);
+        gk58.cpu_write(0x8000 | 0x40 | 0x18 | 0x05, 0);
+        assert_eq!(prg_16k(&gk58), [5, 5]);
+        assert_eq!(gk58.ppu_read(0x0000), 3);
+        gk58.cpu_write(0x8000 | 0x80 | 0x05, 0);
+        assert_eq!(prg_16k(&gk58), [4, 5]);
+        assert_eq!(gk58.mirroring(), MirroringType::Horizontal);
+    }
+
+    #[test]
+    fn test_mapper_60_counts_resets() {
+        let mut reset_based = board(60, 4, 4);
+        for game in [0, 1, 2, 3, 0] {
+            assert_eq!(prg_16k(&reset_based), [game, game]);
+            assert_eq!(reset_based.ppu_read(0x0000), game);
+            reset_based.reset();
+        }
+    }
+
+    #[test]
+    fn test_mapper_225_outer_bank_and_nibble_ram() {
+        let mut bmc225 = board(225, 128, 128);
+        bmc225.cpu_write(0xC000 | 0x1000 | (0x03 << 6) | 0x05, 0);
+        assert_eq!(prg_16k(&bmc225), [67, 67]);
+        assert_eq!(bmc225.ppu_read(0x0000), 69);
+
+        bmc225.cpu_write(0x5800, 0xAB);
+        assert_eq!(bmc225.cpu_read(0x5800), Some(0x0B));
+
+        bmc225.reset();
+        assert_eq!(prg_16k(&bmc225), [0, 1]);
+    }
+
+    #[test]
+    fn test_mapper_226_and_227() {
+        let mut bmc226 = board(226, 128, 0);
+        bmc226.cpu_write(0x8000, 0x80 | 0x20 | 0x03);
+        bmc226.cpu_write(0x8001, 0x01);
+        assert_eq!(prg_16k(&bmc226), [99, 99]);
+        assert_eq!(bmc226.mirroring(), MirroringType::Horizontal);
+
+        // UNROM layout with the last bank of the 128 KiB game fixed
+        let mut bmc227 = board(227, 64, 0);
+        bmc227.cpu_write(0x8000 | 0x200 | (0x0A << 2), 0);
+        assert_eq!(prg_16k(&bmc227), [10, 15]);
+        bmc227.cpu_write(0x8000 | (0x0A << 2), 0);
+        assert_eq!(prg_16k(&bmc227), [10, 8]);
+        bmc227.cpu_write(0x8000 | 0x80 | 0x01 | (0x0A << 2), 0);
+        assert_eq!(prg_16k(&bmc227), [10, 11]);
+    }
+}
@@ -0,0 +1,273 @@
+// Namco 108 and its boards (iNES mappers 206, 76, 88, 95 and 154).
+// The chip is the MMC3 banking core without its modes, IRQ or mirroring control:
+// $8000 selects one of eight registers and $8001 writes it, R0-R1 are 2 KiB CHR banks,
+// R2-R5 1 KiB CHR banks, R6-R7 8 KiB PRG banks at $8000/$A000 and the last two PRG
+// banks are fixed. The boards rewire the CHR lines for bigger CHR or nametable control.
+
+use crate::sys::interfaces::{INes, MirroringType, RomData};
+
+use super::bank::{BankWindow, SIZE_1K, SIZE_8K, SIZE_32K};
+use super::{Mapper, VramSource, chr::Chr, named_registers, window_registers};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Namco108Board {
+    Dxrom,      // 206, plain Namco 108 / Nintendo DxROM
+    Namcot3446, // 76, R2-R5 become 2 KiB banks for up to 128 KiB CHR
+    Namcot3443, // 88, CHR A16 follows the pattern table
+    Namcot3425, // 95, bit 5 of R0/R1 picks the CIRAM page
+    Namcot3453, // 154, 88 plus single-screen select on bit 6 of every write
+}
+
+pub struct Namco108 {
+    board: Namco108Board,
+    prg_rom: RomData,
+    chr: Chr,
+    registers: [u8; 8],
+    select: u8,
+    mirroring: MirroringType,
+    prg_window: BankWindow,
+    chr_window: BankWindow,
+}
+
+impl Namco108 {
+    pub fn new(ines: &INes) -> Self {
+        let board = match ines.mapper {
+            76 => Namco108Board::Namcot3446,
+            88 => Namco108Board::Namcot3443,
+            95 => Namco108Board::Namcot3425,
+            154 => Namco108Board::Namcot3453,
+            _ => Namco108Board::Dxrom,
+        };
+        let mirroring = match board {
+            Namco108Board::Namcot3453 => MirroringType::SingleScreenLower,
+            _ => ines.mirroring.clone(),
+        };
+        let chr = Chr::new(ines);
+
+        let mut namco108 = Namco108 {
+            board,
+            prg_window: BankWindow::new(ines.prg_rom.len(), SIZE_32K, SIZE_8K),
+            chr_window: BankWindow::new(chr.len(), SIZE_8K, SIZE_1K),
+            prg_rom: ines.prg_rom.clone(),
+            chr,
+            registers: [0; 8],
+            select: 0,
+            mirroring,
+        };
+        namco108.update_banks();
+        namco108
+    }
+
+    fn update_banks(&mut self) {
+        let last = self.prg_window.last_bank(SIZE_8K);
+        self.prg_window
+            .select(0, SIZE_8K, (self.registers[6] & 0x0F) as usize);
+        self.prg_window
+            .select(1, SIZE_8K, (self.registers[7] & 0x0F) as usize);
+        self.prg_window.select(2, SIZE_8K, last.saturating_sub(1));
+        self.prg_window.select(3, SIZE_8K, last);
+
+        for slot in 0..8 {
+            let bank = self.chr_bank((slot * SIZE_1K) as u16);
+            self.chr_window.select(slot, SIZE_1K, bank);
+        }
+    }
+
+    // 1 KiB CHR bank for a pattern table address
+    fn chr_bank(&self, addr: u16) -> usize {
+        let slot = (addr >> 10) as usize & 0x07;
+
+        if self.board == Namco108Board::Namcot3446 {
+            let bank = self.registers[2 + (slot >> 1)] as usize & 0x3F;
+            return (bank << 1) | (slot & 0x01);
+        }
+
+        let bank = match slot {
+            0..=3 => (self.registers[slot >> 1] as usize & 0x3E) | (slot & 0x01),
+            _ => self.registers[slot - 2] as usize & 0x3F,
+        };
+
+        match self.board {
+            // The left pattern table reads the lower 64 KiB, the right one the upper
+            Namco108Board::Namcot3443 | Namco108Board::Namcot3453 => match slot {
+                0..=3 => bank & 0x3F,
+                _ => bank | 0x40,
+            },
+            // Bit 5 of R0/R1 goes to the nametables instead
+            Namco108Board::Namcot3425 => bank & 0x1F,
+            _ => bank,
+        }
+    }
+
+    // Mapper 95: $2000-$27FF follow bit 5 of R0 and $2800-$2FFF bit 5 of R1
+    fn nametable_page(&self, addr: u16) -> u8 {
+        let register = self.registers[(addr as usize >> 11) & 0x01];
+        (register >> 5) & 0x01
+    }
+}
+
+impl Mapper for Namco108 {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_window.index(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        if addr < 0x8000 {
+            return;
+        }
+
+        if self.board == Namco108Board::Namcot3453 {
+            self.mirroring = match value & 0x40 {
+                0 => MirroringType::SingleScreenLower,
+                _ => MirroringType::SingleScreenUpper,
+            };
+        }
+
+        match addr & 0xE001 {
+            0x8000 => self.select = value & 0x07,
+            0x8001 => {
+                self.registers[self.select as usize] = value & 0x3F;
+                self.update_banks();
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        self.chr[self.chr_window.index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        self.chr.write(self.chr_window.index(addr), value);
+    }
+
+    fn chr(&self) -> Option<&Chr> {
+        Some(&self.chr)
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut Chr> {
+        Some(&mut self.chr)
+    }
+
+    fn registers(&self) -> Vec<(String, u16)> {
+        let mut registers = window_registers("prg", &self.prg_window);
+        registers.extend(window_registers("chr", &self.chr_window));
+        for (index, &value) in self.registers.iter().enumerate() {
+            registers.push((format!("r{}", index), value as u16));
+        }
+        registers.extend(named_registers([("select", self.select as u16)]));
+        registers
+    }
+
+    fn vram_source(&self, addr: u16) -> VramSource {
+        match (self.board, addr & 0x3FFF) {
+            (_, 0x0000..=0x1FFF) => VramSource::Cartridge,
+            (Namco108Board::Namcot3425, _) => VramSource::Ciram(self.nametable_page(addr)),
+            _ => VramSource::Ciram(self.mirroring.ciram_page(addr)),
+        }
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        match self.board {
+            Namco108Board::Namcot3425 => {
+                match (self.nametable_page(0x2000), self.nametable_page(0x2800)) {
+                    (0, 0) => MirroringType::SingleScreenLower,
+                    (1, 1) => MirroringType::SingleScreenUpper,
+                    // Closest layout when the halves are swapped
+                    _ => MirroringType::Horizontal,
+                }
+            }
+            _ => self.mirroring.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::test_rom::test_rom;
+
+    fn board(mapper: u8, chr_banks: u8) -> Namco108 {
+        let ines = test_rom(mapper)
+            .prg(SIZE_8K, 16)
+            .chr(SIZE_1K, chr_banks as usize);
+        Namco108::new(&ines.build())
+    }
+
+    fn write_register(namco108: &mut Namco108, register: u8, value: u8) {
+        namco108.cpu_write(0x8000, register);
+        namco108.cpu_write(0x8001, value);
+    }
+
+    #[test]
+    fn test_prg_and_chr_banks() {
+        let mut namco108 = board(206, 64);
+        write_register(&mut namco108, 6, 3);
+        write_register(&mut namco108, 7, 5);
+        assert_eq!(namco108.cpu_read(0x8000), Some(3));
+        assert_eq!(namco108.cpu_read(0xA000), Some(5));
+        assert_eq!(namco108.cpu_read(0xC000), Some(14));
+        assert_eq!(namco108.cpu_read(0xE000), Some(15));
+
+        // 2 KiB banks ignore the low bit
+        write_register(&mut namco108, 0, 9);
+        write_register(&mut namco108, 5, 33);
+        assert_eq!(namco108.ppu_read(0x0000), 8);
+        assert_eq!(namco108.ppu_read(0x0400), 9);
+        assert_eq!(namco108.ppu_read(0x1C00), 33);
+
+        // Only $8000-$9FFF decode the registers
+        namco108.cpu_write(0xA001, 0);
+        assert_eq!(namco108.ppu_read(0x1C00), 33);
+    }
+
+    #[test]
+    fn test_mapper_76_2k_chr_banks() {
+        let mut namco108 = board(76, 128);
+        write_register(&mut namco108, 2, 1);
+        write_register(&mut namco108, 5, 40);
+        assert_eq!(namco108.ppu_read(0x0000), 2);
+        assert_eq!(namco108.ppu_read(0x0400), 3);
+        assert_eq!(namco108.ppu_read(0x1800), 80);
+        assert_eq!(namco108.ppu_read(0x1C00), 81);
+    }
+
+    #[test]
+    fn test_mapper_88_and_154_chr_high_bit() {
+        for mapper in [88, 154] {
+            let mut namco108 = board(mapper, 128);
+            write_register(&mut namco108, 0, 4);
+            write_register(&mut namco108, 2, 4);
+            assert_eq!(namco108.ppu_read(0x0000), 4);
+            assert_eq!(namco108.ppu_read(0x1000), 68);
+        }
+    }
+
+    #[test]
+    fn test_mapper_154_single_screen() {
+        let mut namco108 = board(154, 128);
+        assert_eq!(namco108.mirroring(), MirroringType::SingleScreenLower);
+        namco108.cpu_write(0xC000, 0x40);
+        assert_eq!(namco108.mirroring(), MirroringType::SingleScreenUpper);
+        write_register(&mut namco108, 0, 0);
+        assert_eq!(namco108.mirroring(), MirroringType::SingleScreenLower);
+    }
+
+    #[test]
+    fn test_mapper_95_nametables_from_chr_banks() {
+        let mut namco108 = board(95, 32);
+        write_register(&mut namco108, 0, 0x22);
+        write_register(&mut namco108, 1, 0x00);
+        assert_eq!(namco108.ppu_read(0x0000), 2);
+        assert_eq!(namco108.vram_source(0x2000), VramSource::Ciram(1));
+        assert_eq!(namco108.vram_source(0x2400), VramSource::Ciram(1));
+        assert_eq!(namco108.vram_source(0x2800), VramSource::Ciram(0));
+        assert_eq!(namco108.vram_source(0x2C00), VramSource::Ciram(0));
+
+        write_register(&mut namco108, 1, 0x20);
+        assert_eq!(namco108.mirroring(), MirroringType::SingleScreenUpper);
+    }
+}
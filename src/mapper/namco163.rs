@@ -0,0 +1,360 @@
+// Namco 129/163 (iNES mapper 19).
+// Besides PRG/CHR banking the chip maps console nametable RAM (CIRAM) or CHR ROM into
+// every 1 KiB of PPU $0000-$2FFF, and its 128 bytes of internal RAM double as sound
+// registers and battery backed storage.
+
+use crate::apu::namco163::{INTERNAL_RAM_SIZE, Namco163Audio};
+use crate::sys::interfaces::{INes, MirroringType, RomData};
+
+use super::bank::{BankWindow, SIZE_1K, SIZE_8K, SIZE_16K, SIZE_32K};
+use super::{Mapper, VramSource, chr::Chr, named_registers, prg_ram::PrgRam, window_registers};
+
+const CIRAM_BANKS: u8 = 0xE0; // Bank numbers $E0-$FF select CIRAM, bit 0 picks the page
+
+pub struct Namco163 {
+    prg_rom: RomData,
+    prg_ram: PrgRam,
+    chr: Chr,
+    prg_window: BankWindow,
+    chr_window: BankWindow, // Covers $0000-$2FFF, for nametables mapped to CHR ROM
+    chr_banks: [u8; 8],
+    nametable_banks: [u8; 4],
+    ciram_disabled: [bool; 2], // Per pattern table, set means $E0-$FF are CHR ROM banks
+    write_protect: u8,
+    irq_counter: u16,
+    irq_enabled: bool,
+    irq_pending: bool,
+    audio: Namco163Audio,
+}
+
+impl Namco163 {
+    pub fn new(ines: &INes) -> Self {
+        let chr = Chr::new(ines);
+
+        let mut prg_window = BankWindow::new(ines.prg_rom.len(), SIZE_32K, SIZE_8K);
+        prg_window.select(3, SIZE_8K, prg_window.last_bank(SIZE_8K));
+
+        let mut namco163 = Namco163 {
+            prg_rom: ines.prg_rom.clone(),
+            prg_ram: PrgRam::new(ines),
+            prg_window,
+            chr_window: BankWindow::new(chr.len(), SIZE_16K, SIZE_1K),
+            chr,
+            chr_banks: [0; 8],
+            nametable_banks: [CIRAM_BANKS; 4],
+            ciram_disabled: [false; 2],
+            write_protect: 0,
+            irq_counter: 0,
+            irq_enabled: false,
+            irq_pending: false,
+            audio: Namco163Audio::new(),
+        };
+        namco163.update_chr_banks();
+        namco163
+    }
+
+    // $F800 must hold $4x for PRG RAM writes, bits 0-3 protect each 2 KiB quarter
+    fn prg_ram_writable(&self, addr: u16) -> bool {
+        let quarter = (addr - 0x6000) >> 11;
+        self.write_protect & 0xF0 == 0x40 && self.write_protect & (1 << quarter) == 0
+    }
+
+    fn bank_register(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x1FFF => self.chr_banks[(addr >> 10) as usize],
+            _ => self.nametable_banks[((addr >> 10) & 0x03) as usize],
+        }
+    }
+
+    fn update_chr_banks(&mut self) {
+        for slot in 0..12 {
+            let bank = self.bank_register((slot * SIZE_1K) as u16);
+            self.chr_window.select(slot, SIZE_1K, bank as usize);
+        }
+    }
+}
+
+impl Mapper for Namco163 {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x4800..=0x4FFF => Some(self.audio.peek_data()),
+            0x5000..=0x57FF => Some(self.irq_counter as u8),
+            0x5800..=0x5FFF => {
+                Some(((self.irq_counter >> 8) as u8) | ((self.irq_enabled as u8) << 7))
+            }
+            0x6000..=0x7FFF => self.prg_ram.read(addr),
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_window.index(addr)]),
+            _ => None,
+        }
+    }
+
+    // The internal RAM port advances its address on reads
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        match addr {
+            0x4800..=0x4FFF => Some(self.audio.read_data()),
+            _ => self.cpu_peek(addr),
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x4800..=0x4FFF => self.audio.write_data(value),
+            0x5000..=0x57FF => {
+                self.irq_counter = (self.irq_counter & 0x7F00) | value as u16;
+                self.irq_pending = false;
+            }
+            0x5800..=0x5FFF => {
+                self.irq_counter = (self.irq_counter & 0x00FF) | (((value & 0x7F) as u16) << 8);
+                self.irq_enabled = value & 0x80 != 0;
+                self.irq_pending = false;
+            }
+            0x6000..=0x7FFF if self.prg_ram_writable(addr) => self.prg_ram.write(addr, value),
+            0x8000..=0xBFFF => {
+                self.chr_banks[((addr - 0x8000) >> 11) as usize] = value;
+                self.update_chr_banks();
+            }
+            0xC000..=0xDFFF => {
+                self.nametable_banks[((addr - 0xC000) >> 11) as usize] = value;
+                self.update_chr_banks();
+            }
+            0xE000..=0xE7FF => {
+                self.prg_window.select(0, SIZE_8K, (value & 0x3F) as usize);
+                self.audio.set_disabled(value & 0x40 != 0);
+            }
+            0xE800..=0xEFFF => {
+                self.prg_window.select(1, SIZE_8K, (value & 0x3F) as usize);
+                self.ciram_disabled = [value & 0x40 != 0, value & 0x80 != 0];
+            }
+            0xF000..=0xF7FF => self.prg_window.select(2, SIZE_8K, (value & 0x3F) as usize),
+            0xF800..=0xFFFF => {
+                self.audio.set_address(value);
+                self.write_protect = value;
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        self.chr[self.chr_window.index(addr & 0x2FFF)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        self.chr.write(self.chr_window.index(addr & 0x2FFF), value);
+    }
+
+    fn chr(&self) -> Option<&Chr> {
+        Some(&self.chr)
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut Chr> {
+        Some(&mut self.chr)
+    }
+
+    fn registers(&self) -> Vec<(String, u16)> {
+        let mut registers = window_registers("prg", &self.prg_window);
+        registers.extend(window_registers("chr", &self.chr_window));
+        registers.extend(named_registers([
+            ("write_protect", self.write_protect as u16),
+            ("irq_enabled", self.irq_enabled as u16),
+            ("irq_counter", self.irq_counter),
+        ]));
+        registers
+    }
+
+    fn vram_source(&self, addr: u16) -> VramSource {
+        let register = self.bank_register(addr & 0x2FFF);
+        let ciram_allowed = match addr & 0x3FFF {
+            0x0000..=0x1FFF => !self.ciram_disabled[(addr >> 12) as usize & 0x01],
+            _ => true,
+        };
+
+        if register >= CIRAM_BANKS && ciram_allowed {
+            VramSource::Ciram(register & 0x01)
+        } else {
+            VramSource::Cartridge
+        }
+    }
+
+    fn prg_ram(&self) -> Option<&PrgRam> {
+        Some(&self.prg_ram)
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut PrgRam> {
+        Some(&mut self.prg_ram)
+    }
+
+    // Closest standard layout, the real nametable mapping comes from `vram_source`
+    fn mirroring(&self) -> MirroringType {
+        match self.nametable_banks.map(|bank| bank & 0x01) {
+            [0, 0, 0, 0] => MirroringType::SingleScreenLower,
+            [1, 1, 1, 1] => MirroringType::SingleScreenUpper,
+            [0, 0, 1, 1] => MirroringType::Horizontal,
+            _ => MirroringType::Vertical,
+        }
+    }
+
+    // The 15-bit counter counts up every CPU cycle and stops at $7FFF
+    fn cpu_clock(&mut self) {
+        if self.irq_enabled && self.irq_counter < 0x7FFF {
+            self.irq_counter += 1;
+            if self.irq_counter == 0x7FFF {
+                self.irq_pending = true;
+            }
+        }
+
+        self.audio.clock();
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.audio.output()
+    }
+
+    // PRG RAM followed by the internal RAM
+    fn battery_ram(&self) -> Option<Vec<u8>> {
+        self.prg_ram
+            .has_battery()
+            .then(|| [self.prg_ram.data(), &self.audio.ram].concat())
+    }
+
+    fn load_battery_ram(&mut self, data: &[u8]) {
+        let (prg_ram, internal) = data.split_at(data.len().min(self.prg_ram.len()));
+        self.prg_ram.load(prg_ram);
+
+        let internal = &internal[..internal.len().min(INTERNAL_RAM_SIZE)];
+        self.audio.ram[..internal.len()].copy_from_slice(internal);
+    }
+
+    // Both flags are taken, the internal RAM is saved along with the PRG RAM
+    fn take_battery_ram_dirty(&mut self) -> bool {
+        let internal = self.audio.take_dirty();
+        let prg_ram = self.prg_ram.take_dirty();
+        (prg_ram || internal) && self.prg_ram.has_battery()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::test_rom::test_rom;
+
+    fn board() -> Namco163 {
+        let ines = test_rom(19)
+            .prg(SIZE_8K, 16)
+            .chr(SIZE_1K, 256)
+            .battery(true);
+        Namco163::new(&ines.build())
+    }
+
+    #[test]
+    fn test_internal_ram_port_auto_increments() {
+        let mut n163 = board();
+        n163.cpu_write(0xF800, 0x80 | 0x7E);
+        n163.cpu_write(0x4800, 0x11);
+        n163.cpu_write(0x4800, 0x22);
+        n163.cpu_write(0x4800, 0x33); // Wraps to $00
+
+        n163.cpu_write(0xF800, 0xFE);
+        assert_eq!(n163.cpu_peek(0x4800), Some(0x11));
+        assert_eq!(n163.cpu_peek(0x4800), Some(0x11));
+        assert_eq!(n163.cpu_read(0x4800), Some(0x11));
+        assert_eq!(n163.cpu_read(0x4800), Some(0x22));
+        assert_eq!(n163.cpu_read(0x4800), Some(0x33));
+    }
+
+    #[test]
+    fn test_ciram_and_chr_rom_nametables() {
+        let mut n163 = board();
+        n163.cpu_write(0xC000, 0xE1);
+        n163.cpu_write(0xC800, 0x05);
+        assert_eq!(n163.vram_source(0x2000), VramSource::Ciram(1));
+        assert_eq!(n163.vram_source(0x2400), VramSource::Cartridge);
+        assert_eq!(n163.ppu_read(0x2400), 0x05);
+
+        // $E0-$FF in the pattern tables are CIRAM unless disabled through $E800
+        n163.cpu_write(0x8000, 0xE0);
+        assert_eq!(n163.vram_source(0x0000), VramSource::Ciram(0));
+        n163.cpu_write(0xE800, 0x40);
+        assert_eq!(n163.vram_source(0x0000), VramSource::Cartridge);
+        assert_eq!(n163.ppu_read(0x0000), 0xE0);
+    }
+
+    #[test]
+    fn test_prg_ram_write_protect() {
+        let mut n163 = board();
+        n163.cpu_write(0x6000, 0x12);
+        assert_eq!(n163.cpu_read(0x6000), Some(0x00));
+
+        n163.cpu_write(0xF800, 0x41); // First quarter still protected
+        n163.cpu_write(0x6000, 0x12);
+        n163.cpu_write(0x6800, 0x34);
+        assert_eq!(n163.cpu_read(0x6000), Some(0x00));
+        assert_eq!(n163.cpu_read(0x6800), Some(0x34));
+    }
+
+    #[test]
+    fn test_irq_counter_stops_at_7fff() {
+        let mut n163 = board();
+        n163.cpu_write(0x5000, 0xFD);
+        n163.cpu_write(0x5800, 0xFF);
+        n163.cpu_clock();
+        assert!(!n163.irq_pending());
+        n163.cpu_clock();
+        assert!(n163.irq_pending());
+        n163.cpu_clock();
+        assert_eq!(n163.cpu_read(0x5000), Some(0xFF));
+
+        n163.cpu_write(0x5800, 0x00);
+        assert!(!n163.irq_pending());
+    }
+
+    #[test]
+    fn test_battery_ram_round_trip() {
+        let mut n163 = board();
+        n163.cpu_write(0xF800, 0x40);
+        n163.cpu_write(0x7FFF, 0xAB);
+        n163.cpu_write(0x4800, 0xCD);
+
+        let saved = n163.battery_ram().unwrap();
+        assert_eq!(saved.len(), SIZE_8K + INTERNAL_RAM_SIZE);
+        assert!(n163.take_battery_ram_dirty());
+        assert!(!n163.take_battery_ram_dirty());
+
+        // The internal RAM alone flags a flush too
+        n163.cpu_write(0xF800, 0x40);
+        n163.cpu_write(0x4800, 0xEF);
+        assert!(n163.take_battery_ram_dirty());
+
+        let mut restored = board();
+        restored.load_battery_ram(&saved);
+        assert_eq!(restored.cpu_read(0x7FFF), Some(0xAB));
+        restored.cpu_write(0xF800, 0x40);
+        assert_eq!(restored.cpu_read(0x4800), Some(0xCD));
+    }
+
+    #[test]
+    fn test_wavetable_channel_output() {
+        let mut n163 = board();
+        let mut poke = |address: u8, value: u8| {
+            n163.cpu_write(0xF800, address);
+            n163.cpu_write(0x4800, value);
+        };
+        poke(0x00, 0xF0); // Samples 0 and 15
+        poke(0x7C, 0xFD); // Length 4, frequency $10000 (one sample per update)
+        poke(0x7E, 0x00); // Wave at sample 0
+        poke(0x7F, 0x0F); // One channel, volume 15
+
+        let outputs: Vec<f32> = (0..15 * 4)
+            .map(|_| {
+                n163.cpu_clock();
+                n163.audio_output()
+            })
+            .filter(|sample| *sample != 0.0)
+            .collect();
+        assert!(outputs.iter().any(|sample| *sample > 0.0));
+        assert!(outputs.iter().any(|sample| *sample < 0.0));
+    }
+}
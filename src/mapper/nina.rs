@@ -0,0 +1,177 @@
+// AVE NINA boards. NINA-001 (iNES mapper 34) keeps its registers at the top of the 8 KiB
+// PRG RAM: $7FFD selects 32 KiB of PRG, $7FFE and $7FFF 4 KiB CHR banks. Mapper 34 is shared
+// with BNROM, told apart by the submapper or, in plain iNES headers, by CHR ROM over 8 KiB.
+// NINA-003/006 (iNES mapper 79) has a single register in $4100-$5FFF (A8 set, A14-A13 = 10).
+
+use crate::sys::interfaces::{INes, MirroringType, RomData};
+
+use super::bank::{BankWindow, SIZE_4K, SIZE_8K, SIZE_32K};
+use super::{Mapper, chr::Chr, prg_ram::PrgRam, window_registers};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NinaBoard {
+    Nina001, // 34.1
+    Bnrom,   // 34.2
+    Nina003, // 79
+}
+
+pub struct Nina {
+    board: NinaBoard,
+    prg_rom: RomData,
+    prg_ram: PrgRam,
+    chr: Chr,
+    prg_window: BankWindow,
+    chr_window: BankWindow,
+    mirroring: MirroringType,
+}
+
+impl Nina {
+    pub fn new(ines: &INes) -> Self {
+        let board = match (ines.mapper, ines.submapper) {
+            (79, _) => NinaBoard::Nina003,
+            (34, 1) => NinaBoard::Nina001,
+            (34, 2) => NinaBoard::Bnrom,
+            _ if ines.chr_rom.len() > 0x2000 => NinaBoard::Nina001,
+            _ => NinaBoard::Bnrom,
+        };
+        let prg_ram = match board {
+            NinaBoard::Nina001 => PrgRam::new(ines),
+            _ => PrgRam::default(),
+        };
+        let chr = Chr::new(ines);
+
+        let mut prg_window = BankWindow::new(ines.prg_rom.len(), SIZE_32K, SIZE_32K);
+        prg_window.select(0, SIZE_32K, 0);
+
+        Nina {
+            board,
+            prg_rom: ines.prg_rom.clone(),
+            prg_ram,
+            prg_window,
+            chr_window: BankWindow::new(chr.len(), SIZE_8K, SIZE_4K),
+            chr,
+            mirroring: ines.mirroring.clone(),
+        }
+    }
+}
+
+impl Mapper for Nina {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF => self.prg_ram.read(addr),
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_window.index(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match (self.board, addr) {
+            // The registers also land in the RAM underneath
+            (NinaBoard::Nina001, 0x6000..=0x7FFF) => {
+                self.prg_ram.write(addr, value);
+                match addr {
+                    0x7FFD => self.prg_window.select(0, SIZE_32K, (value & 0x01) as usize),
+                    0x7FFE => self.chr_window.select(0, SIZE_4K, (value & 0x0F) as usize),
+                    0x7FFF => self.chr_window.select(1, SIZE_4K, (value & 0x0F) as usize),
+                    _ => {}
+                }
+            }
+            (NinaBoard::Bnrom, 0x8000..=0xFFFF) => {
+                self.prg_window.select(0, SIZE_32K, value as usize)
+            }
+            (NinaBoard::Nina003, 0x4100..=0x5FFF) if addr & 0xE100 == 0x4100 => {
+                self.prg_window
+                    .select(0, SIZE_32K, ((value >> 3) & 0x01) as usize);
+                self.chr_window.select(0, SIZE_8K, (value & 0x07) as usize);
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        self.chr[self.chr_window.index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        self.chr.write(self.chr_window.index(addr), value);
+    }
+
+    fn chr(&self) -> Option<&Chr> {
+        Some(&self.chr)
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut Chr> {
+        Some(&mut self.chr)
+    }
+
+    fn registers(&self) -> Vec<(String, u16)> {
+        let mut registers = window_registers("prg", &self.prg_window);
+        registers.extend(window_registers("chr", &self.chr_window));
+        registers
+    }
+
+    fn prg_ram(&self) -> Option<&PrgRam> {
+        Some(&self.prg_ram)
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut PrgRam> {
+        Some(&mut self.prg_ram)
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        self.mirroring.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::test_rom::test_rom;
+
+    fn board(mapper: u8, submapper: u8, prg_banks: u8, chr_size: usize) -> Nina {
+        let ines = test_rom(mapper)
+            .submapper(submapper)
+            .prg(SIZE_32K, prg_banks as usize)
+            .chr(SIZE_4K, chr_size / SIZE_4K);
+        Nina::new(&ines.build())
+    }
+
+    #[test]
+    fn test_nina001_registers_in_prg_ram() {
+        let mut nina = board(34, 0, 2, 0x10000);
+        nina.cpu_write(0x7FFD, 0x01);
+        nina.cpu_write(0x7FFE, 0x05);
+        nina.cpu_write(0x7FFF, 0x0A);
+        assert_eq!(nina.cpu_read(0x8000), Some(1));
+        assert_eq!(nina.ppu_read(0x0000), 5);
+        assert_eq!(nina.ppu_read(0x1000), 10);
+        assert_eq!(nina.cpu_read(0x7FFE), Some(0x05));
+
+        // Plain writes to ROM do nothing on NINA-001
+        nina.cpu_write(0x8000, 0x00);
+        assert_eq!(nina.cpu_read(0x8000), Some(1));
+    }
+
+    #[test]
+    fn test_bnrom_on_mapper_34() {
+        let mut bnrom = board(34, 0, 4, 0);
+        bnrom.cpu_write(0x8000, 0x03);
+        assert_eq!(bnrom.cpu_read(0xFFFF), Some(3));
+        assert_eq!(bnrom.cpu_read(0x6000), None);
+    }
+
+    #[test]
+    fn test_nina003_register_decoding() {
+        let mut nina = board(79, 0, 2, 0x10000);
+        nina.cpu_write(0x4100, 0x08 | 0x03);
+        assert_eq!(nina.cpu_read(0x8000), Some(1));
+        assert_eq!(nina.ppu_read(0x0000), 6);
+        assert_eq!(nina.ppu_read(0x1000), 7);
+
+        // A8 clear does not decode
+        nina.cpu_write(0x4200, 0x00);
+        assert_eq!(nina.cpu_read(0x8000), Some(1));
+        nina.cpu_write(0x5F00, 0x00);
+        assert_eq!(nina.cpu_read(0x8000), Some(0));
+    }
+}
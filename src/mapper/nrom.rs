@@ -0,0 +1,98 @@
+// NROM (iNES mapper 0), no mapper hardware at all: 16 or 32 KiB of PRG ROM at $8000-$FFFF
+// (16 KiB mirrored twice), 8 KiB of CHR ROM or RAM and the PRG RAM the header asks for
+// (Family BASIC has some) at $6000-$7FFF. Mirroring is soldered.
+
+use crate::sys::interfaces::{INes, MirroringType, RomData};
+
+use super::bank::{BankWindow, SIZE_8K, SIZE_32K};
+use super::{Mapper, chr::Chr, prg_ram::PrgRam};
+
+pub struct Nrom {
+    prg_rom: RomData,
+    prg_ram: PrgRam,
+    chr: Chr,
+    prg_window: BankWindow,
+    chr_window: BankWindow,
+    mirroring: MirroringType,
+}
+
+impl Nrom {
+    pub fn new(ines: &INes) -> Self {
+        let chr = Chr::new(ines);
+
+        Nrom {
+            prg_rom: ines.prg_rom.clone(),
+            prg_ram: PrgRam::new(ines),
+            prg_window: BankWindow::new(ines.prg_rom.len(), SIZE_32K, SIZE_32K),
+            chr_window: BankWindow::new(chr.len(), SIZE_8K, SIZE_8K),
+            chr,
+            mirroring: ines.mirroring.clone(),
+        }
+    }
+}
+
+impl Mapper for Nrom {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF => self.prg_ram.read(addr),
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_window.index(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        if let 0x6000..=0x7FFF = addr {
+            self.prg_ram.write(addr, value);
+        }
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        self.chr[self.chr_window.index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        self.chr.write(self.chr_window.index(addr), value);
+    }
+
+    fn chr(&self) -> Option<&Chr> {
+        Some(&self.chr)
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut Chr> {
+        Some(&mut self.chr)
+    }
+
+    fn prg_ram(&self) -> Option<&PrgRam> {
+        Some(&self.prg_ram)
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut PrgRam> {
+        Some(&mut self.prg_ram)
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        self.mirroring.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::test_rom::test_rom;
+
+    #[test]
+    fn test_nrom_128_mirrors_prg() {
+        let nrom = Nrom::new(&test_rom(0).prg(SIZE_8K, 2).chr(SIZE_8K, 1).build());
+        assert_eq!(nrom.cpu_peek(0xA000), Some(1));
+        assert_eq!(nrom.cpu_peek(0xC000), Some(0));
+        assert_eq!(nrom.cpu_peek(0xE000), Some(1));
+    }
+
+    #[test]
+    fn test_nrom_chr_ram() {
+        let mut nrom = Nrom::new(&test_rom(0).prg(SIZE_32K, 1).build());
+        nrom.ppu_write(0x1234, 0x56);
+        assert_eq!(nrom.ppu_read(0x1234), 0x56);
+        assert!(nrom.chr().unwrap().is_ram());
+    }
+}
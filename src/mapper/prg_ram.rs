@@ -0,0 +1,154 @@
+// Cartridge PRG RAM at $6000-$7FFF, sized from the header (work RAM plus battery backed RAM).
+// Chips smaller than 8 KiB mirror across the window, reads of boards without RAM see open bus.
+// Writes that change a byte mark it dirty so battery backed RAM can be flushed to disk
+// periodically.
+
+use crate::sys::interfaces::INes;
+
+pub const TRAINER_ADDR: u16 = 0x7000;
+
+#[derive(Debug, Clone, Default)]
+pub struct PrgRam {
+    data: Vec<u8>,
+    battery: bool,
+    dirty: bool,
+}
+
+impl PrgRam {
+    pub fn new(ines: &INes) -> Self {
+        Self::with_size(ines.prg_ram_size + ines.prg_nvram_size, ines.battery)
+    }
+
+    pub fn with_size(size: usize, battery: bool) -> Self {
+        PrgRam {
+            data: vec![0; size],
+            battery,
+            dirty: false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn has_battery(&self) -> bool {
+        self.battery && !self.data.is_empty()
+    }
+
+    // `None` without RAM, nothing drives the data bus
+    pub fn read(&self, addr: u16) -> Option<u8> {
+        match self.data.len() {
+            0 => None,
+            len => Some(self.data[(addr as usize & 0x1FFF) % len]),
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, value: u8) {
+        if self.data.is_empty() {
+            return;
+        }
+
+        let index = (addr as usize & 0x1FFF) % self.data.len();
+        self.dirty |= self.data[index] != value;
+        self.data[index] = value;
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    // Raw access that leaves the dirty flag alone, for the power-on state
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    // `None` when the RAM is not battery backed
+    pub fn battery_ram(&self) -> Option<Vec<u8>> {
+        self.has_battery().then(|| self.data.clone())
+    }
+
+    // Short save files fill the start of the RAM, extra bytes are ignored
+    pub fn load(&mut self, data: &[u8]) {
+        let len = data.len().min(self.data.len());
+        self.data[..len].copy_from_slice(&data[..len]);
+        self.dirty = false;
+    }
+
+    // Trainers of hacked dumps sit at $7000-$71FF from power-on, they are not save data
+    pub fn load_trainer(&mut self, trainer: &[u8]) {
+        for (addr, &value) in (TRAINER_ADDR..).zip(trainer) {
+            self.write(addr, value);
+        }
+        self.dirty = false;
+    }
+
+    // Whether battery backed RAM changed since the last call
+    pub fn take_dirty(&mut self) -> bool {
+        self.has_battery() && std::mem::take(&mut self.dirty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sized_from_header() {
+        let ram = PrgRam::new(&INes {
+            prg_ram_size: 0x2000,
+            prg_nvram_size: 0x2000,
+            battery: true,
+            ..Default::default()
+        });
+        assert_eq!(ram.len(), 0x4000);
+        assert!(ram.has_battery());
+
+        let ram = PrgRam::default();
+        assert_eq!(ram.read(0x6000), None);
+        assert_eq!(ram.battery_ram(), None);
+    }
+
+    #[test]
+    fn test_small_ram_mirrors() {
+        let mut ram = PrgRam::with_size(0x0800, false);
+        ram.write(0x6001, 0x42);
+        assert_eq!(ram.read(0x6801), Some(0x42));
+        assert_eq!(ram.read(0x7801), Some(0x42));
+    }
+
+    #[test]
+    fn test_dirty_tracking() {
+        let mut ram = PrgRam::with_size(0x2000, true);
+        assert!(!ram.take_dirty());
+
+        ram.write(0x6000, 0x00);
+        assert!(!ram.take_dirty());
+
+        ram.write(0x6000, 0x12);
+        assert!(ram.take_dirty());
+        assert!(!ram.take_dirty());
+
+        ram.write(0x6000, 0x34);
+        ram.load(&[0x56]);
+        assert!(!ram.take_dirty());
+        assert_eq!(ram.read(0x6000), Some(0x56));
+
+        // Neither is the trainer
+        let mut ram = PrgRam::with_size(0x2000, true);
+        ram.load_trainer(&[0xAB; 512]);
+        assert!(!ram.take_dirty());
+        assert_eq!(ram.read(0x6FFF), Some(0x00));
+        assert_eq!(ram.read(0x7000), Some(0xAB));
+        assert_eq!(ram.read(0x71FF), Some(0xAB));
+        assert_eq!(ram.read(0x7200), Some(0x00));
+
+        // Work RAM without a battery is never flushed
+        let mut ram = PrgRam::with_size(0x2000, false);
+        ram.write(0x6000, 0x12);
+        assert!(!ram.take_dirty());
+    }
+}
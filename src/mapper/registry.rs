@@ -0,0 +1,206 @@
+// Board registry: maps iNES mapper/submapper numbers to a constructor and a readable board
+// name. Rows with a submapper only name a variant more precisely, the row without one
+// covers every submapper of that mapper number.
+
+use crate::sys::errors::Error;
+use crate::sys::interfaces::INes;
+
+use super::{
+    Mapper, action53, bandai, camerica, fme7, mmc2, multicart, namco108, namco163, nina, nrom,
+    sachen8259, unrom512, vrc2_4, vrc6, vrc7,
+};
+
+type Constructor = fn(&INes) -> Box<dyn Mapper>;
+
+pub struct Board {
+    pub mapper: u8,
+    pub submapper: Option<u8>, // `None` matches every submapper
+    pub name: &'static str,
+    new: Option<Constructor>, // `None` for boards without mapper hardware, PRG goes on the bus
+}
+
+const NROM: Option<Constructor> = Some(|ines| Box::new(nrom::Nrom::new(ines)));
+const MMC2: Option<Constructor> = Some(|ines| Box::new(mmc2::Mmc2::new(ines)));
+const MULTICART: Option<Constructor> = Some(|ines| Box::new(multicart::Multicart::new(ines)));
+const BANDAI: Option<Constructor> = Some(|ines| Box::new(bandai::Bandai::new(ines)));
+const NAMCO163: Option<Constructor> = Some(|ines| Box::new(namco163::Namco163::new(ines)));
+const VRC2_4: Option<Constructor> = Some(|ines| Box::new(vrc2_4::Vrc2_4::new(ines)));
+const VRC6: Option<Constructor> = Some(|ines| Box::new(vrc6::Vrc6::new(ines)));
+const ACTION53: Option<Constructor> = Some(|ines| Box::new(action53::Action53::new(ines)));
+const UNROM512: Option<Constructor> = Some(|ines| Box::new(unrom512::Unrom512::new(ines)));
+const NINA: Option<Constructor> = Some(|ines| Box::new(nina::Nina::new(ines)));
+const FME7: Option<Constructor> = Some(|ines| Box::new(fme7::Fme7::new(ines)));
+const CAMERICA: Option<Constructor> = Some(|ines| Box::new(camerica::Camerica::new(ines)));
+const NAMCO108: Option<Constructor> = Some(|ines| Box::new(namco108::Namco108::new(ines)));
+const VRC7: Option<Constructor> = Some(|ines| Box::new(vrc7::Vrc7::new(ines)));
+const SACHEN8259: Option<Constructor> = Some(|ines| Box::new(sachen8259::Sachen8259::new(ines)));
+
+const fn board(
+    mapper: u8,
+    submapper: Option<u8>,
+    name: &'static str,
+    new: Option<Constructor>,
+) -> Board {
+    Board {
+        mapper,
+        submapper,
+        name,
+        new,
+    }
+}
+
+// Sorted by mapper then submapper
+pub const BOARDS: &[Board] = &[
+    board(0, None, "NROM", NROM),
+    board(9, None, "Nintendo MMC2 (PxROM)", MMC2),
+    board(10, None, "Nintendo MMC4 (FxROM)", MMC2),
+    board(15, None, "K-1029 100-in-1", MULTICART),
+    board(16, None, "Bandai FCG/LZ93D50", BANDAI),
+    board(16, Some(4), "Bandai FCG-1/FCG-2", BANDAI),
+    board(16, Some(5), "Bandai LZ93D50 with 24C02", BANDAI),
+    board(19, None, "Namco 163", NAMCO163),
+    board(21, None, "Konami VRC4a/VRC4c", VRC2_4),
+    board(22, None, "Konami VRC2a", VRC2_4),
+    board(23, None, "Konami VRC2b/VRC4e/VRC4f", VRC2_4),
+    board(24, None, "Konami VRC6a", VRC6),
+    board(25, None, "Konami VRC2c/VRC4b/VRC4d", VRC2_4),
+    board(26, None, "Konami VRC6b", VRC6),
+    board(28, None, "Action 53", ACTION53),
+    board(30, None, "UNROM 512", UNROM512),
+    board(34, None, "AVE NINA-001/Nintendo BNROM", NINA),
+    board(34, Some(1), "AVE NINA-001", NINA),
+    board(34, Some(2), "Nintendo BNROM", NINA),
+    board(41, None, "Caltron 6-in-1", MULTICART),
+    board(57, None, "GK 6-in-1", MULTICART),
+    board(58, None, "GK 68-in-1", MULTICART),
+    board(60, None, "Reset-based 4-in-1", MULTICART),
+    board(69, None, "Sunsoft FME-7/5B", FME7),
+    board(71, None, "Camerica BF909x", CAMERICA),
+    board(71, Some(1), "Camerica BF9097 (Fire Hawk)", CAMERICA),
+    board(76, None, "NAMCOT-3446", NAMCO108),
+    board(79, None, "AVE NINA-003/006", NINA),
+    board(85, None, "Konami VRC7", VRC7),
+    board(88, None, "NAMCOT-3443", NAMCO108),
+    board(95, None, "NAMCOT-3425", NAMCO108),
+    board(137, None, "Sachen 8259D", SACHEN8259),
+    board(138, None, "Sachen 8259B", SACHEN8259),
+    board(139, None, "Sachen 8259C", SACHEN8259),
+    board(141, None, "Sachen 8259A", SACHEN8259),
+    board(153, None, "Bandai LZ93D50 with SRAM", BANDAI),
+    board(154, None, "NAMCOT-3453", NAMCO108),
+    board(157, None, "Bandai Datach", BANDAI),
+    board(159, None, "Bandai LZ93D50 with 24C01", BANDAI),
+    board(206, None, "Namco 108 (DxROM)", NAMCO108),
+    board(225, None, "52/64-in-1", MULTICART),
+    board(226, None, "76-in-1", MULTICART),
+    board(227, None, "1200-in-1", MULTICART),
+];
+
+// Well known boards that are not implemented yet, only used to name them in errors
+const UNSUPPORTED_BOARDS: &[(u8, &str)] = &[
+    (1, "Nintendo MMC1 (SxROM)"),
+    (2, "UxROM"),
+    (3, "CNROM"),
+    (4, "Nintendo MMC3 (TxROM)"),
+    (5, "Nintendo MMC5 (ExROM)"),
+    (7, "AxROM"),
+    (11, "Color Dreams"),
+    (13, "CPROM"),
+    (64, "Tengen RAMBO-1"),
+    (66, "GxROM"),
+    (118, "TxSROM"),
+    (119, "TQROM"),
+];
+
+fn find(mapper: u8, submapper: u8) -> Option<&'static Board> {
+    let exact = BOARDS
+        .iter()
+        .find(|board| board.mapper == mapper && board.submapper == Some(submapper));
+
+    exact.or_else(|| {
+        BOARDS
+            .iter()
+            .find(|board| board.mapper == mapper && board.submapper.is_none())
+    })
+}
+
+// Board name for a mapper/submapper pair, implemented or not
+pub fn board_name(mapper: u8, submapper: u8) -> &'static str {
+    match find(mapper, submapper) {
+        Some(board) => board.name,
+        None => UNSUPPORTED_BOARDS
+            .iter()
+            .find(|(number, _)| *number == mapper)
+            .map_or("unknown board", |(_, name)| name),
+    }
+}
+
+pub fn supported_boards() -> &'static [Board] {
+    BOARDS
+}
+
+// Build the mapper for the board described by the header, `None` for boards without
+// mapper hardware whose PRG ROM goes straight on the bus
+pub fn new_mapper(ines: &INes) -> Result<Option<Box<dyn Mapper>>, Error> {
+    match find(ines.mapper, ines.submapper) {
+        Some(board) => Ok(board.new.map(|new| new(ines))),
+        None => Err(Error::UnsupportedMapper {
+            mapper: ines.mapper,
+            submapper: ines.submapper,
+            name: board_name(ines.mapper, ines.submapper),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_boards_sorted_and_unique() {
+        for pair in BOARDS.windows(2) {
+            let key = |board: &Board| (board.mapper, board.submapper.map_or(-1, |sub| sub as i16));
+            assert!(
+                key(&pair[0]) < key(&pair[1]),
+                "{} before {}",
+                pair[0].name,
+                pair[1].name
+            );
+        }
+    }
+
+    #[test]
+    fn test_board_names_prefer_submapper() {
+        assert_eq!(board_name(16, 4), "Bandai FCG-1/FCG-2");
+        assert_eq!(board_name(16, 0), "Bandai FCG/LZ93D50");
+        assert_eq!(board_name(16, 7), "Bandai FCG/LZ93D50");
+        assert_eq!(board_name(4, 0), "Nintendo MMC3 (TxROM)");
+        assert_eq!(board_name(255, 0), "unknown board");
+    }
+
+    #[test]
+    fn test_new_mapper_errors_on_unsupported_boards() {
+        let ines = INes {
+            prg_rom: vec![0; 0x8000].into(),
+            mapper: 4,
+            submapper: 1,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            new_mapper(&ines).err(),
+            Some(Error::UnsupportedMapper {
+                mapper: 4,
+                submapper: 1,
+                name: "Nintendo MMC3 (TxROM)",
+            })
+        );
+
+        // NROM has no mapper hardware but still holds the CHR the PPU fetches
+        let ines = INes {
+            prg_rom: vec![0; 0x8000].into(),
+            ..Default::default()
+        };
+        assert!(new_mapper(&ines).unwrap().unwrap().chr().is_some());
+    }
+}
@@ -0,0 +1,232 @@
+// Sachen 8259 family (iNES mappers 141, 138, 139 and 137). $4100 selects one of eight
+// registers and $4101 writes it (decoded on A14, A8 and A0, mirrored up to $7FFF).
+// R0-R3 are the CHR banks for the four 2 KiB slots, R4 the upper CHR bits, R5 the
+// 32 KiB PRG bank and R7 the mirroring plus a "simple" mode that uses R0 everywhere.
+// The variants differ in how CHR bank numbers reach the ROM: 8259A adds the slot's low
+// bit, 8259B uses 2 KiB banks as is, 8259C adds two slot bits and 8259D maps 1 KiB banks
+// at $0000-$0FFF with the last 4 KiB fixed at $1000.
+
+use crate::sys::interfaces::{INes, MirroringType, RomData};
+
+use super::bank::{BankWindow, SIZE_1K, SIZE_2K, SIZE_8K, SIZE_32K};
+use super::{Mapper, VramSource, chr::Chr, named_registers, window_registers};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sachen8259Chip {
+    A, // 141
+    B, // 138
+    C, // 139
+    D, // 137
+}
+
+pub struct Sachen8259 {
+    chip: Sachen8259Chip,
+    prg_rom: RomData,
+    chr: Chr,
+    prg_window: BankWindow,
+    chr_window: BankWindow,
+    registers: [u8; 8],
+    select: u8,
+}
+
+impl Sachen8259 {
+    pub fn new(ines: &INes) -> Self {
+        let chip = match ines.mapper {
+            137 => Sachen8259Chip::D,
+            138 => Sachen8259Chip::B,
+            139 => Sachen8259Chip::C,
+            _ => Sachen8259Chip::A,
+        };
+        let chr = Chr::new(ines);
+
+        let chr_slot = match chip {
+            Sachen8259Chip::D => SIZE_1K,
+            _ => SIZE_2K,
+        };
+
+        let mut sachen = Sachen8259 {
+            chip,
+            prg_window: BankWindow::new(ines.prg_rom.len(), SIZE_32K, SIZE_32K),
+            chr_window: BankWindow::new(chr.len(), SIZE_8K, chr_slot),
+            prg_rom: ines.prg_rom.clone(),
+            chr,
+            registers: [0; 8],
+            select: 0,
+        };
+        sachen.update_banks();
+        sachen
+    }
+
+    fn update_banks(&mut self) {
+        self.prg_window
+            .select(0, SIZE_32K, self.registers[5] as usize & 0x07);
+
+        if self.chip == Sachen8259Chip::D {
+            let outer = self.registers[4] as usize;
+            let fixed = self.chr_window.last_bank(SIZE_1K).max(7) - 7;
+            for slot in 0..8 {
+                let bank = match slot {
+                    0 => self.registers[0] as usize,
+                    1 => ((outer & 0x01) << 4) | self.registers[1] as usize,
+                    2 => ((outer & 0x02) << 3) | self.registers[2] as usize,
+                    3 => {
+                        ((outer & 0x04) << 2)
+                            | ((self.registers[6] as usize & 0x01) << 3)
+                            | self.registers[3] as usize
+                    }
+                    _ => fixed + slot,
+                };
+                self.chr_window.select(slot, SIZE_1K, bank);
+            }
+            return;
+        }
+
+        for slot in 0..4 {
+            let register = match self.registers[7] & 0x01 {
+                0 => self.registers[slot],
+                _ => self.registers[0],
+            };
+            let bank = (register as usize & 0x07) | ((self.registers[4] as usize & 0x07) << 3);
+            let bank = match self.chip {
+                Sachen8259Chip::A => (bank << 1) | (slot & 0x01),
+                Sachen8259Chip::C => (bank << 2) | (slot & 0x03),
+                _ => bank,
+            };
+            self.chr_window.select(slot, SIZE_2K, bank);
+        }
+    }
+}
+
+impl Mapper for Sachen8259 {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_window.index(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr & 0xC101 {
+            0x4100 => self.select = value & 0x07,
+            0x4101 => {
+                self.registers[self.select as usize] = value & 0x07;
+                self.update_banks();
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        self.chr[self.chr_window.index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        self.chr.write(self.chr_window.index(addr), value);
+    }
+
+    fn chr(&self) -> Option<&Chr> {
+        Some(&self.chr)
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut Chr> {
+        Some(&mut self.chr)
+    }
+
+    fn registers(&self) -> Vec<(String, u16)> {
+        let mut registers = window_registers("prg", &self.prg_window);
+        registers.extend(window_registers("chr", &self.chr_window));
+        for (index, &value) in self.registers.iter().enumerate() {
+            registers.push((format!("r{}", index), value as u16));
+        }
+        registers.extend(named_registers([("select", self.select as u16)]));
+        registers
+    }
+
+    // Mode 2 maps the first nametable to page 0 and the other three to page 1
+    fn vram_source(&self, addr: u16) -> VramSource {
+        match (addr & 0x3FFF, (self.registers[7] >> 1) & 0x03) {
+            (0x0000..=0x1FFF, _) => VramSource::Cartridge,
+            (_, 2) => VramSource::Ciram(((addr >> 10) & 0x03 != 0) as u8),
+            _ => VramSource::Ciram(self.mirroring().ciram_page(addr)),
+        }
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        match (self.registers[7] >> 1) & 0x03 {
+            0 => MirroringType::Vertical,
+            1 => MirroringType::Horizontal,
+            // Closest layout for the three-to-one mode
+            2 => MirroringType::Horizontal,
+            _ => MirroringType::SingleScreenLower,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::test_rom::test_rom;
+
+    fn board(mapper: u8, chr_bank_size: usize) -> Sachen8259 {
+        let ines = test_rom(mapper)
+            .prg(SIZE_32K, 8)
+            .chr(chr_bank_size, 0x40000 / chr_bank_size);
+        Sachen8259::new(&ines.build())
+    }
+
+    fn write_register(sachen: &mut Sachen8259, register: u8, value: u8) {
+        sachen.cpu_write(0x4100, register);
+        sachen.cpu_write(0x4101, value);
+    }
+
+    #[test]
+    fn test_prg_bank_and_mirrored_ports() {
+        let mut sachen = board(141, 0x0800);
+        write_register(&mut sachen, 5, 3);
+        assert_eq!(sachen.cpu_read(0x8000), Some(3));
+
+        sachen.cpu_write(0x7F00, 5);
+        sachen.cpu_write(0x7F01, 6);
+        assert_eq!(sachen.cpu_read(0xFFFF), Some(6));
+    }
+
+    #[test]
+    fn test_chr_variants() {
+        for (mapper, expected) in [(141, [10, 13]), (138, [5, 6]), (139, [20, 27])] {
+            let mut sachen = board(mapper, 0x0800);
+            write_register(&mut sachen, 0, 5);
+            write_register(&mut sachen, 3, 6);
+            assert_eq!(sachen.ppu_read(0x0000), expected[0], "mapper {mapper}");
+            assert_eq!(sachen.ppu_read(0x1800), expected[1], "mapper {mapper}");
+        }
+
+        // Simple mode and the outer bits
+        let mut sachen = board(138, 0x0800);
+        write_register(&mut sachen, 0, 5);
+        write_register(&mut sachen, 4, 1);
+        write_register(&mut sachen, 7, 1);
+        assert_eq!(sachen.ppu_read(0x1800), 13);
+    }
+
+    #[test]
+    fn test_8259d_1k_banks() {
+        let mut sachen = board(137, 0x0400);
+        write_register(&mut sachen, 1, 3);
+        write_register(&mut sachen, 4, 1);
+        assert_eq!(sachen.ppu_read(0x0400), 19);
+        assert_eq!(sachen.ppu_read(0x1000), 252);
+        assert_eq!(sachen.ppu_read(0x1C00), 255);
+    }
+
+    #[test]
+    fn test_three_to_one_nametables() {
+        let mut sachen = board(141, 0x0800);
+        write_register(&mut sachen, 7, 0x04);
+        assert_eq!(sachen.vram_source(0x2000), VramSource::Ciram(0));
+        assert_eq!(sachen.vram_source(0x2400), VramSource::Ciram(1));
+        assert_eq!(sachen.vram_source(0x2C00), VramSource::Ciram(1));
+
+        write_register(&mut sachen, 7, 0x06);
+        assert_eq!(sachen.mirroring(), MirroringType::SingleScreenLower);
+    }
+}
@@ -0,0 +1,186 @@
+// UNROM 512 (iNES mapper 30), a homebrew UNROM extension with 32 KiB of banked CHR RAM,
+// an optional one-screen mirroring select and, on boards with the battery bit set,
+// a self-flashable SST39SF040 PRG chip whose contents are kept in the save file.
+
+use crate::sys::interfaces::{INes, MirroringType};
+
+use super::Mapper;
+use super::bank::{BankWindow, SIZE_8K, SIZE_16K, SIZE_32K};
+use super::chr::Chr;
+use super::flash::Sst39Sf040;
+use super::{named_registers, window_registers};
+
+const CHR_RAM_SIZE: usize = 0x8000; // 4 banks of 8 KiB
+
+pub struct Unrom512 {
+    flash: Sst39Sf040,
+    flashable: bool,
+    chr: Chr,
+    one_screen: bool, // Bit 7 of the bank register picks the CIRAM page
+    mirroring: MirroringType,
+    prg_window: BankWindow,
+    chr_window: BankWindow,
+}
+
+impl Unrom512 {
+    pub fn new(ines: &INes) -> Self {
+        let chr = Chr::with_ram_size(ines, CHR_RAM_SIZE);
+
+        let mut prg_window = BankWindow::new(ines.prg_rom.len(), SIZE_32K, SIZE_16K);
+        prg_window.select(0, SIZE_16K, 0);
+        prg_window.select(1, SIZE_16K, prg_window.last_bank(SIZE_16K));
+
+        Unrom512 {
+            prg_window,
+            chr_window: BankWindow::new(chr.len(), SIZE_8K, SIZE_8K),
+            flash: Sst39Sf040::new(ines.prg_rom.to_vec()), // Rewritable, so not shared
+            flashable: ines.battery,
+            chr,
+            one_screen: ines.mirroring == MirroringType::SingleScreenLower,
+            mirroring: ines.mirroring.clone(),
+        }
+    }
+
+    fn write_bank(&mut self, value: u8) {
+        self.prg_window.select(0, SIZE_16K, (value & 0x1F) as usize);
+        self.chr_window
+            .select(0, SIZE_8K, ((value >> 5) & 0x03) as usize);
+
+        if self.one_screen {
+            self.mirroring = match value & 0x80 {
+                0 => MirroringType::SingleScreenLower,
+                _ => MirroringType::SingleScreenUpper,
+            };
+        }
+    }
+}
+
+impl Mapper for Unrom512 {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xFFFF => Some(self.flash.read(self.prg_window.index(addr))),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            // Flash commands go through the $8000 window of the selected bank
+            0x8000..=0xBFFF if self.flashable => {
+                let index = self.prg_window.index(addr);
+                self.flash.write(index, value);
+            }
+            0x8000..=0xFFFF => self.write_bank(value),
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        self.chr[self.chr_window.index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        self.chr.write(self.chr_window.index(addr), value);
+    }
+
+    fn chr(&self) -> Option<&Chr> {
+        Some(&self.chr)
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut Chr> {
+        Some(&mut self.chr)
+    }
+
+    fn registers(&self) -> Vec<(String, u16)> {
+        let mut registers = window_registers("prg", &self.prg_window);
+        registers.extend(window_registers("chr", &self.chr_window));
+        registers.extend(named_registers([("one_screen", self.one_screen as u16)]));
+        registers
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        self.mirroring.clone()
+    }
+
+    // The whole PRG image is saved, but only once the game has flashed it
+    fn battery_ram(&self) -> Option<Vec<u8>> {
+        match self.flashable && self.flash.dirty() {
+            true => Some(self.flash.data().to_vec()),
+            false => None,
+        }
+    }
+
+    fn load_battery_ram(&mut self, data: &[u8]) {
+        if self.flashable {
+            self.flash.load(data);
+        }
+    }
+
+    fn take_battery_ram_dirty(&mut self) -> bool {
+        self.flash.take_changed() && self.flashable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::test_rom::test_rom;
+
+    fn board(battery: bool, mirroring: MirroringType) -> Unrom512 {
+        let ines = test_rom(30)
+            .prg(SIZE_16K, 32)
+            .battery(battery)
+            .mirroring(mirroring);
+        Unrom512::new(&ines.build())
+    }
+
+    #[test]
+    fn test_prg_chr_banking_and_one_screen() {
+        let mut unrom512 = board(false, MirroringType::SingleScreenLower);
+        assert_eq!(unrom512.cpu_read(0xC000), Some(31));
+
+        unrom512.cpu_write(0x8000, 0x80 | 0x40 | 0x05);
+        assert_eq!(unrom512.cpu_read(0x8000), Some(5));
+        assert_eq!(unrom512.cpu_read(0xFFFF), Some(31));
+        assert_eq!(unrom512.mirroring(), MirroringType::SingleScreenUpper);
+
+        unrom512.ppu_write(0x0010, 0xAB);
+        unrom512.cpu_write(0x8000, 0x00);
+        assert_eq!(unrom512.ppu_read(0x0010), 0x00);
+        assert_eq!(unrom512.mirroring(), MirroringType::SingleScreenLower);
+        unrom512.cpu_write(0x8000, 0x40);
+        assert_eq!(unrom512.ppu_read(0x0010), 0xAB);
+
+        // Without the one-screen board layout bit 7 does nothing
+        let mut unrom512 = board(false, MirroringType::Vertical);
+        unrom512.cpu_write(0x8000, 0x80);
+        assert_eq!(unrom512.mirroring(), MirroringType::Vertical);
+    }
+
+    #[test]
+    fn test_self_flash_saved() {
+        let mut unrom512 = board(true, MirroringType::Vertical);
+        assert_eq!(unrom512.battery_ram(), None);
+
+        // Unlock through banks 1 and 0 as games do, then program bank 3
+        let mut command = |bank: u8, addr: u16, value: u8| {
+            unrom512.cpu_write(0xC000, bank);
+            unrom512.cpu_write(addr, value);
+        };
+        command(1, 0x9555, 0xAA);
+        command(0, 0xAAAA, 0x55);
+        command(1, 0x9555, 0xA0);
+        command(3, 0x8123, 0x01);
+        assert!(unrom512.take_battery_ram_dirty());
+        assert!(!unrom512.take_battery_ram_dirty());
+
+        assert_eq!(unrom512.cpu_read(0x8123), Some(0x01));
+        let saved = unrom512.battery_ram().unwrap();
+        assert_eq!(saved[3 * SIZE_16K + 0x123], 0x01);
+
+        let mut reloaded = board(true, MirroringType::Vertical);
+        reloaded.load_battery_ram(&saved);
+        reloaded.cpu_write(0xC000, 3);
+        assert_eq!(reloaded.cpu_read(0x8123), Some(0x01));
+    }
+}
@@ -0,0 +1,318 @@
+// Konami VRC2 and VRC4 (iNES mappers 21, 22, 23 and 25).
+// Every board routes two different CPU address lines into the chip's register
+// select pins, so the register address is rebuilt from the submapper wiring first.
+
+use crate::sys::interfaces::{INes, MirroringType, RomData};
+
+use super::bank::{BankWindow, SIZE_1K, SIZE_8K, SIZE_32K};
+use super::{
+    Mapper, chr::Chr, named_registers, prg_ram::PrgRam, vrc_irq::VrcIrq, window_registers,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VrcChip {
+    Vrc2,
+    Vrc4,
+}
+
+pub struct Vrc2_4 {
+    chip: VrcChip,
+    prg_rom: RomData,
+    prg_ram: PrgRam,
+    chr: Chr,
+    a0_lines: u16, // CPU address lines wired to the chip A0 pin
+    a1_lines: u16, // CPU address lines wired to the chip A1 pin
+    chr_shift: u8, // VRC2a ignores the low bit of every CHR bank
+    prg_banks: [u8; 2],
+    prg_swap: bool,
+    chr_banks: [u16; 8],
+    prg_window: BankWindow,
+    chr_window: BankWindow,
+    mirroring: MirroringType,
+    microwire: u8,
+    irq: VrcIrq,
+}
+
+impl Vrc2_4 {
+    pub fn new(ines: &INes) -> Self {
+        let (chip, a0_lines, a1_lines) = Self::wiring(ines.mapper, ines.submapper);
+        let chr = Chr::new(ines);
+
+        let mut vrc = Vrc2_4 {
+            chip,
+            prg_window: BankWindow::new(ines.prg_rom.len(), SIZE_32K, SIZE_8K),
+            chr_window: BankWindow::new(chr.len(), SIZE_8K, SIZE_1K),
+            prg_rom: ines.prg_rom.clone(),
+            prg_ram: PrgRam::new(ines),
+            chr,
+            a0_lines,
+            a1_lines,
+            chr_shift: if ines.mapper == 22 { 1 } else { 0 },
+            prg_banks: [0, 1],
+            prg_swap: false,
+            chr_banks: [0; 8],
+            mirroring: ines.mirroring.clone(),
+            microwire: 0,
+            irq: VrcIrq::new(),
+        };
+        vrc.update_banks();
+        vrc
+    }
+
+    // Submapper 0 is the legacy iNES assignment where the wiring is unknown, so both
+    // candidate line pairs are ORed together, which works for every board of that mapper.
+    fn wiring(mapper: u8, submapper: u8) -> (VrcChip, u16, u16) {
+        match (mapper, submapper) {
+            (21, 1) => (VrcChip::Vrc4, 0x02, 0x04), // VRC4a
+            (21, 2) => (VrcChip::Vrc4, 0x40, 0x80), // VRC4c
+            (21, _) => (VrcChip::Vrc4, 0x42, 0x84),
+            (22, _) => (VrcChip::Vrc2, 0x02, 0x01), // VRC2a
+            (23, 1) => (VrcChip::Vrc4, 0x01, 0x02), // VRC4f
+            (23, 2) => (VrcChip::Vrc4, 0x04, 0x08), // VRC4e
+            (23, 3) => (VrcChip::Vrc2, 0x01, 0x02), // VRC2b
+            (23, _) => (VrcChip::Vrc4, 0x05, 0x0A),
+            (25, 1) => (VrcChip::Vrc4, 0x02, 0x01), // VRC4b
+            (25, 2) => (VrcChip::Vrc4, 0x08, 0x04), // VRC4d
+            (25, 3) => (VrcChip::Vrc2, 0x02, 0x01), // VRC2c
+            _ => (VrcChip::Vrc4, 0x0A, 0x05),
+        }
+    }
+
+    pub fn chip(&self) -> VrcChip {
+        self.chip
+    }
+
+    // Translate a CPU address into the chip register $x000-$x003 it selects
+    fn register(&self, addr: u16) -> u16 {
+        let a0 = (addr & self.a0_lines != 0) as u16;
+        let a1 = (addr & self.a1_lines != 0) as u16;
+
+        (addr & 0xF000) | (a1 << 1) | a0
+    }
+
+    fn update_banks(&mut self) {
+        let last = self.prg_window.last_bank(SIZE_8K);
+        let (first, third) = match self.prg_swap {
+            false => (self.prg_banks[0] as usize, last.saturating_sub(1)),
+            true => (last.saturating_sub(1), self.prg_banks[0] as usize),
+        };
+        self.prg_window.select(0, SIZE_8K, first);
+        self.prg_window
+            .select(1, SIZE_8K, self.prg_banks[1] as usize);
+        self.prg_window.select(2, SIZE_8K, third);
+        self.prg_window.select(3, SIZE_8K, last);
+
+        for (slot, bank) in self.chr_banks.iter().enumerate() {
+            let bank = (bank >> self.chr_shift) as usize;
+            self.chr_window.select(slot, SIZE_1K, bank);
+        }
+    }
+
+    fn write_chr_bank(&mut self, register: u16, value: u8) {
+        let slot = (((register >> 12) - 0x0B) * 2 + ((register >> 1) & 0x01)) as usize;
+        let bank = &mut self.chr_banks[slot];
+
+        if register & 0x01 == 0 {
+            *bank = (*bank & 0x1F0) | (value & 0x0F) as u16;
+        } else {
+            let high_mask = match self.chip {
+                VrcChip::Vrc2 => 0x0F,
+                VrcChip::Vrc4 => 0x1F,
+            };
+            *bank = (*bank & 0x00F) | (((value & high_mask) as u16) << 4);
+        }
+    }
+
+    fn write_mirroring(&mut self, value: u8) {
+        self.mirroring = match (self.chip, value & 0x03) {
+            (VrcChip::Vrc2, bits) if bits & 0x01 == 0 => MirroringType::Vertical,
+            (VrcChip::Vrc2, _) => MirroringType::Horizontal,
+            (VrcChip::Vrc4, 0) => MirroringType::Vertical,
+            (VrcChip::Vrc4, 1) => MirroringType::Horizontal,
+            (VrcChip::Vrc4, 2) => MirroringType::SingleScreenLower,
+            (VrcChip::Vrc4, _) => MirroringType::SingleScreenUpper,
+        };
+    }
+}
+
+impl Mapper for Vrc2_4 {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match (self.chip, addr) {
+            // VRC2 boards without RAM expose a one bit latch, the upper bits are open bus
+            (VrcChip::Vrc2, 0x6000..=0x6FFF) => Some(((addr >> 8) as u8 & 0xFE) | self.microwire),
+            (VrcChip::Vrc4, 0x6000..=0x7FFF) => self.prg_ram.read(addr),
+            (_, 0x8000..=0xFFFF) => Some(self.prg_rom[self.prg_window.index(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match (self.chip, addr) {
+            (VrcChip::Vrc2, 0x6000..=0x6FFF) => self.microwire = value & 0x01,
+            (VrcChip::Vrc4, 0x6000..=0x7FFF) => self.prg_ram.write(addr, value),
+            (_, 0x8000..=0xFFFF) => {
+                let register = self.register(addr);
+
+                match (self.chip, register) {
+                    (_, 0x8000..=0x8003) => self.prg_banks[0] = value & 0x1F,
+                    (VrcChip::Vrc2, 0x9000..=0x9003) => self.write_mirroring(value),
+                    (VrcChip::Vrc4, 0x9000..=0x9001) => self.write_mirroring(value),
+                    (VrcChip::Vrc4, 0x9002..=0x9003) => self.prg_swap = value & 0x02 != 0,
+                    (_, 0xA000..=0xA003) => self.prg_banks[1] = value & 0x1F,
+                    (_, 0xB000..=0xEFFF) => self.write_chr_bank(register, value),
+                    (VrcChip::Vrc4, 0xF000) => self.irq.write_latch_low(value),
+                    (VrcChip::Vrc4, 0xF001) => self.irq.write_latch_high(value),
+                    (VrcChip::Vrc4, 0xF002) => self.irq.write_control(value),
+                    (VrcChip::Vrc4, 0xF003) => self.irq.acknowledge(),
+                    _ => {}
+                }
+                self.update_banks();
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        self.chr[self.chr_window.index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        self.chr.write(self.chr_window.index(addr), value);
+    }
+
+    fn chr(&self) -> Option<&Chr> {
+        Some(&self.chr)
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut Chr> {
+        Some(&mut self.chr)
+    }
+
+    fn registers(&self) -> Vec<(String, u16)> {
+        let mut registers = window_registers("prg", &self.prg_window);
+        registers.extend(window_registers("chr", &self.chr_window));
+        registers.extend(named_registers([
+            ("prg_swap", self.prg_swap as u16),
+            ("microwire", self.microwire as u16),
+            ("irq_latch", self.irq.latch as u16),
+            ("irq_counter", self.irq.counter as u16),
+        ]));
+        registers
+    }
+
+    fn prg_ram(&self) -> Option<&PrgRam> {
+        Some(&self.prg_ram)
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut PrgRam> {
+        Some(&mut self.prg_ram)
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        self.mirroring.clone()
+    }
+
+    fn cpu_clock(&mut self) {
+        if self.chip == VrcChip::Vrc4 {
+            self.irq.clock();
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq.pending()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::test_rom::test_rom;
+
+    fn board(mapper: u8, submapper: u8) -> Vrc2_4 {
+        let ines = test_rom(mapper)
+            .submapper(submapper)
+            .prg(SIZE_8K, 16)
+            .chr(SIZE_1K, 256);
+        Vrc2_4::new(&ines.build())
+    }
+
+    #[test]
+    fn test_vrc4_fixed_banks_and_prg_swap() {
+        let mut vrc = board(21, 1);
+        vrc.cpu_write(0x8000, 3);
+        vrc.cpu_write(0xA000, 5);
+        assert_eq!(vrc.cpu_read(0x8000), Some(3));
+        assert_eq!(vrc.cpu_read(0xA000), Some(5));
+        assert_eq!(vrc.cpu_read(0xC000), Some(14));
+        assert_eq!(vrc.cpu_read(0xE000), Some(15));
+
+        vrc.cpu_write(0x9004, 0x02); // $9002 on VRC4a
+        assert_eq!(vrc.cpu_read(0x8000), Some(14));
+        assert_eq!(vrc.cpu_read(0xC000), Some(3));
+    }
+
+    #[test]
+    fn test_submapper_wiring_selects_registers() {
+        // VRC4c uses A6/A7, so $B0C0 is the high nibble of CHR bank 1
+        let mut vrc = board(21, 2);
+        vrc.cpu_write(0xB080, 0x02);
+        vrc.cpu_write(0xB0C0, 0x01);
+        assert_eq!(vrc.ppu_read(0x0400), 0x12);
+
+        // VRC4b swaps the lines, so $B001 selects $B002, the low nibble of CHR bank 1
+        let mut vrc = board(25, 1);
+        vrc.cpu_write(0xB001, 0x07);
+        assert_eq!(vrc.ppu_read(0x0400), 0x07);
+        assert_eq!(vrc.ppu_read(0x0000), 0x00);
+    }
+
+    #[test]
+    fn test_vrc2a_ignores_low_chr_bit_and_latches_microwire() {
+        let mut vrc = board(22, 0);
+        assert_eq!(vrc.chip(), VrcChip::Vrc2);
+        vrc.cpu_write(0xB000, 0x05);
+        assert_eq!(vrc.ppu_read(0x0000), 0x02);
+
+        vrc.cpu_write(0x6000, 0xFF);
+        assert_eq!(vrc.cpu_read(0x6000).unwrap() & 0x01, 0x01);
+        vrc.cpu_write(0x6000, 0xFE);
+        assert_eq!(vrc.cpu_read(0x6000).unwrap() & 0x01, 0x00);
+    }
+
+    #[test]
+    fn test_vrc4_mirroring_control() {
+        let mut vrc = board(23, 1);
+        vrc.cpu_write(0x9000, 0x03);
+        assert_eq!(vrc.mirroring(), MirroringType::SingleScreenUpper);
+        vrc.cpu_write(0x9000, 0x01);
+        assert_eq!(vrc.mirroring(), MirroringType::Horizontal);
+    }
+
+    #[test]
+    fn test_vrc4_irq_cycle_and_scanline_mode() {
+        let mut vrc = board(23, 1);
+        vrc.cpu_write(0xF000, 0x0D);
+        vrc.cpu_write(0xF001, 0x0F); // latch = $FD
+        vrc.cpu_write(0xF002, 0x06); // enabled, cycle mode
+
+        vrc.cpu_clock();
+        vrc.cpu_clock();
+        assert!(!vrc.irq_pending());
+        vrc.cpu_clock();
+        assert!(vrc.irq_pending());
+
+        vrc.cpu_write(0xF003, 0x00);
+        assert!(!vrc.irq_pending());
+
+        // Scanline mode clocks the counter every 113.67 CPU cycles
+        vrc.cpu_write(0xF001, 0x0F);
+        vrc.cpu_write(0xF000, 0x0F); // latch = $FF
+        vrc.cpu_write(0xF002, 0x02);
+        for _ in 0..113 {
+            vrc.cpu_clock();
+        }
+        assert!(!vrc.irq_pending());
+        vrc.cpu_clock();
+        assert!(vrc.irq_pending());
+    }
+}
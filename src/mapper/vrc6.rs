@@ -0,0 +1,274 @@
+// Konami VRC6 (iNES mappers 24 and 26).
+// VRC6b (mapper 26) swaps the A0 and A1 lines going into the chip, the expansion
+// sound lives in the $9000-$B002 registers.
+
+use crate::apu::vrc6::Vrc6Audio;
+use crate::sys::interfaces::{INes, MirroringType, RomData};
+
+use super::bank::{BankWindow, SIZE_1K, SIZE_8K, SIZE_16K, SIZE_32K};
+use super::{
+    Mapper, chr::Chr, named_registers, prg_ram::PrgRam, vrc_irq::VrcIrq, window_registers,
+};
+
+pub struct Vrc6 {
+    prg_rom: RomData,
+    prg_ram: PrgRam,
+    chr: Chr,
+    swapped_lines: bool,
+    prg_bank_16k: u8,
+    prg_bank_8k: u8,
+    chr_banks: [u8; 8],
+    banking_control: u8,
+    prg_window: BankWindow,
+    chr_window: BankWindow,
+    irq: VrcIrq,
+    audio: Vrc6Audio,
+}
+
+impl Vrc6 {
+    pub fn new(ines: &INes) -> Self {
+        let chr = Chr::new(ines);
+
+        let mut vrc6 = Vrc6 {
+            prg_window: BankWindow::new(ines.prg_rom.len(), SIZE_32K, SIZE_8K),
+            chr_window: BankWindow::new(chr.len(), SIZE_8K, SIZE_1K),
+            prg_rom: ines.prg_rom.clone(),
+            prg_ram: PrgRam::new(ines),
+            chr,
+            swapped_lines: ines.mapper == 26,
+            prg_bank_16k: 0,
+            prg_bank_8k: 0,
+            chr_banks: [0; 8],
+            banking_control: 0,
+            irq: VrcIrq::new(),
+            audio: Vrc6Audio::new(),
+        };
+        vrc6.update_banks();
+        vrc6
+    }
+
+    fn register(&self, addr: u16) -> u16 {
+        let (a0, a1) = if self.swapped_lines {
+            ((addr >> 1) & 0x01, addr & 0x01)
+        } else {
+            (addr & 0x01, (addr >> 1) & 0x01)
+        };
+
+        (addr & 0xF000) | (a1 << 1) | a0
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.banking_control & 0x80 != 0
+    }
+
+    // $B003 bits 0-1 pick between eight 1 KiB banks, four 2 KiB banks or a mix of both,
+    // in the 2 KiB layouts bit 5 takes the low bank bit from PPU A10 instead of the register
+    fn chr_bank(&self, addr: u16) -> usize {
+        let a10 = ((addr >> 10) & 0x01) as u8;
+        let two_kib = |register: u8| {
+            if self.banking_control & 0x20 != 0 {
+                (register & 0xFE) | a10
+            } else {
+                register
+            }
+        };
+
+        let bank = match (self.banking_control & 0x03, addr) {
+            (0, _) => self.chr_banks[(addr >> 10) as usize & 0x07],
+            (1, _) => two_kib(self.chr_banks[(addr >> 11) as usize & 0x03]),
+            (_, 0x0000..=0x0FFF) => self.chr_banks[(addr >> 10) as usize & 0x03],
+            (_, _) => two_kib(self.chr_banks[4 + ((addr >> 11) as usize & 0x01)]),
+        };
+
+        bank as usize
+    }
+
+    fn update_banks(&mut self) {
+        self.prg_window
+            .select(0, SIZE_16K, self.prg_bank_16k as usize);
+        self.prg_window
+            .select(2, SIZE_8K, self.prg_bank_8k as usize);
+        self.prg_window
+            .select(3, SIZE_8K, self.prg_window.last_bank(SIZE_8K));
+
+        for slot in 0..8 {
+            let bank = self.chr_bank((slot * SIZE_1K) as u16);
+            self.chr_window.select(slot, SIZE_1K, bank);
+        }
+    }
+}
+
+impl Mapper for Vrc6 {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => self.prg_ram.read(addr),
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_window.index(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        if addr < 0x8000 {
+            if (0x6000..=0x7FFF).contains(&addr) && self.prg_ram_enabled() {
+                self.prg_ram.write(addr, value);
+            }
+            return;
+        }
+
+        match self.register(addr) {
+            0x8000..=0x8003 => self.prg_bank_16k = value & 0x0F,
+            0x9000 => self.audio.pulse1.write_control(value),
+            0x9001 => self.audio.pulse1.write_period_low(value),
+            0x9002 => self.audio.pulse1.write_period_high(value),
+            0x9003 => self.audio.write_frequency_control(value),
+            0xA000 => self.audio.pulse2.write_control(value),
+            0xA001 => self.audio.pulse2.write_period_low(value),
+            0xA002 => self.audio.pulse2.write_period_high(value),
+            0xB000 => self.audio.sawtooth.write_rate(value),
+            0xB001 => self.audio.sawtooth.write_period_low(value),
+            0xB002 => self.audio.sawtooth.write_period_high(value),
+            0xB003 => self.banking_control = value,
+            0xC000..=0xC003 => self.prg_bank_8k = value & 0x1F,
+            register @ 0xD000..=0xE003 => {
+                let slot = (((register >> 12) - 0x0D) * 4 + (register & 0x03)) as usize;
+                self.chr_banks[slot] = value;
+            }
+            0xF000 => self.irq.write_latch(value),
+            0xF001 => self.irq.write_control(value),
+            0xF002 => self.irq.acknowledge(),
+            _ => {}
+        }
+        self.update_banks();
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        self.chr[self.chr_window.index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        self.chr.write(self.chr_window.index(addr), value);
+    }
+
+    fn chr(&self) -> Option<&Chr> {
+        Some(&self.chr)
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut Chr> {
+        Some(&mut self.chr)
+    }
+
+    fn registers(&self) -> Vec<(String, u16)> {
+        let mut registers = window_registers("prg", &self.prg_window);
+        registers.extend(window_registers("chr", &self.chr_window));
+        registers.extend(named_registers([
+            ("banking_control", self.banking_control as u16),
+            ("irq_latch", self.irq.latch as u16),
+            ("irq_counter", self.irq.counter as u16),
+        ]));
+        registers
+    }
+
+    fn prg_ram(&self) -> Option<&PrgRam> {
+        Some(&self.prg_ram)
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut PrgRam> {
+        Some(&mut self.prg_ram)
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        match (self.banking_control >> 2) & 0x03 {
+            0 => MirroringType::Vertical,
+            1 => MirroringType::Horizontal,
+            2 => MirroringType::SingleScreenLower,
+            _ => MirroringType::SingleScreenUpper,
+        }
+    }
+
+    fn cpu_clock(&mut self) {
+        self.irq.clock();
+        self.audio.clock();
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq.pending()
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.audio.output()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::test_rom::test_rom;
+
+    fn board(mapper: u8) -> Vrc6 {
+        Vrc6::new(&test_rom(mapper).prg(SIZE_8K, 32).chr(SIZE_1K, 128).build())
+    }
+
+    #[test]
+    fn test_prg_banking() {
+        let mut vrc = board(24);
+        vrc.cpu_write(0x8000, 0x03);
+        vrc.cpu_write(0xC000, 0x09);
+        assert_eq!(vrc.cpu_read(0x8000), Some(6));
+        assert_eq!(vrc.cpu_read(0xA000), Some(7));
+        assert_eq!(vrc.cpu_read(0xC000), Some(9));
+        assert_eq!(vrc.cpu_read(0xE000), Some(31));
+    }
+
+    #[test]
+    fn test_vrc6b_swaps_register_lines() {
+        let mut vrc = board(26);
+        vrc.cpu_write(0xD001, 0x11); // $D002 on VRC6b
+        assert_eq!(vrc.ppu_read(0x0800), 0x11);
+
+        vrc.cpu_write(0xB003, 0x84); // $B003 is the same on both boards
+        assert_eq!(vrc.mirroring(), MirroringType::Horizontal);
+        vrc.cpu_write(0x6000, 0x42);
+        assert_eq!(vrc.cpu_read(0x6000), Some(0x42));
+    }
+
+    #[test]
+    fn test_pulse_duty_cycle() {
+        let mut vrc = board(24);
+        vrc.cpu_write(0x9000, 0x3F); // duty 4/16, volume 15
+        vrc.cpu_write(0x9001, 0x00);
+        vrc.cpu_write(0x9002, 0x80); // enabled, period 0
+
+        let high = (0..16)
+            .filter(|_| {
+                vrc.cpu_clock();
+                vrc.audio_output() > 0.0
+            })
+            .count();
+        assert_eq!(high, 4);
+
+        vrc.cpu_write(0x9000, 0x8A); // ignore duty, volume 10
+        vrc.cpu_clock();
+        assert_eq!(vrc.audio_output(), 10.0);
+    }
+
+    #[test]
+    fn test_sawtooth_accumulates_and_resets() {
+        let mut vrc = board(24);
+        vrc.cpu_write(0xB000, 0x2A); // rate 42
+        vrc.cpu_write(0xB002, 0x80);
+
+        let outputs: Vec<f32> = (0..14)
+            .map(|_| {
+                vrc.cpu_clock();
+                vrc.audio_output()
+            })
+            .collect();
+        assert_eq!(outputs[11], ((42 * 6) >> 3) as f32);
+        assert_eq!(outputs[13], 0.0);
+
+        vrc.cpu_write(0x9003, 0x01); // halt
+        vrc.cpu_clock();
+        vrc.cpu_clock();
+        assert_eq!(vrc.audio_output(), 0.0);
+    }
+}
@@ -0,0 +1,240 @@
+// Konami VRC7 (iNES mapper 85).
+// VRC7b (submapper 1) selects the odd registers with A3, VRC7a (submapper 2) with A4.
+// The OPLL sound chip is only wired on VRC7a boards, through $9010 and $9030.
+
+use crate::apu::vrc7::Vrc7Audio;
+use crate::sys::interfaces::{INes, MirroringType, RomData};
+
+use super::bank::{BankWindow, SIZE_1K, SIZE_8K, SIZE_32K};
+use super::{
+    Mapper, chr::Chr, named_registers, prg_ram::PrgRam, vrc_irq::VrcIrq, window_registers,
+};
+
+pub struct Vrc7 {
+    prg_rom: RomData,
+    prg_ram: PrgRam,
+    chr: Chr,
+    odd_register_lines: u16,
+    prg_window: BankWindow,
+    chr_window: BankWindow,
+    control: u8,
+    irq: VrcIrq,
+    audio: Vrc7Audio,
+}
+
+impl Vrc7 {
+    pub fn new(ines: &INes) -> Self {
+        let chr = Chr::new(ines);
+        let odd_register_lines = match ines.submapper {
+            1 => 0x08,
+            2 => 0x10,
+            _ => 0x18,
+        };
+
+        let mut prg_window = BankWindow::new(ines.prg_rom.len(), SIZE_32K, SIZE_8K);
+        prg_window.select(0, SIZE_8K, 0);
+        prg_window.select(1, SIZE_8K, 0);
+        prg_window.select(2, SIZE_8K, 0);
+        prg_window.select(3, SIZE_8K, prg_window.last_bank(SIZE_8K));
+        let mut chr_window = BankWindow::new(chr.len(), SIZE_8K, SIZE_1K);
+        for slot in 0..8 {
+            chr_window.select(slot, SIZE_1K, 0);
+        }
+
+        Vrc7 {
+            prg_rom: ines.prg_rom.clone(),
+            prg_ram: PrgRam::new(ines),
+            chr,
+            odd_register_lines,
+            prg_window,
+            chr_window,
+            control: 0,
+            irq: VrcIrq::new(),
+            audio: Vrc7Audio::new(),
+        }
+    }
+
+    fn register(&self, addr: u16) -> u16 {
+        let odd = if addr & self.odd_register_lines != 0 {
+            0x10
+        } else {
+            0x00
+        };
+
+        (addr & 0xF000) | odd
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.control & 0x80 != 0
+    }
+}
+
+impl Mapper for Vrc7 {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => self.prg_ram.read(addr),
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_window.index(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => self.prg_ram.write(addr, value),
+            0x8000..=0xFFFF => match (addr & 0xF030, self.register(addr)) {
+                (0x9010, _) => self.audio.write_address(value),
+                (0x9030, _) => self.audio.write_data(value),
+                (_, 0x8000) => self.prg_window.select(0, SIZE_8K, (value & 0x3F) as usize),
+                (_, 0x8010) => self.prg_window.select(1, SIZE_8K, (value & 0x3F) as usize),
+                (_, 0x9000) => self.prg_window.select(2, SIZE_8K, (value & 0x3F) as usize),
+                (_, register @ 0xA000..=0xD010) => {
+                    let slot = (((register >> 12) - 0x0A) * 2 + ((register >> 4) & 0x01)) as usize;
+                    self.chr_window.select(slot, SIZE_1K, value as usize);
+                }
+                (_, 0xE000) => {
+                    self.control = value;
+                    self.audio.set_silenced(value & 0x40 != 0);
+                }
+                (_, 0xE010) => self.irq.write_latch(value),
+                (_, 0xF000) => self.irq.write_control(value),
+                (_, 0xF010) => self.irq.acknowledge(),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        self.chr[self.chr_window.index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        self.chr.write(self.chr_window.index(addr), value);
+    }
+
+    fn chr(&self) -> Option<&Chr> {
+        Some(&self.chr)
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut Chr> {
+        Some(&mut self.chr)
+    }
+
+    fn registers(&self) -> Vec<(String, u16)> {
+        let mut registers = window_registers("prg", &self.prg_window);
+        registers.extend(window_registers("chr", &self.chr_window));
+        registers.extend(named_registers([
+            ("control", self.control as u16),
+            ("irq_latch", self.irq.latch as u16),
+            ("irq_counter", self.irq.counter as u16),
+        ]));
+        registers
+    }
+
+    fn prg_ram(&self) -> Option<&PrgRam> {
+        Some(&self.prg_ram)
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut PrgRam> {
+        Some(&mut self.prg_ram)
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        match self.control & 0x03 {
+            0 => MirroringType::Vertical,
+            1 => MirroringType::Horizontal,
+            2 => MirroringType::SingleScreenLower,
+            _ => MirroringType::SingleScreenUpper,
+        }
+    }
+
+    fn cpu_clock(&mut self) {
+        self.irq.clock();
+        self.audio.clock();
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq.pending()
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.audio.output()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::test_rom::test_rom;
+
+    const CYCLES_PER_SAMPLE: usize = 36;
+
+    fn board(submapper: u8) -> Vrc7 {
+        Vrc7::new(&test_rom(85).submapper(submapper).prg(SIZE_8K, 16).build())
+    }
+
+    fn write_audio(vrc: &mut Vrc7, register: u8, value: u8) {
+        vrc.cpu_write(0x9010, register);
+        vrc.cpu_write(0x9030, value);
+    }
+
+    fn run_samples(vrc: &mut Vrc7, samples: usize) -> Vec<f32> {
+        (0..samples)
+            .map(|_| {
+                (0..CYCLES_PER_SAMPLE).for_each(|_| vrc.cpu_clock());
+                vrc.audio_output()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_prg_banking_per_wiring() {
+        let mut vrc = board(1);
+        vrc.cpu_write(0x8008, 0x05);
+        vrc.cpu_write(0x9000, 0x07);
+        assert_eq!(vrc.cpu_read(0xA000), Some(5));
+        assert_eq!(vrc.cpu_read(0xC000), Some(7));
+        assert_eq!(vrc.cpu_read(0xE000), Some(15));
+
+        let mut vrc = board(2);
+        vrc.cpu_write(0x8010, 0x03);
+        assert_eq!(vrc.cpu_read(0xA000), Some(3));
+        vrc.cpu_write(0xE000, 0x81);
+        assert_eq!(vrc.mirroring(), MirroringType::Horizontal);
+        vrc.cpu_write(0x7FFF, 0x5A);
+        assert_eq!(vrc.cpu_read(0x7FFF), Some(0x5A));
+    }
+
+    #[test]
+    fn test_fm_channel_keys_on_and_releases() {
+        let mut vrc = board(2);
+        write_audio(&mut vrc, 0x30, 0x30); // Wurly, full volume
+        write_audio(&mut vrc, 0x10, 0xAC);
+        write_audio(&mut vrc, 0x20, 0x19); // Key on, octave 4
+
+        let playing = run_samples(&mut vrc, 2000);
+        let peak = playing
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        assert!(peak > 1.0);
+        assert!(playing.iter().any(|sample| *sample < 0.0));
+
+        write_audio(&mut vrc, 0x20, 0x09); // Key off
+        let released = run_samples(&mut vrc, 50000);
+        assert!(released.iter().rev().take(100).all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn test_silence_bit_resets_audio() {
+        let mut vrc = board(2);
+        write_audio(&mut vrc, 0x30, 0x10);
+        write_audio(&mut vrc, 0x10, 0x80);
+        write_audio(&mut vrc, 0x20, 0x1A);
+        run_samples(&mut vrc, 500);
+        assert_ne!(vrc.audio_output(), 0.0);
+
+        vrc.cpu_write(0xE000, 0x40);
+        run_samples(&mut vrc, 10);
+        assert_eq!(vrc.audio_output(), 0.0);
+    }
+}
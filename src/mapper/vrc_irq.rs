@@ -0,0 +1,83 @@
+// IRQ counter shared by the Konami VRC4, VRC6 and VRC7.
+// In scanline mode a prescaler divides CPU cycles by 341/3 (one NTSC scanline),
+// in cycle mode the 8-bit counter is clocked on every CPU cycle.
+
+const PRESCALER_RELOAD: i16 = 341;
+const PRESCALER_STEP: i16 = 3;
+
+#[derive(Debug, Default, Clone)]
+pub struct VrcIrq {
+    pub latch: u8,
+    pub counter: u8,
+    prescaler: i16,
+    enabled: bool,
+    enable_after_ack: bool,
+    cycle_mode: bool,
+    pending: bool,
+}
+
+impl VrcIrq {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write_latch_low(&mut self, value: u8) {
+        self.latch = (self.latch & 0xF0) | (value & 0x0F);
+    }
+
+    pub fn write_latch_high(&mut self, value: u8) {
+        self.latch = (self.latch & 0x0F) | ((value & 0x0F) << 4);
+    }
+
+    pub fn write_latch(&mut self, value: u8) {
+        self.latch = value;
+    }
+
+    // Control register: bit 0 = enable after acknowledge, bit 1 = enable, bit 2 = cycle mode
+    pub fn write_control(&mut self, value: u8) {
+        self.enable_after_ack = value & 0x01 != 0;
+        self.enabled = value & 0x02 != 0;
+        self.cycle_mode = value & 0x04 != 0;
+        self.pending = false;
+
+        if self.enabled {
+            self.counter = self.latch;
+            self.prescaler = PRESCALER_RELOAD;
+        }
+    }
+
+    pub fn acknowledge(&mut self) {
+        self.pending = false;
+        self.enabled = self.enable_after_ack;
+    }
+
+    pub fn clock(&mut self) {
+        if !self.enabled {
+            return;
+        }
+
+        if self.cycle_mode {
+            self.clock_counter();
+            return;
+        }
+
+        self.prescaler -= PRESCALER_STEP;
+        if self.prescaler <= 0 {
+            self.prescaler += PRESCALER_RELOAD;
+            self.clock_counter();
+        }
+    }
+
+    fn clock_counter(&mut self) {
+        if self.counter == 0xFF {
+            self.counter = self.latch;
+            self.pending = true;
+        } else {
+            self.counter += 1;
+        }
+    }
+
+    pub fn pending(&self) -> bool {
+        self.pending
+    }
+}
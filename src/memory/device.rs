@@ -0,0 +1,178 @@
+// Memory mapped devices plugged into the bus on an address range, for add-ons and test
+// hardware that should not live in `Bus::read`/`Bus::write`. Devices answer before the
+// cartridge and the console's own registers and get the CPU cycle of every access.
+
+use std::any::Any;
+use std::io::Write;
+use std::ops::RangeInclusive;
+
+pub const DEBUG_CONSOLE_PORT: u16 = 0x401F;
+
+pub trait Device: Any {
+    // `None` leaves the data bus floating (open bus)
+    fn read(&mut self, _addr: u16, _cycle: u64) -> Option<u8> {
+        None
+    }
+
+    // Side-effect-free read for debuggers
+    fn peek(&self, _addr: u16) -> Option<u8> {
+        None
+    }
+
+    fn write(&mut self, addr: u16, value: u8, cycle: u64);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceId(usize);
+
+pub(crate) struct MappedDevice {
+    id: DeviceId,
+    range: RangeInclusive<u16>,
+    device: Box<dyn Device>,
+}
+
+// Registered devices, looked up by address on every bus access
+#[derive(Default)]
+pub(crate) struct DeviceMap {
+    devices: Vec<MappedDevice>,
+    next_id: usize,
+}
+
+impl DeviceMap {
+    pub fn insert(
+        &mut self,
+        range: RangeInclusive<u16>,
+        device: Box<dyn Device>,
+    ) -> Option<DeviceId> {
+        let overlaps = self.devices.iter().any(|mapped| {
+            mapped.range.start() <= range.end() && range.start() <= mapped.range.end()
+        });
+        if overlaps || range.is_empty() {
+            return None;
+        }
+
+        let id = DeviceId(self.next_id);
+        self.next_id += 1;
+        self.devices.push(MappedDevice { id, range, device });
+        Some(id)
+    }
+
+    pub fn remove(&mut self, id: DeviceId) -> Option<Box<dyn Device>> {
+        let index = self.devices.iter().position(|mapped| mapped.id == id)?;
+        Some(self.devices.remove(index).device)
+    }
+
+    pub fn get(&self, id: DeviceId) -> Option<&dyn Device> {
+        self.devices
+            .iter()
+            .find(|mapped| mapped.id == id)
+            .map(|mapped| mapped.device.as_ref())
+    }
+
+    pub fn get_mut(&mut self, id: DeviceId) -> Option<&mut dyn Device> {
+        self.devices
+            .iter_mut()
+            .find(|mapped| mapped.id == id)
+            .map(|mapped| mapped.device.as_mut())
+    }
+
+    pub fn at(&self, addr: u16) -> Option<&dyn Device> {
+        if self.devices.is_empty() {
+            return None;
+        }
+
+        self.devices
+            .iter()
+            .find(|mapped| mapped.range.contains(&addr))
+            .map(|mapped| mapped.device.as_ref())
+    }
+
+    pub fn at_mut(&mut self, addr: u16) -> Option<&mut dyn Device> {
+        if self.devices.is_empty() {
+            return None;
+        }
+
+        self.devices
+            .iter_mut()
+            .find(|mapped| mapped.range.contains(&addr))
+            .map(|mapped| mapped.device.as_mut())
+    }
+}
+
+// Collects the characters a test ROM writes to its port, optionally echoing them to stdout
+#[derive(Debug, Default)]
+pub struct DebugConsole {
+    output: Vec<u8>,
+    echo: bool,
+}
+
+impl DebugConsole {
+    pub fn new(echo: bool) -> Self {
+        DebugConsole {
+            output: Vec::new(),
+            echo,
+        }
+    }
+
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.output).into_owned()
+    }
+}
+
+impl Device for DebugConsole {
+    fn write(&mut self, _addr: u16, value: u8, _cycle: u64) {
+        self.output.push(value);
+
+        if self.echo {
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(&[value]);
+            if value == b'\n' {
+                let _ = stdout.flush();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ranges_do_not_overlap() {
+        let mut map = DeviceMap::default();
+        let first = map.insert(0x5000..=0x5FFF, Box::new(DebugConsole::default()));
+        assert!(first.is_some());
+        assert!(
+            map.insert(0x5FFF..=0x6000, Box::new(DebugConsole::default()))
+                .is_none()
+        );
+        assert!(
+            map.insert(0x4018..=0x401F, Box::new(DebugConsole::default()))
+                .is_some()
+        );
+
+        assert!(map.at(0x5123).is_some());
+        assert!(map.at(0x6000).is_none());
+
+        map.remove(first.unwrap());
+        assert!(map.at(0x5123).is_none());
+        assert!(
+            map.insert(0x5800..=0x6000, Box::new(DebugConsole::default()))
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_debug_console_collects_text() {
+        let mut console = DebugConsole::new(false);
+        for &byte in b"ok\n" {
+            console.write(DEBUG_CONSOLE_PORT, byte, 0);
+        }
+        assert_eq!(console.text(), "ok\n");
+        assert_eq!(console.read(DEBUG_CONSOLE_PORT, 0), None);
+    }
+}
@@ -0,0 +1,254 @@
+// Memory dumps: every memory space of the console as raw bytes, for binary files, or as an
+// annotated hexdump with the address of each line and a header where a region starts. Runs of
+// identical lines are folded into a `*` line like `hexdump -C` does.
+
+use std::fs;
+use std::path::Path;
+
+use crate::memory::{Bus, BusInterface};
+use crate::sys::errors::Error;
+
+const BYTES_PER_LINE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemorySpace {
+    CpuRam,
+    PrgRam,
+    CpuBus, // $0000-$FFFF as the CPU sees it, through `peek`
+    Vram,   // CIRAM nametable pages
+    Chr,
+    Oam,
+    Palette,
+    Mapper, // Registers of the cartridge, 16-bit little endian in raw dumps
+}
+
+impl MemorySpace {
+    pub const ALL: [MemorySpace; 8] = [
+        MemorySpace::CpuRam,
+        MemorySpace::PrgRam,
+        MemorySpace::CpuBus,
+        MemorySpace::Vram,
+        MemorySpace::Chr,
+        MemorySpace::Oam,
+        MemorySpace::Palette,
+        MemorySpace::Mapper,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MemorySpace::CpuRam => "cpu-ram",
+            MemorySpace::PrgRam => "prg-ram",
+            MemorySpace::CpuBus => "cpu-bus",
+            MemorySpace::Vram => "vram",
+            MemorySpace::Chr => "chr",
+            MemorySpace::Oam => "oam",
+            MemorySpace::Palette => "palette",
+            MemorySpace::Mapper => "mapper",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, Error> {
+        Self::ALL
+            .into_iter()
+            .find(|space| space.name() == name)
+            .ok_or(Error::ErrorInvalidMemorySpace)
+    }
+
+    // Address of the first byte, as the hexdump shows it
+    fn base(&self) -> usize {
+        match self {
+            MemorySpace::PrgRam => 0x6000,
+            MemorySpace::Vram => 0x2000,
+            MemorySpace::Palette => 0x3F00,
+            _ => 0,
+        }
+    }
+
+    // Where each region starts, relative to the start of the space
+    fn regions(&self) -> &'static [(usize, &'static str)] {
+        match self {
+            MemorySpace::CpuRam => &[(0x0000, "Zero page"), (0x0100, "Stack"), (0x0200, "RAM")],
+            MemorySpace::CpuBus => &[
+                (0x0000, "RAM"),
+                (0x0800, "RAM mirrors"),
+                (0x2000, "PPU registers"),
+                (0x4000, "APU and I/O registers"),
+                (0x4020, "Cartridge expansion"),
+                (0x6000, "PRG RAM"),
+                (0x8000, "PRG ROM"),
+            ],
+            MemorySpace::Vram => &[
+                (0x0000, "CIRAM page 0"),
+                (0x0400, "CIRAM page 1"),
+                (0x0800, "Four-screen page 2"),
+                (0x0C00, "Four-screen page 3"),
+            ],
+            MemorySpace::Chr => &[(0x0000, "Pattern table 0"), (0x1000, "Pattern table 1")],
+            MemorySpace::Palette => &[(0x00, "Background palettes"), (0x10, "Sprite palettes")],
+            _ => &[],
+        }
+    }
+
+    pub fn read(&self, bus: &Bus) -> Vec<u8> {
+        match self {
+            MemorySpace::CpuRam => bus.ram.to_vec(),
+            MemorySpace::PrgRam => bus
+                .cartridge_ram()
+                .map_or_else(Vec::new, |prg_ram| prg_ram.data().to_vec()),
+            MemorySpace::CpuBus => (0..=0xFFFF).map(|addr| bus.peek(addr)).collect(),
+            MemorySpace::Vram => bus.ppu.ciram.to_vec(),
+            MemorySpace::Chr => bus
+                .cartridge
+                .as_ref()
+                .and_then(|cartridge| cartridge.chr())
+                .map_or_else(Vec::new, |chr| chr.to_vec()),
+            MemorySpace::Oam => bus.ppu.oam.to_vec(),
+            MemorySpace::Palette => bus.ppu.palette.to_vec(),
+            MemorySpace::Mapper => mapper_registers(bus)
+                .iter()
+                .flat_map(|(_, value)| value.to_le_bytes())
+                .collect(),
+        }
+    }
+
+    pub fn hexdump(&self, bus: &Bus) -> String {
+        let mut text = format!("; {}\n", self.name());
+        if *self == MemorySpace::Mapper {
+            for (name, value) in mapper_registers(bus) {
+                text += &format!("{:<20} ${:04X}\n", name, value);
+            }
+            return text;
+        }
+
+        text += &hexdump(&self.read(bus), self.base(), self.regions());
+        text
+    }
+
+    // `<dir>/<name>.bin`
+    pub fn write_raw(&self, bus: &Bus, dir: &Path) -> Result<(), Error> {
+        fs::create_dir_all(dir).map_err(|_| Error::ErrorWritingDump)?;
+        fs::write(dir.join(self.name()).with_extension("bin"), self.read(bus))
+            .map_err(|_| Error::ErrorWritingDump)
+    }
+}
+
+fn mapper_registers(bus: &Bus) -> Vec<(String, u16)> {
+    bus.cartridge
+        .as_ref()
+        .map_or_else(Vec::new, |cartridge| cartridge.registers())
+}
+
+// 16 bytes per line with their ASCII, `regions` headers start new lines
+pub fn hexdump(data: &[u8], base: usize, regions: &[(usize, &str)]) -> String {
+    let digits = format!("{:X}", (base + data.len()).saturating_sub(1))
+        .len()
+        .max(4);
+    let mut text = String::new();
+    let mut last_line: Option<&[u8]> = None;
+    let mut folded = false;
+
+    for (index, line) in data.chunks(BYTES_PER_LINE).enumerate() {
+        let offset = index * BYTES_PER_LINE;
+        let region = regions
+            .iter()
+            .find(|(start, _)| (offset..offset + BYTES_PER_LINE).contains(start));
+        if let Some((_, name)) = region {
+            text += &format!("; {}\n", name);
+            last_line = None;
+        }
+
+        let is_last = offset + BYTES_PER_LINE >= data.len();
+        if last_line == Some(line) && !is_last {
+            if !folded {
+                text += "*\n";
+                folded = true;
+            }
+            continue;
+        }
+        last_line = Some(line);
+        folded = false;
+
+        let hex: Vec<String> = line.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let ascii: String = line
+            .iter()
+            .map(|&byte| match byte {
+                0x20..=0x7E => byte as char,
+                _ => '.',
+            })
+            .collect();
+        text += &format!(
+            "{:0digits$X}  {:<width$}  |{}|\n",
+            base + offset,
+            hex.join(" "),
+            ascii,
+            digits = digits,
+            width = BYTES_PER_LINE * 3 - 1,
+        );
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sys::interfaces::INes;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_hexdump_folds_repeats_and_marks_regions() {
+        let mut data = vec![0u8; 0x60];
+        data[..5].copy_from_slice(b"NES\x1A!");
+        let text = hexdump(&data, 0x6000, &[(0x00, "Header"), (0x40, "Body")]);
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines[0], "; Header");
+        assert!(lines[1].starts_with("6000  4E 45 53 1A 21 00"));
+        assert!(lines[1].ends_with("|NES.!...........|"));
+        assert!(lines[2].starts_with("6010  00"));
+        assert_eq!(lines[3], "*");
+        assert_eq!(lines[4], "; Body");
+        assert!(lines[5].starts_with("6040  00"));
+        assert!(lines[6].starts_with("6050  00"));
+        assert_eq!(lines.len(), 7);
+    }
+
+    #[test]
+    fn test_dump_spaces() {
+        // NROM with every 16 byte tile filled with its own number
+        let ines = INes {
+            prg_rom: vec![0xEA; 0x4000].into(),
+            chr_rom: (0..0x2000).map(|i| (i >> 4) as u8).collect(),
+            ..Default::default()
+        };
+        let mut bus = Bus::new(ines.prg_rom.clone());
+        bus.insert_cartridge(crate::mapper::new_mapper(&ines).unwrap().unwrap());
+        bus.write(0x0010, 0x42);
+        bus.ppu.palette[0x10] = 0x0F;
+
+        assert_eq!(MemorySpace::CpuRam.read(&bus)[0x10], 0x42);
+        let cpu_bus = MemorySpace::CpuBus.read(&bus);
+        assert_eq!(cpu_bus.len(), 0x10000);
+        assert_eq!((cpu_bus[0x0810], cpu_bus[0xC000]), (0x42, 0xEA));
+        let chr = MemorySpace::Chr.read(&bus);
+        assert_eq!(chr.len(), 0x2000);
+        assert_eq!((chr[0x0010], chr[0x1234]), (0x01, 0x23));
+        assert!(
+            MemorySpace::Chr
+                .hexdump(&bus)
+                .contains("; Pattern table 1\n1000  00 00")
+        );
+        assert!(
+            MemorySpace::Palette
+                .hexdump(&bus)
+                .contains("; Sprite palettes\n3F10  0F")
+        );
+
+        let dir = tempdir().unwrap();
+        for space in MemorySpace::ALL {
+            assert_eq!(MemorySpace::parse(space.name()), Ok(space));
+            space.write_raw(&bus, dir.path()).unwrap();
+        }
+        assert_eq!(fs::read(dir.path().join("oam.bin")).unwrap().len(), 256);
+        assert!(MemorySpace::parse("rom").is_err());
+    }
+}
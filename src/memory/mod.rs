@@ -1,6 +1,21 @@
-use crate::sys::errors::Error;
-use core::panic;
+pub mod device;
+pub mod dump;
+pub mod power_on;
+
+use std::any::Any;
+use std::ops::RangeInclusive;
 
+use crate::apu::mixer;
+use crate::cheat::Cheats;
+use crate::mapper::Mapper;
+use crate::mapper::chr::Chr;
+use crate::mapper::prg_ram::PrgRam;
+use crate::ppu::Ppu;
+use crate::scheduler::{Scheduler, SyncMode};
+use crate::sys::errors::Error;
+use crate::sys::interfaces::{Region, RomData};
+use device::{Device, DeviceId, DeviceMap};
+use power_on::{PowerOnMemory, PowerOnState};
 pub const RAM_SIZE: usize = 2048; // 2 KiB = 0x07FF - 0x0000 + 1 = Max 8 KiB
 pub const PPU_SIZE: usize = 8192; // 8 KiB = 0x3FFF - 0x2000 + 1 = Max 8 bytes
 pub const MIRRORED_PRG_SIZE: usize = 16384; // 16 KiB = 0x7FFF - 0x8000 + 1
@@ -8,45 +23,103 @@ pub const PRG_SIZE: usize = 32768; // 32 KiB = 0xFFFF - 0x8000 + 1
 
 pub trait BusInterface {
     fn resolve_prg_rom_index(&self, addr: u16) -> usize;
-    fn new(prg_rom: &[u8]) -> Self;
+    fn new(prg_rom: RomData) -> Self;
     fn write(&mut self, addr: u16, value: u8);
-    fn read(&self, addr: u16) -> u8;
-    fn load_prg_rom(&mut self, data: &[u8]) -> Result<usize, Error>;
+    fn read(&mut self, addr: u16) -> u8;
+    fn peek(&self, addr: u16) -> u8;
+    fn load_prg_rom(&mut self, data: RomData) -> Result<usize, Error>;
+    fn load_prg_ram(&mut self, prg_ram: PrgRam);
+    fn load_trainer(&mut self, trainer: &[u8]) -> Result<(), Error>;
+    fn insert_cartridge(&mut self, cartridge: Box<dyn Mapper>);
 }
 
 pub struct Bus {
     pub ram: [u8; RAM_SIZE],
-    pub ppu: [u8; PPU_SIZE],
-    pub prg_rom: Vec<u8>,
+    pub ppu: Ppu,
+    pub scheduler: Scheduler,
+    pub prg_rom: RomData,
+    pub prg_ram: PrgRam, // $6000-$7FFF on boards without a mapper
+    pub cartridge: Option<Box<dyn Mapper>>,
+    pub cheats: Cheats,
+    devices: DeviceMap,
+    power_on: PowerOnState,
+    open_bus: u8, // Last value driven on the data bus, what unmapped reads see
 }
 
 impl BusInterface for Bus {
-    fn new(prg_rom: &[u8]) -> Self {
+    fn new(prg_rom: RomData) -> Self {
         Bus {
             ram: [0; RAM_SIZE],
-            ppu: [0; PPU_SIZE],
-            prg_rom: prg_rom.to_vec(),
+            ppu: Ppu::new(Region::Ntsc),
+            scheduler: Scheduler::new(Region::Ntsc),
+            prg_rom,
+            prg_ram: PrgRam::default(),
+            cartridge: None,
+            cheats: Cheats::default(),
+            devices: DeviceMap::default(),
+            power_on: PowerOnState::default(),
+            open_bus: 0,
         }
     }
 
-    fn load_prg_rom(&mut self, data: &[u8]) -> Result<usize, Error> {
-        self.prg_rom = data.to_vec();
+    // Shares `data` with the other instances instead of copying it
+    fn load_prg_rom(&mut self, data: RomData) -> Result<usize, Error> {
+        self.prg_rom = data;
 
-        if self.prg_rom.len() < 1 || self.prg_rom.len() > PRG_SIZE {
+        // Anything past 32 KiB is only reachable through a mapper
+        if self.prg_rom.is_empty() {
             return Err(Error::ErrorLoadingROMFile);
         }
         Ok(self.prg_rom.len())
     }
 
+    fn load_prg_ram(&mut self, prg_ram: PrgRam) {
+        self.prg_ram = prg_ram;
+        self.power_on
+            .fill(self.prg_ram.data_mut(), PowerOnMemory::PrgRam);
+    }
+
+    // Into the cartridge's PRG RAM, boards without any have nowhere to put it
+    fn load_trainer(&mut self, trainer: &[u8]) -> Result<(), Error> {
+        let prg_ram = self.cartridge_ram_mut().ok_or(Error::ErrorLoadingTrainer)?;
+        prg_ram.load_trainer(trainer);
+        Ok(())
+    }
+
+    // Route $4020-$FFFF to the cartridge mapper instead of the flat PRG ROM
+    fn insert_cartridge(&mut self, cartridge: Box<dyn Mapper>) {
+        self.cartridge = Some(cartridge);
+        self.fill_cartridge_memory();
+    }
+
     #[inline(always)]
     fn write(&mut self, addr: u16, value: u8) {
+        self.tick();
+        self.open_bus = value;
+
+        let cycle = self.scheduler.cpu_cycles();
+        if let Some(device) = self.devices.at_mut(addr) {
+            device.write(addr, value, cycle);
+            return;
+        }
+
+        if let 0x2000..=0x3FFF = addr {
+            self.sync_ppu();
+            self.ppu
+                .write_register(addr, value, self.cartridge.as_mut());
+            self.update_ppu_deadline();
+            return;
+        }
+
+        if let (0x4020..=0xFFFF, Some(cartridge)) = (addr, self.cartridge.as_mut()) {
+            cartridge.cpu_write(addr, value);
+            return;
+        }
+
         match addr {
             // RAM (2KB + mirrors)
             0x0000..=0x1FFF => self.ram[(addr & 0x07FF) as usize] = value,
 
-            // PPU registers (8 registers, mirrored every 8 bytes)
-            0x2000..=0x3FFF => self.ppu[(addr & 0x0007) as usize] = value,
-
             // APU and I/O registers
             0x4000..=0x4013 | 0x4015 | 0x4017 => {
                 // TODO: To be implemented
@@ -57,32 +130,299 @@ impl BusInterface for Bus {
                 // TODO: To be implemented
             }
 
-            0x8000..=0xFFFF => panic!("Attempted to write to PRG ROM at {:#06X}", addr),
+            0x6000..=0x7FFF => self.prg_ram.write(addr, value),
 
-            // Open bus behavior (optional: log invalid writes)
-            _ => eprintln!("Invalid write to {:#06X}", addr),
+            // ROM and unmapped space ignore writes
+            _ => {}
         }
     }
 
     #[inline(always)]
-    fn read(&self, addr: u16) -> u8 {
-        match addr {
-            0x0000..=0x1FFF => self.ram[(addr & 0x07FF) as usize],
-            0x2000..=0x3FFF => self.ppu[(addr & 0x0007) as usize],
-            0x8000..=0xFFFF => {
+    // CPU reads, the value read is left on the data bus
+    fn read(&mut self, addr: u16) -> u8 {
+        self.tick();
+        if let 0x2000..=0x3FFF = addr {
+            self.sync_ppu();
+        }
+
+        let cycle = self.scheduler.cpu_cycles();
+        if let Some(device) = self.devices.at_mut(addr) {
+            let value = device.read(addr, cycle).unwrap_or(self.open_bus);
+            self.open_bus = value;
+            return value;
+        }
+
+        let value = match (addr, self.cartridge.as_mut()) {
+            (0x4020..=0xFFFF, Some(cartridge)) => cartridge.cpu_read(addr).unwrap_or(self.open_bus),
+            (0x2000..=0x3FFF, cartridge) => self.ppu.read_register(addr, cartridge),
+            // Read inside the CPU, the bus keeps its value
+            (0x4015, _) => return self.peek(addr),
+            _ => self.bus_value(addr),
+        };
+        let value = self.cheats.patch_read(addr, value);
+
+        self.open_bus = value;
+        value
+    }
+
+    // What a read would return, without side effects on the bus, the cartridge or the latch
+    fn peek(&self, addr: u16) -> u8 {
+        if let Some(device) = self.devices.at(addr) {
+            return device.peek(addr).unwrap_or(self.open_bus);
+        }
+        self.cheats.patch_read(addr, self.bus_value(addr))
+    }
+
+    fn resolve_prg_rom_index(&self, addr: u16) -> usize {
+        // 16 KiB (and smaller) images mirror across $8000-$FFFF
+        (addr - 0x8000) as usize % self.prg_rom.len().max(1)
+    }
+}
+
+impl Bus {
+    // What the memory or register at `addr` drives on the bus, before cheats
+    fn bus_value(&self, addr: u16) -> u8 {
+        let open_bus = self.open_bus;
+        match (addr, self.cartridge.as_ref()) {
+            (0x4020..=0xFFFF, Some(cartridge)) => cartridge.cpu_peek(addr).unwrap_or(open_bus),
+            (0x0000..=0x1FFF, _) => self.ram[(addr & 0x07FF) as usize],
+            (0x2000..=0x3FFF, cartridge) => {
+                self.ppu.peek_register(addr, cartridge.map(Box::as_ref))
+            }
+
+            // APU status, bit 5 is undriven
+            (0x4015, _) => open_bus & 0x20,
+
+            // Controllers only drive bits 0-4, the APU and controllers are not implemented yet
+            (0x4016 | 0x4017, _) => open_bus & 0xE0,
+
+            (0x6000..=0x7FFF, _) => self.prg_ram.read(addr).unwrap_or(open_bus),
+            (0x8000..=0xFFFF, _) if !self.prg_rom.is_empty() => {
                 let index = self.resolve_prg_rom_index(addr);
                 self.prg_rom[index]
             }
-            _ => panic!("Invalid address: {:#X}", addr),
+
+            // Nothing answers, the bus still holds the last value
+            _ => open_bus,
         }
     }
 
-    fn resolve_prg_rom_index(&self, addr: u16) -> usize {
-        let offset = (addr - 0x8000) as usize;
-        if self.prg_rom.len() == MIRRORED_PRG_SIZE {
-            offset % MIRRORED_PRG_SIZE
-        } else {
-            offset
+    // Timing of the loaded game, restarts the master clock
+    pub fn set_region(&mut self, region: Region) {
+        let sync_mode = self.scheduler.sync_mode();
+        self.scheduler = Scheduler::new(region);
+        self.ppu = Ppu::new(region);
+        self.fill_ppu_memory();
+        self.set_sync_mode(sync_mode);
+    }
+
+    pub fn power_on_state(&self) -> PowerOnState {
+        self.power_on
+    }
+
+    // Refills the RAM with `state`, cartridges inserted later get it too. Call it before the
+    // trainer and the save file are loaded, they go on top
+    pub fn set_power_on_state(&mut self, state: PowerOnState) {
+        self.power_on = state;
+        state.fill(&mut self.ram, PowerOnMemory::CpuRam);
+        self.fill_ppu_memory();
+        state.fill(self.prg_ram.data_mut(), PowerOnMemory::PrgRam);
+        self.fill_cartridge_memory();
+    }
+
+    fn fill_ppu_memory(&mut self) {
+        self.power_on.fill(&mut self.ppu.oam, PowerOnMemory::Oam);
+        self.power_on
+            .fill(&mut self.ppu.palette, PowerOnMemory::Palette);
+        for entry in self.ppu.palette.iter_mut() {
+            *entry &= 0x3F;
+        }
+    }
+
+    fn fill_cartridge_memory(&mut self) {
+        let Some(cartridge) = self.cartridge.as_mut() else {
+            return;
+        };
+
+        if let Some(prg_ram) = cartridge.prg_ram_mut() {
+            self.power_on
+                .fill(prg_ram.data_mut(), PowerOnMemory::PrgRam);
         }
+        if let Some(chr_ram) = cartridge.chr_mut().and_then(Chr::ram_mut) {
+            self.power_on.fill(chr_ram, PowerOnMemory::ChrRam);
+        }
+    }
+
+    // Catch-up is faster, lockstep simpler to follow in a debugger, both give the same result
+    pub fn set_sync_mode(&mut self, sync_mode: SyncMode) {
+        self.sync_ppu();
+        self.scheduler.set_sync_mode(sync_mode);
+        self.update_ppu_deadline();
+    }
+
+    // One CPU cycle, run at the start of every bus access: the PPU reaches the access (now or,
+    // in catch-up mode, once something can see it) and the cartridge's IRQ counters and
+    // expansion audio step once. Only lockstep reports the PPU's fetches to the cartridge, so
+    // boards that watch them get it whatever the sync mode
+    pub fn tick(&mut self) {
+        let dots = self.scheduler.cpu_cycle();
+
+        let lockstep = self.scheduler.sync_mode() == SyncMode::Lockstep
+            || self
+                .cartridge
+                .as_ref()
+                .is_some_and(|cartridge| cartridge.watches_ppu_bus());
+
+        if lockstep {
+            for _ in 0..dots {
+                self.ppu.tick();
+                self.ppu.fetch(self.cartridge.as_mut());
+            }
+        } else if self.scheduler.defer_dots(dots) {
+            self.sync_ppu();
+        }
+
+        if let Some(cartridge) = self.cartridge.as_mut() {
+            cartridge.cpu_clock();
+        }
+    }
+
+    // Runs the dots owed to the PPU, call it before looking at `ppu` from outside the bus
+    // (at the end of a frame, for instance)
+    pub fn sync_ppu(&mut self) {
+        let dots = self.scheduler.take_pending_dots();
+        if dots > 0 {
+            self.ppu.run(dots);
+            self.update_ppu_deadline();
+        }
+    }
+
+    fn update_ppu_deadline(&mut self) {
+        self.scheduler
+            .set_deadline(self.ppu.dots_until_vblank_edge());
+    }
+
+    // Plugs `device` into `range`, ahead of whatever else answers there
+    pub fn register_device<D: Device>(
+        &mut self,
+        range: RangeInclusive<u16>,
+        device: D,
+    ) -> Result<DeviceId, Error> {
+        self.devices
+            .insert(range, Box::new(device))
+            .ok_or(Error::ErrorDeviceOverlap)
+    }
+
+    pub fn remove_device(&mut self, id: DeviceId) -> Option<Box<dyn Device>> {
+        self.devices.remove(id)
+    }
+
+    pub fn device<D: Device>(&self, id: DeviceId) -> Option<&D> {
+        let device: &dyn Any = self.devices.get(id)?;
+        device.downcast_ref()
+    }
+
+    pub fn device_mut<D: Device>(&mut self, id: DeviceId) -> Option<&mut D> {
+        let device: &mut dyn Any = self.devices.get_mut(id)?;
+        device.downcast_mut()
+    }
+
+    // Call once per frame, writes the frozen cheat values back to RAM
+    pub fn apply_cheats(&mut self) {
+        let freezes: Vec<(u16, u8)> = self.cheats.freezes().collect();
+        for (addr, value) in freezes {
+            match addr {
+                0x0000..=0x1FFF => self.ram[(addr & 0x07FF) as usize] = value,
+                0x6000..=0x7FFF => {
+                    if let Some(prg_ram) = self.cartridge_ram_mut() {
+                        prg_ram.write(addr, value);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Runs until the PPU enters its next vblank, where games do their per-frame work, then
+    // applies the frozen cheats. There is no CPU core yet, the bus idles through the frame
+    pub fn run_frame(&mut self) {
+        while self.ppu.vblank() {
+            self.tick();
+        }
+        while !self.ppu.vblank() {
+            self.tick();
+        }
+        self.apply_cheats();
+    }
+
+    // PRG RAM at $6000-$7FFF: the cartridge's, or the bus's own for boards without a mapper
+    pub fn cartridge_ram(&self) -> Option<&PrgRam> {
+        match self.cartridge.as_ref() {
+            Some(cartridge) => cartridge.prg_ram(),
+            None => Some(&self.prg_ram),
+        }
+    }
+
+    pub fn cartridge_ram_mut(&mut self) -> Option<&mut PrgRam> {
+        match self.cartridge.as_mut() {
+            Some(cartridge) => cartridge.prg_ram_mut(),
+            None => Some(&mut self.prg_ram),
+        }
+    }
+
+    pub fn nmi_line(&self) -> bool {
+        self.ppu.nmi_line()
+    }
+
+    pub fn irq_line(&self) -> bool {
+        self.cartridge
+            .as_ref()
+            .is_some_and(|cartridge| cartridge.irq_pending())
+    }
+
+    pub fn open_bus(&self) -> u8 {
+        self.open_bus
+    }
+
+    // Reset button, only the cartridge sees it on the bus side
+    pub fn reset(&mut self) {
+        if let Some(cartridge) = self.cartridge.as_mut() {
+            cartridge.reset();
+        }
+    }
+
+    // Battery backed memory of the cartridge, or of the PRG RAM on the bus for mapperless boards
+    pub fn battery_ram(&self) -> Option<Vec<u8>> {
+        match self.cartridge.as_ref() {
+            Some(cartridge) => cartridge.battery_ram(),
+            None => self.prg_ram.battery_ram(),
+        }
+    }
+
+    pub fn load_battery_ram(&mut self, data: &[u8]) {
+        match self.cartridge.as_mut() {
+            Some(cartridge) => cartridge.load_battery_ram(data),
+            None => self.prg_ram.load(data),
+        }
+    }
+
+    // Battery memory that changed since the last call, `None` when there is nothing to flush
+    pub fn flush_battery_ram(&mut self) -> Option<Vec<u8>> {
+        let dirty = match self.cartridge.as_mut() {
+            Some(cartridge) => cartridge.take_battery_ram_dirty(),
+            None => self.prg_ram.take_dirty(),
+        };
+
+        if dirty { self.battery_ram() } else { None }
+    }
+
+    // Current audio level, the 2A03 channels are silent until the APU is implemented
+    pub fn audio_sample(&self) -> f32 {
+        let expansion = self
+            .cartridge
+            .as_ref()
+            .map_or(0.0, |cartridge| cartridge.audio_output());
+
+        mixer::mix(0, 0, 0, 0, 0, expansion)
     }
 }
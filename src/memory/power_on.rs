@@ -0,0 +1,147 @@
+// What RAM holds when the console is switched on. Real hardware powers up with mostly random
+// content that differs between consoles and even between boots, games that forget to clear
+// their RAM only break on some of them. Random states come from a seed so a failing run can
+// be repeated: `random:<seed>` is what gets printed and what `parse` takes back.
+
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::sys::errors::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PowerOnState {
+    #[default]
+    Zeros,
+    Ones,    // $FF
+    Pattern, // $00 $00 $00 $00 $FF $FF $FF $FF...
+    Random(u64),
+}
+
+// Each memory gets its own random stream, so resizing one leaves the others alone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerOnMemory {
+    CpuRam = 1,
+    PrgRam,
+    ChrRam,
+    Oam,
+    Palette,
+}
+
+impl PowerOnState {
+    // Random content from a seed taken from the clock
+    pub fn random() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        PowerOnState::Random(nanos)
+    }
+
+    // `zeros`, `ff`, `pattern`, `random` or `random:<seed>`
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        match text.split_once(':') {
+            Some(("random", seed)) => seed
+                .parse()
+                .map(PowerOnState::Random)
+                .map_err(|_| Error::ErrorInvalidPowerOnState),
+            Some(_) => Err(Error::ErrorInvalidPowerOnState),
+            None => match text {
+                "zeros" => Ok(PowerOnState::Zeros),
+                "ff" => Ok(PowerOnState::Ones),
+                "pattern" => Ok(PowerOnState::Pattern),
+                "random" => Ok(Self::random()),
+                _ => Err(Error::ErrorInvalidPowerOnState),
+            },
+        }
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        match self {
+            PowerOnState::Random(seed) => Some(*seed),
+            _ => None,
+        }
+    }
+
+    pub fn fill(&self, data: &mut [u8], memory: PowerOnMemory) {
+        match self {
+            PowerOnState::Zeros => data.fill(0x00),
+            PowerOnState::Ones => data.fill(0xFF),
+            PowerOnState::Pattern => {
+                for (index, byte) in data.iter_mut().enumerate() {
+                    *byte = if index & 4 == 0 { 0x00 } else { 0xFF };
+                }
+            }
+            PowerOnState::Random(seed) => {
+                let mut state = seed ^ (memory as u64).wrapping_mul(0xA076_1D64_78BD_642F);
+                for chunk in data.chunks_mut(8) {
+                    let bytes = splitmix64(&mut state).to_le_bytes();
+                    chunk.copy_from_slice(&bytes[..chunk.len()]);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for PowerOnState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PowerOnState::Zeros => write!(f, "zeros"),
+            PowerOnState::Ones => write!(f, "ff"),
+            PowerOnState::Pattern => write!(f, "pattern"),
+            PowerOnState::Random(seed) => write!(f, "random:{}", seed),
+        }
+    }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patterns() {
+        let mut data = [0x55; 10];
+        PowerOnState::Pattern.fill(&mut data, PowerOnMemory::CpuRam);
+        assert_eq!(data, [0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0]);
+
+        PowerOnState::Ones.fill(&mut data, PowerOnMemory::CpuRam);
+        assert_eq!(data, [0xFF; 10]);
+        PowerOnState::Zeros.fill(&mut data, PowerOnMemory::CpuRam);
+        assert_eq!(data, [0; 10]);
+    }
+
+    #[test]
+    fn test_random_is_reproducible() {
+        let state = PowerOnState::parse("random:1234").unwrap();
+        assert_eq!(state.seed(), Some(1234));
+        assert_eq!(PowerOnState::parse(&state.to_string()), Ok(state));
+
+        let fill = |state: PowerOnState, memory| {
+            let mut data = [0; 13];
+            state.fill(&mut data, memory);
+            data
+        };
+        assert_eq!(
+            fill(state, PowerOnMemory::CpuRam),
+            fill(state, PowerOnMemory::CpuRam)
+        );
+        assert_ne!(
+            fill(state, PowerOnMemory::CpuRam),
+            fill(state, PowerOnMemory::Oam)
+        );
+        assert_ne!(
+            fill(state, PowerOnMemory::CpuRam),
+            fill(PowerOnState::Random(1235), PowerOnMemory::CpuRam)
+        );
+
+        assert!(PowerOnState::parse("random").unwrap().seed().is_some());
+        assert!(PowerOnState::parse("random:x").is_err());
+        assert!(PowerOnState::parse("noise").is_err());
+    }
+}
@@ -0,0 +1,179 @@
+// The memory fetches of a rendering PPU, reported to the cartridge for boards that watch the
+// PPU address bus (the MMC2 latches, for one). Every tile takes 8 dots: the nametable byte,
+// the attribute byte, then the low and high pattern planes. Dots 1-256 fetch this line's
+// background, 257-320 the sprites of the next line, 321-336 the first two background tiles
+// of the next line and 337-340 two nametable bytes nothing uses. Nothing is drawn yet, only
+// the addresses matter.
+
+use crate::mapper::Mapper;
+
+use super::{Ppu, VISIBLE_SCANLINES};
+
+pub const SPRITES_PER_LINE: usize = 8;
+
+impl Ppu {
+    // Reports the fetch that starts on the current dot, call it after every `tick`
+    pub fn fetch(&mut self, cartridge: Option<&mut Box<dyn Mapper>>) {
+        let Some(cartridge) = cartridge else {
+            return;
+        };
+        if !self.fetching() {
+            return;
+        }
+
+        let v = self.registers.v;
+        let nametable = 0x2000 | (v & 0x0FFF);
+        let addr = match self.dot {
+            1..=256 | 321..=336 => match (self.dot - 1) % 8 {
+                0 => {
+                    self.tile = self.vram_read(nametable, Some(cartridge.as_ref()));
+                    nametable
+                }
+                2 => 0x23C0 | (v & 0x0C00) | ((v >> 4) & 0x38) | ((v >> 2) & 0x07),
+                4 => self.background_pattern(),
+                6 => self.background_pattern() + 8,
+                _ => return,
+            },
+            257..=320 => {
+                let slot = (self.dot - 257) as usize / 8;
+                if self.dot == 257 {
+                    self.sprite_patterns = self.evaluate_sprites();
+                }
+                match (self.dot - 257) % 8 {
+                    // The sprite slots fetch two nametable bytes they ignore
+                    0 | 2 => nametable,
+                    4 => self.sprite_patterns[slot],
+                    6 => self.sprite_patterns[slot] + 8,
+                    _ => return,
+                }
+            }
+            337 | 339 => nametable,
+            _ => return,
+        };
+        cartridge.ppu_address(addr);
+    }
+
+    // Low plane row of the tile just fetched, PPUCTRL bit 4 picks the pattern table
+    fn background_pattern(&self) -> u16 {
+        let table = (self.registers.ctrl as u16 & 0x10) << 8;
+        let fine_y = (self.registers.v >> 12) & 0x07;
+        table | ((self.tile as u16) << 4) | fine_y
+    }
+
+    // The first 8 sprites in OAM order that cover the next line, as the pattern rows they
+    // fetch. The pre-render line finds none, and empty slots fetch tile $FF
+    fn evaluate_sprites(&self) -> [u16; SPRITES_PER_LINE] {
+        let tall = self.registers.ctrl & 0x20 != 0;
+        let height = if tall { 16 } else { 8 };
+        let mut patterns = [self.sprite_pattern(0xFF, 0x00, 0); SPRITES_PER_LINE];
+        if self.scanline >= VISIBLE_SCANLINES {
+            return patterns;
+        }
+
+        // OAM Y is one less than the sprite's first line, so this line's Y hits the next line
+        let in_range = self.oam.chunks_exact(4).filter_map(|sprite| {
+            let row = self.scanline.wrapping_sub(sprite[0] as u16);
+            (row < height).then(|| self.sprite_pattern(sprite[1], sprite[2], row))
+        });
+        for (pattern, slot) in in_range.zip(patterns.iter_mut()) {
+            *slot = pattern;
+        }
+        patterns
+    }
+
+    // 8x8 sprites take their pattern table from PPUCTRL bit 3, 8x16 ones from bit 0 of the tile
+    fn sprite_pattern(&self, tile: u8, attributes: u8, row: u16) -> u16 {
+        let flip_vertical = attributes & 0x80 != 0;
+        if self.registers.ctrl & 0x20 != 0 {
+            let row = if flip_vertical { 15 - row } else { row };
+            let table = (tile as u16 & 0x01) << 12;
+            let tile = (tile as u16 & 0xFE) + (row >> 3);
+            table | (tile << 4) | (row & 0x07)
+        } else {
+            let row = if flip_vertical { 7 - row } else { row };
+            let table = (self.registers.ctrl as u16 & 0x08) << 9;
+            table | ((tile as u16) << 4) | row
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::test_rom::test_rom;
+    use crate::sys::interfaces::Region;
+
+    // An MMC2 with CHR banks 1 and 2 behind the left table's $FD and $FE latch, 3 and 4
+    // behind the right one's
+    fn mmc2() -> Box<dyn Mapper> {
+        let ines = test_rom(9).prg(0x2000, 16).chr(0x1000, 32).build();
+        let mut mmc2 = crate::mapper::new_mapper(&ines).unwrap().unwrap();
+        for (addr, bank) in [(0xB000, 1), (0xC000, 2), (0xD000, 3), (0xE000, 4)] {
+            mmc2.cpu_write(addr, bank);
+        }
+        mmc2
+    }
+
+    fn run_frame(ppu: &mut Ppu, cartridge: &mut Box<dyn Mapper>) {
+        let frame = ppu.frame();
+        while ppu.frame() == frame {
+            ppu.tick();
+            ppu.fetch(Some(cartridge));
+        }
+    }
+
+    #[test]
+    fn test_background_fetches_flip_mmc2_latches() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = mmc2();
+        assert_eq!(cartridge.ppu_read(0x0000), 2);
+
+        // Tile $FD on the second row of the first nametable
+        ppu.write_register(0x2006, 0x20, Some(&mut cartridge));
+        ppu.write_register(0x2006, 0x25, Some(&mut cartridge));
+        ppu.write_register(0x2007, 0xFD, Some(&mut cartridge));
+        ppu.write_register(0x2006, 0x00, Some(&mut cartridge));
+        ppu.write_register(0x2006, 0x00, Some(&mut cartridge));
+        assert_eq!(cartridge.ppu_read(0x0000), 2);
+
+        // Background off, nothing is fetched
+        run_frame(&mut ppu, &mut cartridge);
+        assert_eq!(cartridge.ppu_read(0x0000), 2);
+
+        ppu.write_register(0x2001, 0x08, Some(&mut cartridge));
+        run_frame(&mut ppu, &mut cartridge);
+        assert_eq!(cartridge.ppu_read(0x0000), 1);
+        assert_eq!(cartridge.ppu_read(0x1000), 4);
+
+        // PPUCTRL bit 4 moves the background to the right table
+        ppu.write_register(0x2000, 0x10, Some(&mut cartridge));
+        run_frame(&mut ppu, &mut cartridge);
+        assert_eq!(cartridge.ppu_read(0x1000), 3);
+    }
+
+    #[test]
+    fn test_sprite_fetches_flip_mmc2_latches() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = mmc2();
+
+        // Sprites from the right table, the background's blank tiles keep the left one on $FE
+        ppu.oam[..4].copy_from_slice(&[100, 0xFD, 0x00, 50]);
+        ppu.write_register(0x2000, 0x08, Some(&mut cartridge));
+        ppu.write_register(0x2001, 0x10, Some(&mut cartridge));
+        run_frame(&mut ppu, &mut cartridge);
+        assert_eq!(cartridge.ppu_read(0x1000), 3);
+        assert_eq!(cartridge.ppu_read(0x0000), 2);
+    }
+
+    #[test]
+    fn test_sprite_pattern_rows() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        ppu.registers.ctrl = 0x08;
+        assert_eq!(ppu.sprite_pattern(0x12, 0x00, 3), 0x1123);
+        assert_eq!(ppu.sprite_pattern(0x12, 0x80, 3), 0x1124);
+
+        ppu.registers.ctrl = 0x20;
+        assert_eq!(ppu.sprite_pattern(0x13, 0x00, 10), 0x1132);
+        assert_eq!(ppu.sprite_pattern(0x13, 0x80, 10), 0x1125);
+    }
+}
@@ -0,0 +1,507 @@
+// 2C02/2C07 frame timing: 341 dots per scanline, 262 scanlines on NTSC and 312 on PAL.
+// Scanline 241 starts vblank (the NMI fires on its second dot when PPUCTRL bit 7 is set) and
+// the last scanline is the pre-render line, which clears it again. On NTSC the pre-render line
+// is one dot shorter on odd frames while rendering is enabled, which also walks `v` across the
+// nametables as the tiles are fetched. `run` advances many dots at once for the catch-up
+// scheduler and lands on the same state as ticking them one by one.
+//
+// The CPU reaches the PPU through the ports in `registers`, PPUDATA goes through the cartridge
+// for the pattern tables and the nametables it maps, and to CIRAM and palette RAM otherwise.
+
+mod fetch;
+pub mod registers;
+
+use crate::mapper::{Mapper, VramSource};
+use crate::sys::interfaces::Region;
+use fetch::SPRITES_PER_LINE;
+use registers::{
+    OAMADDR, OAMDATA, PALETTE_BITS, PPUADDR, PPUCTRL, PPUDATA, PPUMASK, PPUSCROLL, PPUSTATUS,
+    PpuRegisters, STATUS_BITS, STATUS_SPRITE_OVERFLOW, STATUS_SPRITE_ZERO_HIT, STATUS_VBLANK,
+};
+
+pub const DOTS_PER_SCANLINE: u16 = 341;
+pub const VISIBLE_SCANLINES: u16 = 240;
+pub const VBLANK_SCANLINE: u16 = 241;
+pub const OAM_SIZE: usize = 256; // 64 sprites of 4 bytes
+pub const CIRAM_SIZE: usize = 0x1000; // 2 KiB of nametables, 4 KiB with four-screen RAM
+pub const PALETTE_SIZE: usize = 32;
+
+pub struct Ppu {
+    pub registers: PpuRegisters,
+    pub ciram: [u8; CIRAM_SIZE],
+    pub oam: [u8; OAM_SIZE],
+    pub palette: [u8; PALETTE_SIZE], // 6 bits per entry
+    region: Region,
+    dot: u16,
+    scanline: u16,
+    frame: u64,
+    vblank: bool,
+    tile: u8,                                 // Last background tile fetched
+    sprite_patterns: [u16; SPRITES_PER_LINE], // Pattern rows of the next line's sprites
+}
+
+impl Ppu {
+    pub fn new(region: Region) -> Self {
+        Ppu {
+            registers: PpuRegisters::default(),
+            ciram: [0; CIRAM_SIZE],
+            oam: [0; OAM_SIZE],
+            palette: [0; PALETTE_SIZE],
+            region,
+            dot: 0,
+            scanline: 0,
+            frame: 0,
+            vblank: false,
+            tile: 0,
+            sprite_patterns: [0; SPRITES_PER_LINE],
+        }
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    pub fn dot(&self) -> u16 {
+        self.dot
+    }
+
+    pub fn scanline(&self) -> u16 {
+        self.scanline
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn vblank(&self) -> bool {
+        self.vblank
+    }
+
+    // PPUCTRL bit 7 gates the vblank flag onto the CPU's NMI line
+    pub fn nmi_line(&self) -> bool {
+        self.vblank && self.registers.ctrl & 0x80 != 0
+    }
+
+    // PPUMASK background or sprites
+    fn rendering_enabled(&self) -> bool {
+        self.registers.mask & 0x18 != 0
+    }
+
+    // Visible and pre-render lines fetch tiles while rendering is enabled
+    fn fetching(&self) -> bool {
+        self.rendering_enabled()
+            && (self.scanline < VISIBLE_SCANLINES || self.scanline == self.pre_render_scanline())
+    }
+
+    fn status(&self) -> u8 {
+        let flags = self.registers.status & (STATUS_SPRITE_ZERO_HIT | STATUS_SPRITE_OVERFLOW);
+        if self.vblank {
+            flags | STATUS_VBLANK
+        } else {
+            flags
+        }
+    }
+
+    // CPU read of $2000-$3FFF
+    pub fn read_register(&mut self, addr: u16, mut cartridge: Option<&mut Box<dyn Mapper>>) -> u8 {
+        let value = self.peek_register(addr, cartridge.as_deref().map(Box::as_ref));
+        let frame = self.frame;
+
+        match addr & 0x0007 {
+            PPUSTATUS => {
+                self.registers.latch.drive(value, STATUS_BITS, frame);
+                self.vblank = false;
+                self.registers.w = false;
+            }
+            OAMDATA => self.registers.latch.drive(value, 0xFF, frame),
+            PPUDATA => {
+                let vram_addr = self.registers.v & 0x3FFF;
+                // Palette reads skip the buffer, which gets the nametable byte under them
+                let (driven, buffered) = match vram_addr {
+                    0x3F00..=0x3FFF => (PALETTE_BITS, vram_addr - 0x1000),
+                    _ => (0xFF, vram_addr),
+                };
+                self.registers.latch.drive(value, driven, frame);
+                self.registers.read_buffer =
+                    self.vram_read(buffered, cartridge.as_deref().map(Box::as_ref));
+                if let Some(cartridge) = cartridge.as_mut() {
+                    cartridge.ppu_address(vram_addr);
+                }
+                self.registers.increment_v();
+            }
+            // Write-only ports read back the I/O bus
+            _ => {}
+        }
+        value
+    }
+
+    // What a CPU read of $2000-$3FFF would return, without side effects
+    pub fn peek_register(&self, addr: u16, cartridge: Option<&dyn Mapper>) -> u8 {
+        let latch = self.registers.latch.value(self.frame, self.region);
+
+        match addr & 0x0007 {
+            PPUSTATUS => self.status() | (latch & !STATUS_BITS),
+            OAMDATA => {
+                let oam_addr = self.registers.oam_addr;
+                let value = self.oam[oam_addr as usize];
+                // Bits 2-4 of the sprite attributes don't exist
+                if oam_addr & 0x03 == 2 {
+                    value & 0xE3
+                } else {
+                    value
+                }
+            }
+            PPUDATA => match self.registers.v & 0x3FFF {
+                vram_addr @ 0x3F00..=0x3FFF => {
+                    (latch & !PALETTE_BITS) | self.vram_read(vram_addr, cartridge)
+                }
+                _ => self.registers.read_buffer,
+            },
+            _ => latch,
+        }
+    }
+
+    // CPU write to $2000-$3FFF
+    pub fn write_register(
+        &mut self,
+        addr: u16,
+        value: u8,
+        cartridge: Option<&mut Box<dyn Mapper>>,
+    ) {
+        self.registers.latch.drive(value, 0xFF, self.frame);
+
+        match addr & 0x0007 {
+            PPUCTRL => self.registers.write_ctrl(value),
+            PPUMASK => self.registers.mask = value,
+            OAMADDR => self.registers.oam_addr = value,
+            OAMDATA => {
+                self.oam[self.registers.oam_addr as usize] = value;
+                self.registers.oam_addr = self.registers.oam_addr.wrapping_add(1);
+            }
+            PPUSCROLL => self.registers.write_scroll(value),
+            PPUADDR => self.registers.write_addr(value),
+            PPUDATA => {
+                let vram_addr = self.registers.v & 0x3FFF;
+                self.vram_write(vram_addr, value, cartridge);
+                self.registers.increment_v();
+            }
+            // PPUSTATUS is read-only
+            _ => {}
+        }
+    }
+
+    // PPU address space, $0000-$3FFF
+    pub fn vram_read(&self, addr: u16, cartridge: Option<&dyn Mapper>) -> u8 {
+        let addr = addr & 0x3FFF;
+        if addr >= 0x3F00 {
+            let value = self.palette[palette_index(addr)];
+            // PPUMASK grayscale
+            return if self.registers.mask & 0x01 != 0 {
+                value & 0x30
+            } else {
+                value
+            };
+        }
+
+        match cartridge {
+            Some(cartridge) => match cartridge.vram_source(addr) {
+                VramSource::Cartridge => cartridge.ppu_read(addr),
+                VramSource::Ciram(page) => self.ciram[ciram_index(page, addr)],
+            },
+            // Without a cartridge nothing drives the pattern tables and every nametable is CIRAM
+            None => match addr {
+                0x0000..=0x1FFF => 0,
+                _ => self.ciram[addr as usize & 0x0FFF],
+            },
+        }
+    }
+
+    fn vram_write(&mut self, addr: u16, value: u8, cartridge: Option<&mut Box<dyn Mapper>>) {
+        if addr >= 0x3F00 {
+            self.palette[palette_index(addr)] = value & PALETTE_BITS;
+            return;
+        }
+
+        match cartridge {
+            Some(cartridge) => {
+                match cartridge.vram_source(addr) {
+                    VramSource::Cartridge => cartridge.ppu_write(addr, value),
+                    VramSource::Ciram(page) => self.ciram[ciram_index(page, addr)] = value,
+                }
+                cartridge.ppu_address(addr);
+            }
+            None => {
+                if addr >= 0x2000 {
+                    self.ciram[addr as usize & 0x0FFF] = value;
+                }
+            }
+        }
+    }
+
+    fn pre_render_scanline(&self) -> u16 {
+        self.region.scanlines() - 1
+    }
+
+    fn line_length(&self) -> u16 {
+        let skip_dot = self.region == Region::Ntsc
+            && self.scanline == self.pre_render_scanline()
+            && self.frame % 2 == 1
+            && self.rendering_enabled();
+
+        DOTS_PER_SCANLINE - skip_dot as u16
+    }
+
+    // Dots until the vblank flag can next change, on dot 1 of the vblank or pre-render line.
+    // Only register writes move it, they change PPUMASK and so the odd frame dot
+    pub fn dots_until_vblank_edge(&self) -> u32 {
+        let position =
+            |scanline: u16, dot: u16| scanline as u32 * DOTS_PER_SCANLINE as u32 + dot as u32;
+        let current = position(self.scanline, self.dot);
+        let pre_render = self.pre_render_scanline();
+
+        if current < position(VBLANK_SCANLINE, 1) {
+            position(VBLANK_SCANLINE, 1) - current
+        } else if current < position(pre_render, 1) {
+            position(pre_render, 1) - current
+        } else {
+            (self.line_length() - self.dot) as u32 + position(VBLANK_SCANLINE, 1)
+        }
+    }
+
+    // Same as `dots` ticks, skipping straight over the dots where nothing happens
+    pub fn run(&mut self, mut dots: u32) {
+        while dots > 0 {
+            let mut next_event = if self.dot == 0 { 1 } else { self.line_length() };
+            if let Some(dot) = self.next_scroll_dot() {
+                next_event = next_event.min(dot);
+            }
+            let plain = ((next_event - 1 - self.dot) as u32).min(dots);
+            self.dot += plain as u16;
+            dots -= plain;
+
+            if dots > 0 {
+                self.tick();
+                dots -= 1;
+            }
+        }
+    }
+
+    // One dot
+    pub fn tick(&mut self) {
+        self.dot += 1;
+
+        if self.dot >= self.line_length() {
+            self.dot = 0;
+            self.scanline += 1;
+            if self.scanline == self.region.scanlines() {
+                self.scanline = 0;
+                self.frame += 1;
+            }
+        }
+
+        match (self.scanline, self.dot) {
+            (VBLANK_SCANLINE, 1) => self.vblank = true,
+            (scanline, 1) if scanline == self.pre_render_scanline() => self.vblank = false,
+            _ => {}
+        }
+
+        if self.fetching() {
+            self.update_scroll();
+        }
+    }
+
+    // Coarse X steps after each tile, fine Y at the end of the visible dots, and `t` reloads
+    // the horizontal position for the next line and, on the pre-render line, the vertical one
+    fn update_scroll(&mut self) {
+        let pre_render = self.scanline == self.pre_render_scanline();
+        match self.dot {
+            256 => {
+                self.registers.increment_x();
+                self.registers.increment_y();
+            }
+            257 => self.registers.copy_horizontal(),
+            280..=304 if pre_render => self.registers.copy_vertical(),
+            8..=255 | 328 | 336 if self.dot.is_multiple_of(8) => self.registers.increment_x(),
+            _ => {}
+        }
+    }
+
+    // The next dot of this line `update_scroll` changes `v` on, `run` can't skip over it
+    fn next_scroll_dot(&self) -> Option<u16> {
+        if !self.fetching() {
+            return None;
+        }
+        match self.dot + 1 {
+            dot @ 0..=256 => Some(dot.next_multiple_of(8)),
+            257 => Some(257),
+            dot @ 258..=304 if self.scanline == self.pre_render_scanline() => Some(dot.max(280)),
+            dot @ 258..=336 => Some(dot.next_multiple_of(8).max(328)),
+            _ => None,
+        }
+    }
+}
+
+fn ciram_index(page: u8, addr: u16) -> usize {
+    page as usize * 0x0400 + (addr as usize & 0x03FF)
+}
+
+// $3F10/$3F14/$3F18/$3F1C are the backdrop entries of the background palettes
+fn palette_index(addr: u16) -> usize {
+    let index = addr as usize & 0x1F;
+    if index & 0x13 == 0x10 {
+        index & 0x0F
+    } else {
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_frame(ppu: &mut Ppu) -> u32 {
+        let frame = ppu.frame();
+        let mut dots = 0;
+        while ppu.frame() == frame {
+            ppu.tick();
+            dots += 1;
+        }
+        dots
+    }
+
+    #[test]
+    fn test_vblank_and_nmi() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        ppu.registers.ctrl = 0x80;
+        while (ppu.scanline(), ppu.dot()) != (VBLANK_SCANLINE, 0) {
+            ppu.tick();
+        }
+        assert!(!ppu.nmi_line());
+        ppu.tick();
+        assert!(ppu.vblank() && ppu.nmi_line());
+
+        while (ppu.scanline(), ppu.dot()) != (261, 1) {
+            ppu.tick();
+        }
+        assert!(!ppu.vblank());
+    }
+
+    #[test]
+    fn test_run_matches_ticks() {
+        for (region, mask) in [
+            (Region::Ntsc, 0x00),
+            (Region::Ntsc, 0x08),
+            (Region::Pal, 0x18),
+        ] {
+            let mut ticked = Ppu::new(region);
+            let mut run = Ppu::new(region);
+            ticked.registers.mask = mask;
+            run.registers.mask = mask;
+            ticked.registers.t = 0x1234;
+            run.registers.t = 0x1234;
+
+            for dots in [1, 2, 340, 341, 1000, 89341, 7, 100000] {
+                for _ in 0..dots {
+                    ticked.tick();
+                }
+                run.run(dots);
+                assert_eq!(
+                    (run.scanline(), run.dot(), run.frame(), run.vblank()),
+                    (
+                        ticked.scanline(),
+                        ticked.dot(),
+                        ticked.frame(),
+                        ticked.vblank()
+                    ),
+                );
+                assert_eq!(run.registers.v, ticked.registers.v);
+            }
+        }
+    }
+
+    #[test]
+    fn test_vblank_edge_prediction() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        ppu.registers.mask = 0x08;
+        for _ in 0..6 {
+            let vblank = ppu.vblank();
+            let dots = ppu.dots_until_vblank_edge();
+            ppu.run(dots - 1);
+            assert_eq!(ppu.vblank(), vblank);
+            ppu.tick();
+            assert_ne!(ppu.vblank(), vblank);
+        }
+    }
+
+    #[test]
+    fn test_frame_lengths() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        assert_eq!(run_frame(&mut ppu), 341 * 262);
+        assert_eq!(run_frame(&mut ppu), 341 * 262);
+
+        // Odd frames lose a dot while rendering
+        ppu.registers.mask = 0x08;
+        assert_eq!(run_frame(&mut ppu), 341 * 262);
+        assert_eq!(run_frame(&mut ppu), 341 * 262 - 1);
+
+        let mut ppu = Ppu::new(Region::Pal);
+        ppu.registers.mask = 0x18;
+        assert_eq!(run_frame(&mut ppu), 341 * 312);
+        assert_eq!(run_frame(&mut ppu), 341 * 312);
+    }
+
+    #[test]
+    fn test_ppustatus_read_clears_vblank_and_toggle() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        ppu.write_register(0x2000, 0x80, None);
+        ppu.write_register(0x2005, 0x00, None);
+        while !ppu.vblank() {
+            ppu.tick();
+        }
+        assert!(ppu.nmi_line());
+
+        // The low bits are the last value written
+        assert_eq!(ppu.peek_register(0x2002, None), 0x80);
+        assert_eq!(ppu.read_register(0x2002, None), 0x80);
+        assert!(!ppu.vblank() && !ppu.nmi_line() && !ppu.registers.w);
+        assert_eq!(ppu.read_register(0x2002, None), 0x00);
+    }
+
+    #[test]
+    fn test_ppudata_buffer_and_palette() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let set_address = |ppu: &mut Ppu, addr: u16| {
+            ppu.write_register(0x2006, (addr >> 8) as u8, None);
+            ppu.write_register(0x2006, addr as u8, None);
+        };
+        set_address(&mut ppu, 0x2F00);
+        ppu.write_register(0x2007, 0x12, None);
+        set_address(&mut ppu, 0x3F10);
+        ppu.write_register(0x2007, 0xED, None);
+        assert_eq!(ppu.palette[0x00], 0x2D);
+
+        // Palette reads come straight back, their top bits from the I/O bus, and fill the
+        // buffer with the nametable byte underneath
+        set_address(&mut ppu, 0x3F00);
+        ppu.write_register(0x2000, 0xC0, None);
+        assert_eq!(ppu.read_register(0x2007, None), 0xED);
+        assert_eq!(ppu.registers.read_buffer, 0x12);
+        assert_eq!(ppu.registers.v, 0x3F01);
+
+        ppu.write_register(0x2001, 0x01, None);
+        set_address(&mut ppu, 0x3F00);
+        assert_eq!(ppu.read_register(0x2007, None) & 0x3F, 0x20);
+    }
+
+    #[test]
+    fn test_io_bus_decays() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        ppu.write_register(0x2001, 0x1F, None);
+        ppu.registers.mask = 0;
+        assert_eq!(ppu.read_register(0x2000, None), 0x1F);
+        for _ in 0..40 {
+            run_frame(&mut ppu);
+        }
+        assert_eq!(ppu.read_register(0x2000, None), 0x00);
+    }
+}
@@ -0,0 +1,204 @@
+// The eight CPU ports of the PPU and the latches behind them. PPUSCROLL and PPUADDR share the
+// write toggle `w` and both fill `t`, the VRAM address the next frame starts from, with
+// PPUADDR's second write copying it into `v`, the address PPUDATA goes through and rendering
+// walks (the "loopy" registers, `x` is the fine X scroll). The ports sit on an 8-bit I/O bus
+// of their own whose capacitance keeps the last value written: write-only ports and the
+// undriven bits of PPUSTATUS read it back, and each bit fades to 0 about 600 ms after
+// something last drove it.
+
+use crate::sys::interfaces::Region;
+
+pub const PPUCTRL: u16 = 0;
+pub const PPUMASK: u16 = 1;
+pub const PPUSTATUS: u16 = 2;
+pub const OAMADDR: u16 = 3;
+pub const OAMDATA: u16 = 4;
+pub const PPUSCROLL: u16 = 5;
+pub const PPUADDR: u16 = 6;
+pub const PPUDATA: u16 = 7;
+
+pub const STATUS_VBLANK: u8 = 0x80;
+pub const STATUS_SPRITE_ZERO_HIT: u8 = 0x40;
+pub const STATUS_SPRITE_OVERFLOW: u8 = 0x20;
+
+// I/O bus bits driven by PPUSTATUS and by PPUDATA palette reads, the others stay on the latch
+pub const STATUS_BITS: u8 = 0xE0;
+pub const PALETTE_BITS: u8 = 0x3F;
+
+// Frames in 600 ms
+fn decay_frames(region: Region) -> u64 {
+    match region {
+        Region::Ntsc => 36,
+        Region::Pal => 30,
+    }
+}
+
+// PPU I/O data bus, every bit remembers the frame it was last driven on
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IoLatch {
+    value: u8,
+    driven: [u64; 8],
+}
+
+impl IoLatch {
+    pub fn value(&self, frame: u64, region: Region) -> u8 {
+        (0..8)
+            .filter(|&bit| frame.saturating_sub(self.driven[bit]) < decay_frames(region))
+            .fold(0, |value, bit| value | (self.value & (1 << bit)))
+    }
+
+    // Puts the `mask` bits of `value` on the bus
+    pub fn drive(&mut self, value: u8, mask: u8, frame: u64) {
+        self.value = (self.value & !mask) | (value & mask);
+        for bit in (0..8).filter(|bit| mask & (1 << bit) != 0) {
+            self.driven[bit] = frame;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PpuRegisters {
+    pub ctrl: u8,
+    pub mask: u8,
+    pub status: u8, // Sprite zero hit and overflow, vblank is `Ppu::vblank`
+    pub oam_addr: u8,
+    pub v: u16, // 15 bits: fine Y, nametable, coarse Y, coarse X
+    pub t: u16,
+    pub x: u8,
+    pub w: bool,
+    pub read_buffer: u8, // PPUDATA reads return the previous fetch
+    pub latch: IoLatch,
+}
+
+impl PpuRegisters {
+    // PPUDATA steps `v` across (1) or down (32) the nametable
+    pub fn vram_increment(&self) -> u16 {
+        if self.ctrl & 0x04 != 0 { 32 } else { 1 }
+    }
+
+    pub fn increment_v(&mut self) {
+        self.v = self.v.wrapping_add(self.vram_increment()) & 0x7FFF;
+    }
+
+    pub fn write_ctrl(&mut self, value: u8) {
+        self.ctrl = value;
+        self.t = (self.t & !0x0C00) | ((value as u16 & 0x03) << 10);
+    }
+
+    // First write is X (coarse and fine), second is Y (coarse and fine)
+    pub fn write_scroll(&mut self, value: u8) {
+        let value = value as u16;
+        if !self.w {
+            self.t = (self.t & !0x001F) | (value >> 3);
+            self.x = value as u8 & 0x07;
+        } else {
+            self.t = (self.t & !0x73E0) | ((value & 0x07) << 12) | ((value & 0xF8) << 2);
+        }
+        self.w = !self.w;
+    }
+
+    // High 6 bits (bit 14 is cleared), then the low byte, which also sets `v`
+    pub fn write_addr(&mut self, value: u8) {
+        let value = value as u16;
+        if !self.w {
+            self.t = (self.t & 0x00FF) | ((value & 0x3F) << 8);
+        } else {
+            self.t = (self.t & 0x7F00) | value;
+            self.v = self.t;
+        }
+        self.w = !self.w;
+    }
+
+    // Rendering walks `v` across the nametables, coarse X wraps into the horizontal nametable
+    pub fn increment_x(&mut self) {
+        if self.v & 0x001F == 31 {
+            self.v = (self.v & !0x001F) ^ 0x0400;
+        } else {
+            self.v += 1;
+        }
+    }
+
+    // Fine Y, then coarse Y, which wraps into the vertical nametable after row 29 (rows 30 and
+    // 31 are the attribute table and wrap without switching)
+    pub fn increment_y(&mut self) {
+        if self.v & 0x7000 != 0x7000 {
+            self.v += 0x1000;
+            return;
+        }
+        let coarse_y = match (self.v >> 5) & 0x1F {
+            29 => {
+                self.v ^= 0x0800;
+                0
+            }
+            31 => 0,
+            coarse_y => coarse_y + 1,
+        };
+        self.v = (self.v & !0x73E0) | (coarse_y << 5);
+    }
+
+    pub fn copy_horizontal(&mut self) {
+        self.v = (self.v & !0x041F) | (self.t & 0x041F);
+    }
+
+    pub fn copy_vertical(&mut self) {
+        self.v = (self.v & !0x7BE0) | (self.t & 0x7BE0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loopy_registers() {
+        let mut registers = PpuRegisters::default();
+        registers.write_ctrl(0x03);
+        registers.write_scroll(0x7D); // Coarse X 15, fine X 5
+        registers.write_scroll(0x5E); // Coarse Y 11, fine Y 6
+        assert_eq!((registers.t, registers.x, registers.w), (0x6D6F, 5, false));
+
+        registers.write_addr(0xFF);
+        assert_eq!((registers.t, registers.v), (0x3F6F, 0));
+        registers.write_addr(0x21);
+        assert_eq!((registers.t, registers.v), (0x3F21, 0x3F21));
+
+        registers.write_ctrl(0x04);
+        registers.increment_v();
+        assert_eq!(registers.v, 0x3F41);
+    }
+
+    #[test]
+    fn test_rendering_scroll_increments() {
+        let mut registers = PpuRegisters {
+            v: 0x001F,
+            ..Default::default()
+        };
+        registers.increment_x();
+        assert_eq!(registers.v, 0x0400);
+
+        registers.v = 0x7000 | (29 << 5);
+        registers.increment_y();
+        assert_eq!(registers.v, 0x0800);
+        registers.v = 0x7000 | (31 << 5);
+        registers.increment_y();
+        assert_eq!(registers.v, 0x0000);
+
+        registers.t = 0x7FFF;
+        registers.copy_horizontal();
+        assert_eq!(registers.v, 0x041F);
+        registers.copy_vertical();
+        assert_eq!(registers.v, 0x7FFF);
+    }
+
+    #[test]
+    fn test_io_latch_decays_per_bit() {
+        let mut latch = IoLatch::default();
+        latch.drive(0xFF, 0xFF, 0);
+        latch.drive(0x00, STATUS_BITS, 20);
+        assert_eq!(latch.value(35, Region::Ntsc), 0x1F);
+        latch.drive(0xA0, STATUS_BITS, 30);
+        assert_eq!(latch.value(36, Region::Ntsc), 0xA0);
+        assert_eq!(latch.value(66, Region::Ntsc), 0x00);
+        assert_eq!(latch.value(30, Region::Pal), 0xA0);
+    }
+}
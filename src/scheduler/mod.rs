@@ -0,0 +1,129 @@
+// Master clock scheduler. Each CPU cycle advances the master clock by the region's CPU divider
+// and lets the PPU run every dot that falls before the cycle's bus access: three per cycle on
+// NTSC, a 3-3-3-3-4 pattern (3.2 on average) on PAL.
+//
+// In lockstep the bus runs those dots right away. In catch-up mode it only adds them to a
+// count owed to the PPU and pays it back in one go when the CPU touches a PPU register, when
+// the count reaches the predicted next vblank edge (so NMIs land on the same cycle) or when a
+// frame ends. Both end in the same state on every CPU cycle.
+
+use crate::sys::interfaces::Region;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncMode {
+    #[default]
+    Lockstep,
+    CatchUp,
+}
+
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    region: Region,
+    sync_mode: SyncMode,
+    master_clock: u64,
+    ppu_clock: u64, // Master clock of the last PPU dot
+    cpu_cycles: u64,
+    pending_dots: u32, // Owed to the PPU in catch-up mode
+    deadline: u32,     // Pending dots that force a catch-up
+}
+
+impl Scheduler {
+    pub fn new(region: Region) -> Self {
+        Scheduler {
+            region,
+            sync_mode: SyncMode::Lockstep,
+            master_clock: 0,
+            ppu_clock: 0,
+            cpu_cycles: 0,
+            pending_dots: 0,
+            deadline: 0,
+        }
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    pub fn sync_mode(&self) -> SyncMode {
+        self.sync_mode
+    }
+
+    // Take the pending dots first, switching modes does not run them
+    pub fn set_sync_mode(&mut self, sync_mode: SyncMode) {
+        self.sync_mode = sync_mode;
+    }
+
+    pub fn master_clock(&self) -> u64 {
+        self.master_clock
+    }
+
+    pub fn cpu_cycles(&self) -> u64 {
+        self.cpu_cycles
+    }
+
+    // Starts a CPU cycle, returns the number of PPU dots to run before its bus access
+    pub fn cpu_cycle(&mut self) -> u32 {
+        self.cpu_cycles += 1;
+        self.master_clock += self.region.cpu_divider();
+
+        let ppu_divider = self.region.ppu_divider();
+        let mut dots = 0;
+        while self.ppu_clock + ppu_divider <= self.master_clock {
+            self.ppu_clock += ppu_divider;
+            dots += 1;
+        }
+        dots
+    }
+
+    // Catch-up mode: owes `dots` to the PPU, true once the debt reaches the deadline
+    pub fn defer_dots(&mut self, dots: u32) -> bool {
+        self.pending_dots += dots;
+        self.pending_dots >= self.deadline
+    }
+
+    pub fn pending_dots(&self) -> u32 {
+        self.pending_dots
+    }
+
+    pub fn take_pending_dots(&mut self) -> u32 {
+        std::mem::take(&mut self.pending_dots)
+    }
+
+    // Dots from the PPU's current position to its next event
+    pub fn set_deadline(&mut self, dots: u32) {
+        self.deadline = dots;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ntsc_three_dots_per_cycle() {
+        let mut scheduler = Scheduler::new(Region::Ntsc);
+        for _ in 0..100 {
+            assert_eq!(scheduler.cpu_cycle(), 3);
+        }
+        assert_eq!(scheduler.cpu_cycles(), 100);
+        assert_eq!(scheduler.master_clock(), 1200);
+    }
+
+    #[test]
+    fn test_deferred_dots_until_deadline() {
+        let mut scheduler = Scheduler::new(Region::Ntsc);
+        scheduler.set_deadline(7);
+        assert!(!scheduler.defer_dots(3));
+        assert!(!scheduler.defer_dots(3));
+        assert!(scheduler.defer_dots(3));
+        assert_eq!(scheduler.take_pending_dots(), 9);
+        assert_eq!(scheduler.pending_dots(), 0);
+    }
+
+    #[test]
+    fn test_pal_dot_pattern() {
+        let mut scheduler = Scheduler::new(Region::Pal);
+        let dots: Vec<u32> = (0..10).map(|_| scheduler.cpu_cycle()).collect();
+        assert_eq!(dots, [3, 3, 3, 3, 4, 3, 3, 3, 3, 4]);
+    }
+}
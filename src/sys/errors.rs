@@ -19,6 +19,18 @@ pub struct ErrorInvalidRange;
 pub struct ErrorOpeningROMFile;
 pub struct ErrorReadingROMFile;
 pub struct ErrorLoadingROMFile;
+pub struct ErrorReadingSaveFile;
+pub struct ErrorWritingSaveFile;
+pub struct ErrorDeviceOverlap;
+pub struct ErrorInvalidCheatCode;
+pub struct ErrorReadingCheatFile;
+pub struct ErrorWritingCheatFile;
+pub struct ErrorInvalidSearchFilter;
+pub struct ErrorInvalidPowerOnState;
+pub struct ErrorInvalidMemorySpace;
+pub struct ErrorWritingDump;
+pub struct ErrorLoadingTrainer;
+pub struct ErrorInvalidFrameCount;
 
 #[derive(Debug, PartialEq)]
 pub enum Error {
@@ -29,6 +41,23 @@ pub enum Error {
     ErrorOpeningROMFile,
     ErrorReadingROMFile,
     ErrorLoadingROMFile,
+    ErrorReadingSaveFile,
+    ErrorWritingSaveFile,
+    ErrorDeviceOverlap,
+    ErrorInvalidCheatCode,
+    ErrorReadingCheatFile,
+    ErrorWritingCheatFile,
+    ErrorInvalidSearchFilter,
+    ErrorInvalidPowerOnState,
+    ErrorInvalidMemorySpace,
+    ErrorWritingDump,
+    ErrorLoadingTrainer,
+    ErrorInvalidFrameCount,
+    UnsupportedMapper {
+        mapper: u8,
+        submapper: u8,
+        name: &'static str,
+    },
 }
 
 impl std::fmt::Display for Error {
@@ -45,6 +74,42 @@ impl std::fmt::Display for Error {
             Error::ErrorLoadingROMFile => {
                 write!(f, "Error loading ROM file into PRG ROM memory section..")
             }
+            Error::ErrorReadingSaveFile => write!(f, "Error when trying to read save file."),
+            Error::ErrorWritingSaveFile => write!(f, "Error when trying to write save file."),
+            Error::ErrorDeviceOverlap => {
+                write!(
+                    f,
+                    "Device address range is empty or overlaps another device."
+                )
+            }
+            Error::ErrorInvalidCheatCode => write!(f, "Cheat code is not valid."),
+            Error::ErrorReadingCheatFile => write!(f, "Error when trying to read cheat file."),
+            Error::ErrorWritingCheatFile => write!(f, "Error when trying to write cheat file."),
+            Error::ErrorInvalidSearchFilter => write!(f, "RAM search filter is not valid."),
+            Error::ErrorInvalidPowerOnState => {
+                write!(
+                    f,
+                    "Power-on state must be zeros, ff, pattern or random[:seed]."
+                )
+            }
+            Error::ErrorInvalidMemorySpace => write!(f, "Unknown memory space."),
+            Error::ErrorWritingDump => write!(f, "Error when trying to write memory dump."),
+            Error::ErrorInvalidFrameCount => write!(f, "Frame count must be a whole number."),
+            Error::ErrorLoadingTrainer => {
+                write!(
+                    f,
+                    "Error when trying to load trainer, the cartridge has no PRG RAM."
+                )
+            }
+            Error::UnsupportedMapper {
+                mapper,
+                submapper,
+                name,
+            } => write!(
+                f,
+                "Mapper {}.{} ({}) is not supported.",
+                mapper, submapper, name
+            ),
         }
     }
 }
@@ -1,9 +1,11 @@
 use std::{
     ops::{Deref, DerefMut},
     path::Path,
+    sync::Arc,
 };
 
 use crate::{
+    mapper::PRG_RAM_SIZE,
     memory::{Bus, BusInterface},
     sys::errors::Error,
 };
@@ -19,6 +21,11 @@ pub trait ROMFs<'a>: Sized {
     where
         Self: Sized;
     fn write_rom_memory<B>(&self, bus: &mut B) -> Result<(), Error>
+    where
+        B: BusInterface,
+        Self: Sized;
+    // Copies the trainer to $7000, after the save file is loaded so the save doesn't cover it
+    fn write_trainer<B>(&self, bus: &mut B) -> Result<(), Error>
     where
         B: BusInterface,
         Self: Sized;
@@ -30,27 +37,43 @@ pub trait ROMFs<'a>: Sized {
     fn size(&self) -> usize;
 }
 
-#[derive(Debug)]
+// Cartridge ROM loaded once and shared by every emulator instance running it, each instance
+// only allocates its own RAM and mapper state
+pub type RomData = Arc<[u8]>;
+
+#[derive(Debug, Clone)]
 pub struct INes {
-    pub prg_rom: Vec<u8>,
-    pub chr_rom: Vec<u8>,
+    pub prg_rom: RomData,
+    pub chr_rom: RomData,
     pub trainer: usize,
+    pub trainer_data: Vec<u8>, // Copied to $7000 at power-on
     pub prg_size: usize,
     pub chr_size: usize,
     pub mapper: u8,
+    pub submapper: u8,
     pub mirroring: MirroringType,
+    pub battery: bool,
+    pub prg_ram_size: usize,   // Volatile work RAM at $6000-$7FFF
+    pub prg_nvram_size: usize, // Battery backed part of it
+    pub region: Region,
 }
 
 impl Default for INes {
     fn default() -> Self {
         INes {
-            prg_rom: Vec::new(),
-            chr_rom: Vec::new(),
+            prg_rom: RomData::default(),
+            chr_rom: RomData::default(),
             trainer: 0,
+            trainer_data: Vec::new(),
             prg_size: 0,
             chr_size: 0,
             mapper: 0,
+            submapper: 0,
             mirroring: MirroringType::Horizontal,
+            battery: false,
+            prg_ram_size: PRG_RAM_SIZE,
+            prg_nvram_size: 0,
+            region: Region::Ntsc,
         }
     }
 }
@@ -61,6 +84,57 @@ pub enum MirroringType {
     Horizontal,
     Vertical,
     FourScreen,
+    SingleScreenLower,
+    SingleScreenUpper,
+}
+
+impl MirroringType {
+    // CIRAM page (1 KiB) backing a nametable address in $2000-$2FFF
+    pub fn ciram_page(&self, addr: u16) -> u8 {
+        let table = ((addr >> 10) & 0x03) as u8;
+
+        match self {
+            MirroringType::Horizontal => table >> 1,
+            MirroringType::Vertical => table & 0x01,
+            MirroringType::FourScreen => table,
+            MirroringType::SingleScreenLower => 0,
+            MirroringType::SingleScreenUpper => 1,
+        }
+    }
+}
+
+// Console timing. NTSC divides a 21.477 MHz master clock by 12 for the CPU and by 4 for the
+// PPU, PAL divides 26.602 MHz by 16 and 5, so a PAL CPU cycle lasts 3.2 dots
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Ntsc,
+    Pal,
+}
+
+impl Region {
+    // Master clocks per CPU cycle
+    pub fn cpu_divider(&self) -> u64 {
+        match self {
+            Region::Ntsc => 12,
+            Region::Pal => 16,
+        }
+    }
+
+    // Master clocks per PPU dot
+    pub fn ppu_divider(&self) -> u64 {
+        match self {
+            Region::Ntsc => 4,
+            Region::Pal => 5,
+        }
+    }
+
+    // Scanlines per frame, vblank through the pre-render line
+    pub fn scanlines(&self) -> u16 {
+        match self {
+            Region::Ntsc => 262,
+            Region::Pal => 312,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -122,13 +196,18 @@ impl<T: for<'a> ROMFs<'a>> ROMFile<T> {
         info!("Writing ROM to path: {:?}", self.path().as_ref());
         Ok(self.rom.write_rom_memory(bus)?)
     }
+
+    pub fn write_trainer(&self, bus: &mut Bus) -> Result<(), Error> {
+        self.rom.write_trainer(bus)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{
-        memory::{MIRRORED_PRG_SIZE, PPU_SIZE, PRG_SIZE, RAM_SIZE},
+        memory::{MIRRORED_PRG_SIZE, PRG_SIZE, RAM_SIZE},
+        scheduler::SyncMode,
         sys::rom_file::{DEFAULT_NES_ROM_HEADER, ROM},
     };
     use std::{
@@ -258,8 +337,8 @@ mod tests {
         content.extend_from_slice(&chr);
 
         let ines = ROM::parse_ines(&content).unwrap();
-        assert_eq!(ines.prg_rom, prg);
-        assert_eq!(ines.chr_rom, chr);
+        assert_eq!(ines.prg_rom[..], prg[..]);
+        assert_eq!(ines.chr_rom[..], chr[..]);
         assert_eq!(ines.prg_size, 16 * 1024);
         assert_eq!(ines.chr_size, 8 * 1024);
     }
@@ -284,8 +363,8 @@ mod tests {
 
         let rom = ROM::new(&file_path).unwrap();
         assert_eq!(rom.content.len(), 16 + 16 * 1024 + 8 * 1024);
-        assert_eq!(rom.format.prg_rom, prg);
-        assert_eq!(rom.format.chr_rom, chr);
+        assert_eq!(rom.format.prg_rom[..], prg[..]);
+        assert_eq!(rom.format.chr_rom[..], chr[..]);
     }
 
     #[test]
@@ -347,6 +426,7 @@ mod tests {
 
         let ines = ROM::parse_ines(&content).unwrap();
         assert_eq!(ines.trainer, 512);
+        assert_eq!(ines.trainer_data, vec![0xFF; 512]);
         assert_eq!(ines.prg_rom.len(), prg_size);
         assert_eq!(ines.chr_rom.len(), chr_size);
     }
@@ -396,25 +476,205 @@ mod tests {
         assert_eq!(ines.mapper, expected_mapper as u8);
     }
 
+    #[test]
+    fn test_parse_ines_unrom512_one_screen_and_battery() {
+        // Mapper 30 with only the four-screen bit set is the one-screen board
+        let header = dummy_header(2, 0, 0xEA, 0x10);
+        let mut content = Vec::from(header);
+        content.extend(vec![0; 2 * 16 * 1024]);
+        let ines = ROM::parse_ines(&content).unwrap();
+        assert_eq!(ines.mapper, 30);
+        assert_eq!(ines.mirroring, MirroringType::SingleScreenLower);
+        assert!(ines.battery);
+
+        let header = dummy_header(2, 0, 0xE9, 0x10);
+        let mut content = Vec::from(header);
+        content.extend(vec![0; 2 * 16 * 1024]);
+        let ines = ROM::parse_ines(&content).unwrap();
+        assert_eq!(ines.mirroring, MirroringType::FourScreen);
+        assert!(!ines.battery);
+    }
+
+    #[test]
+    fn test_parse_ines_nes2_submapper() {
+        let mut header = dummy_header(8, 1, 0x50, 0x18);
+        header[8] = 0x20;
+        let mut content = Vec::from(header);
+        content.extend(vec![0; 8 * 16 * 1024 + 8 * 1024]);
+        let ines = ROM::parse_ines(&content).unwrap();
+        assert_eq!(ines.mapper, 21);
+        assert_eq!(ines.submapper, 2);
+
+        // Plain iNES headers never carry a submapper
+        let mut header = dummy_header(8, 1, 0x50, 0x10);
+        header[8] = 0x20;
+        let mut content = Vec::from(header);
+        content.extend(vec![0; 8 * 16 * 1024 + 8 * 1024]);
+        let ines = ROM::parse_ines(&content).unwrap();
+        assert_eq!(ines.submapper, 0);
+    }
+
+    #[test]
+    fn test_parse_ines_nes2_rom_sizes() {
+        // 8 MiB of PRG ($200 16 KiB banks) and 24 KiB of CHR
+        let mut header = dummy_header(0, 3, 0x00, 0x08);
+        header[9] = 0x02;
+        let mut content = Vec::from(header);
+        content.extend(vec![0; 512 * 16 * 1024 + 24 * 1024]);
+        let ines = ROM::parse_ines(&content).unwrap();
+        assert_eq!(ines.prg_size, 8 * 1024 * 1024);
+        assert_eq!(ines.prg_rom.len(), 8 * 1024 * 1024);
+        assert_eq!(ines.chr_size, 24 * 1024);
+
+        // Exponent-multiplier notation: 2^13 * 3 = 24 KiB of PRG
+        let mut header = dummy_header((13 << 2) | 0x01, 0, 0x00, 0x08);
+        header[9] = 0x0F;
+        let mut content = Vec::from(header);
+        content.extend(vec![0; 24 * 1024]);
+        let ines = ROM::parse_ines(&content).unwrap();
+        assert_eq!(ines.prg_size, 24 * 1024);
+
+        // Sizes past the address space are an error, not an overflow
+        let mut header = dummy_header((63 << 2) | 0x03, 0, 0x00, 0x08);
+        header[9] = 0x0F;
+        assert!(ROM::parse_ines(&header).is_err());
+        let mut header = dummy_header(63 << 2, 63 << 2, 0x00, 0x08);
+        header[9] = 0xFF;
+        assert!(ROM::parse_ines(&header).is_err());
+    }
+
+    #[test]
+    fn test_parse_ines_region() {
+        let mut header = dummy_header(1, 0, 0x00, 0x08);
+        header[12] = 0x01;
+        let mut content = Vec::from(header);
+        content.extend(vec![0; 16 * 1024]);
+        assert_eq!(ROM::parse_ines(&content).unwrap().region, Region::Pal);
+
+        header[12] = 0x02;
+        let mut content = Vec::from(header);
+        content.extend(vec![0; 16 * 1024]);
+        assert_eq!(ROM::parse_ines(&content).unwrap().region, Region::Ntsc);
+    }
+
+    #[test]
+    fn test_parse_ines_prg_ram_sizes() {
+        let mut content = Vec::from(dummy_header(1, 0, 0x02, 0x00));
+        content.extend(vec![0; 16 * 1024]);
+        let ines = ROM::parse_ines(&content).unwrap();
+        assert_eq!((ines.prg_ram_size, ines.prg_nvram_size), (0, 8 * 1024));
+
+        // NES 2.0: 64 << 7 bytes of work RAM, 64 << 9 bytes battery backed
+        let mut header = dummy_header(1, 0, 0x02, 0x08);
+        header[10] = 0x97;
+        let mut content = Vec::from(header);
+        content.extend(vec![0; 16 * 1024]);
+        let ines = ROM::parse_ines(&content).unwrap();
+        assert_eq!(
+            (ines.prg_ram_size, ines.prg_nvram_size),
+            (8 * 1024, 32 * 1024)
+        );
+    }
+
+    #[test]
+    fn test_mapperless_board_prg_ram() {
+        let mut content = Vec::from(dummy_header(1, 0, 0x02, 0x00));
+        content.extend(vec![0; 16 * 1024]);
+        let rom = ROM {
+            format: ROM::parse_ines(&content).unwrap(),
+            rom_path: Path::new("dummy.nes"),
+            content,
+        };
+        let mut bus = Bus::new(RomData::default());
+        rom.write_rom_memory(&mut bus).unwrap();
+
+        bus.write(0x6010, 0x5A);
+        assert_eq!(bus.read(0x6010), 0x5A);
+        assert_eq!(bus.flush_battery_ram().unwrap()[0x10], 0x5A);
+        assert_eq!(bus.flush_battery_ram(), None);
+    }
+
+    #[test]
+    fn test_trainer_copied_to_prg_ram() {
+        let mut trainer: Vec<u8> = (0..=255).collect();
+        trainer.extend(0..=255u8);
+        for mapper in [0u8, 21] {
+            let mut content = Vec::from(dummy_header(8, 0, (mapper << 4) | 0x04, mapper & 0xF0));
+            content.extend(&trainer);
+            content.extend(vec![0; 8 * 16 * 1024]);
+            let rom = ROM {
+                format: ROM::parse_ines(&content).unwrap(),
+                rom_path: Path::new("dummy.nes"),
+                content,
+            };
+            let mut bus = Bus::new(RomData::default());
+            rom.write_rom_memory(&mut bus).unwrap();
+            rom.write_trainer(&mut bus).unwrap();
+
+            assert_eq!(bus.read(0x7000), 0x00, "mapper {mapper}");
+            assert_eq!(bus.read(0x7080), 0x80, "mapper {mapper}");
+            assert_eq!(bus.read(0x71FF), 0xFF, "mapper {mapper}");
+            assert_eq!(bus.battery_ram(), None, "mapper {mapper}");
+        }
+    }
+
+    #[test]
+    fn test_trainer_stays_on_top_of_the_save_file() {
+        use crate::sys::save::{self, SaveFile, write_save};
+
+        let mut content = Vec::from(dummy_header(2, 0, 0x06, 0x00)); // NROM, battery, trainer
+        content.extend([0xAB; 512]);
+        content.extend(vec![0; 2 * 16 * 1024]);
+        let rom = ROM {
+            format: ROM::parse_ines(&content).unwrap(),
+            rom_path: Path::new("dummy.nes"),
+            content,
+        };
+        let dir = tempdir().unwrap();
+        let save_path = dir.path().join("dummy.sav");
+        write_save(&save_path, &[0x5A; 0x2000]).unwrap();
+
+        let mut bus = Bus::new(RomData::default());
+        rom.write_rom_memory(&mut bus).unwrap();
+        let save_file = SaveFile::new(save_path, save::FLUSH_INTERVAL);
+        assert!(save_file.load(&mut bus).unwrap());
+        rom.write_trainer(&mut bus).unwrap();
+
+        assert_eq!(bus.read(0x6FFF), 0x5A);
+        assert_eq!(bus.read(0x7000), 0xAB);
+        assert_eq!(bus.read(0x71FF), 0xAB);
+        assert_eq!(bus.read(0x7200), 0x5A);
+    }
+
     struct DummyBus {
         pub loaded: bool,
         pub last_data: Vec<u8>,
     }
 
     impl BusInterface for DummyBus {
-        fn new(_rom: &[u8]) -> Self {
+        fn new(_prg_rom: RomData) -> Self {
             Self {
                 loaded: false,
                 last_data: vec![],
             }
         }
-        fn load_prg_rom(&mut self, data: &[u8]) -> Result<usize, Error> {
+        fn load_prg_rom(&mut self, data: RomData) -> Result<usize, Error> {
             self.loaded = true;
             self.last_data = data.to_vec();
 
             Ok(data.len())
         }
 
+        fn load_prg_ram(&mut self, _prg_ram: crate::mapper::prg_ram::PrgRam) {}
+
+        fn load_trainer(&mut self, _trainer: &[u8]) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn insert_cartridge(&mut self, _cartridge: Box<dyn crate::mapper::Mapper>) {
+            self.loaded = true;
+        }
+
         fn resolve_prg_rom_index(&self, _addr: u16) -> usize {
             todo!()
         }
@@ -423,14 +683,18 @@ mod tests {
             todo!()
         }
 
-        fn read(&self, _addr: u16) -> u8 {
+        fn read(&mut self, _addr: u16) -> u8 {
             todo!()
         }
+
+        fn peek(&self, _addr: u16) -> u8 {
+            0
+        }
     }
 
     impl Default for DummyBus {
         fn default() -> Self {
-            Self::new(&[0])
+            Self::new(RomData::default())
         }
     }
 
@@ -442,7 +706,7 @@ mod tests {
         let rom_data = create_temp_rom_file(&content, ".nes");
         let tempfile = rom_data.path();
         let rom = ROM::new(&tempfile).unwrap();
-        let mut bus = DummyBus::new(&content);
+        let mut bus = DummyBus::new(content.clone().into());
 
         // Actually test
         let result = rom.write_rom_memory(&mut bus);
@@ -452,15 +716,18 @@ mod tests {
     #[test]
     fn test_bus_new_initializes_memory() {
         let prg_data = vec![1, 2, 3, 4];
-        let bus = Bus::new(&prg_data);
+        let bus = Bus::new(prg_data.clone().into());
         assert_eq!(bus.ram, [0; RAM_SIZE]);
-        assert_eq!(bus.ppu, [0; PPU_SIZE]);
-        assert_eq!(bus.prg_rom, prg_data);
+        assert_eq!(
+            bus.ppu.registers,
+            crate::ppu::registers::PpuRegisters::default()
+        );
+        assert_eq!(bus.prg_rom[..], prg_data[..]);
     }
 
     #[test]
     fn test_write_and_read_ram() {
-        let mut bus = Bus::new(&[]);
+        let mut bus = Bus::new(RomData::default());
         bus.write(0x0002, 0xAB);
         assert_eq!(bus.read(0x0002), 0xAB);
         // Test RAM mirroring
@@ -470,25 +737,32 @@ mod tests {
 
     #[test]
     fn test_write_and_read_ppu() {
-        let mut bus = Bus::new(&[]);
-        bus.write(0x2003, 0x55);
-        assert_eq!(bus.read(0x2003), 0x55);
+        let mut bus = Bus::new(RomData::default());
+        bus.write(0x2003, 0x10);
+        bus.write(0x2004, 0x55);
         // Test PPU mirroring
-        bus.write(0x200B, 0x77);
+        bus.write(0x200B, 0x10);
+        assert_eq!(bus.read(0x3FFC), 0x55);
+        assert_eq!(bus.ppu.registers.oam_addr, 0x10);
+
+        // Write-only ports read back the PPU's I/O bus
+        bus.write(0x2000, 0x77);
         assert_eq!(bus.read(0x2003), 0x77);
+        assert_eq!(bus.read(0x2002) & 0x1F, 0x17);
     }
 
     #[test]
-    #[should_panic(expected = "Attempted to write to PRG ROM")]
-    fn test_write_to_prg_rom_panics() {
-        let mut bus = Bus::new(&[0; PRG_SIZE]);
+    fn test_write_to_prg_rom_is_ignored() {
+        let mut bus = Bus::new(vec![0; PRG_SIZE].into());
         bus.write(0x8000, 0xFF);
+        assert_eq!(bus.read(0x8000), 0x00);
+        bus.write(0x5000, 0xFF);
     }
 
     #[test]
     fn test_resolve_prg_rom_index_mirrored() {
         let prg_data = vec![0xAA; MIRRORED_PRG_SIZE];
-        let bus = Bus::new(&prg_data);
+        let bus = Bus::new(prg_data.clone().into());
         // Should wrap around for 16 KiB ROMs
         let idx = bus.resolve_prg_rom_index(0xC000);
 
@@ -499,33 +773,494 @@ mod tests {
 
     #[test]
     fn test_load_prg_rom_success() {
-        let mut bus = Bus::new(&[]);
+        let mut bus = Bus::new(RomData::default());
         let data = vec![0x11; PRG_SIZE];
-        let result = bus.load_prg_rom(&data);
+        let result = bus.load_prg_rom(data.clone().into());
         assert!(result.is_ok());
-        assert_eq!(bus.prg_rom, data);
+        assert_eq!(bus.prg_rom[..], data[..]);
     }
 
     #[test]
     fn test_load_prg_rom_too_small() {
-        let mut bus = Bus::new(&[]);
-        let data = vec![];
-        let result = bus.load_prg_rom(&data);
+        let mut bus = Bus::new(RomData::default());
+        let result = bus.load_prg_rom(RomData::default());
         assert_eq!(result, Err(Error::ErrorLoadingROMFile));
     }
 
     #[test]
-    fn test_load_prg_rom_too_large() {
-        let mut bus = Bus::new(&[]);
-        let data = vec![0xFF; PRG_SIZE + 1];
-        let result = bus.load_prg_rom(&data);
-        assert_eq!(result, Err(Error::ErrorLoadingROMFile));
+    fn test_load_prg_rom_larger_than_32k() {
+        let mut bus = Bus::new(RomData::default());
+        let mut data = vec![0xFF; PRG_SIZE * 4];
+        data[0x0123] = 0x42;
+        let result = bus.load_prg_rom(data.into());
+        assert_eq!(result, Ok(PRG_SIZE * 4));
+        assert_eq!(bus.read(0x8123), 0x42);
+    }
+
+    #[test]
+    fn test_resolve_prg_rom_index_small_rom() {
+        let bus = Bus::new(vec![0; 0x2000].into());
+        assert_eq!(bus.resolve_prg_rom_index(0xE005), 0x0005);
     }
 
     #[test]
-    #[should_panic(expected = "Invalid address")]
-    fn test_read_invalid_address_panics() {
-        let bus = Bus::new(&[]);
-        bus.read(0x5000);
+    fn test_bus_routes_cartridge_space_to_mapper() {
+        let ines = INes {
+            prg_rom: crate::mapper::test_rom::banked_rom(0x2000, 16),
+            mapper: 23,
+            submapper: 1,
+            ..Default::default()
+        };
+        let mut bus = Bus::new(RomData::default());
+        bus.insert_cartridge(crate::mapper::new_mapper(&ines).unwrap().unwrap());
+
+        bus.write(0x8000, 0x04);
+        assert_eq!(bus.read(0x8000), 0x04);
+        assert_eq!(bus.read(0xE000), 0x0F);
+        bus.write(0x6000, 0x99);
+        assert_eq!(bus.read(0x6000), 0x99);
+    }
+
+    #[test]
+    fn test_unmapped_reads_return_open_bus() {
+        let mut bus = Bus::new(RomData::default());
+        bus.write(0x0000, 0x5A);
+        assert_eq!(bus.read(0x5000), 0x5A);
+        assert_eq!(bus.read(0x6000), 0x5A);
+        assert_eq!(bus.read(0x8000), 0x5A);
+        assert_eq!(bus.read(0x4000), 0x5A);
+
+        bus.ram[0x10] = 0x3C;
+        assert_eq!(bus.read(0x0010), 0x3C);
+        assert_eq!(bus.read(0x5000), 0x3C);
+        assert_eq!(bus.open_bus(), 0x3C);
+    }
+
+    #[test]
+    fn test_cartridge_unmapped_reads_return_open_bus() {
+        let mut content = dummy_header(2, 1, 0x70, 0x40).to_vec(); // Camerica (71), no PRG RAM
+        content.resize(16 + 0x8000 + 0x2000, 0xEA);
+        let ines = ROM::parse_ines(&content).unwrap();
+        let mut bus = Bus::new(RomData::default());
+        bus.insert_cartridge(crate::mapper::new_mapper(&ines).unwrap().unwrap());
+
+        bus.write(0x0000, 0x5A);
+        assert_eq!(bus.read(0x5000), 0x5A);
+        assert_eq!(bus.peek(0x6000), 0x5A);
+        assert_eq!(bus.read(0x6000), 0x5A);
+        assert_eq!(bus.read(0x8000), 0xEA);
+        assert_eq!(bus.read(0x7FFF), 0xEA);
+    }
+
+    #[test]
+    fn test_bus_accesses_advance_the_clock() {
+        let mut bus = Bus::new(RomData::default());
+        bus.read(0x0000);
+        bus.write(0x0000, 0x00);
+        assert_eq!(bus.scheduler.cpu_cycles(), 2);
+        assert_eq!(bus.ppu.dot(), 6);
+
+        // Peeking is free
+        bus.peek(0x0000);
+        assert_eq!(bus.scheduler.cpu_cycles(), 2);
+
+        bus.set_region(Region::Pal);
+        for _ in 0..5 {
+            bus.read(0x0000);
+        }
+        assert_eq!(bus.ppu.dot(), 16);
+    }
+
+    #[test]
+    fn test_catch_up_matches_lockstep() {
+        for region in [Region::Ntsc, Region::Pal] {
+            let mut lockstep = Bus::new(RomData::default());
+            let mut catch_up = Bus::new(RomData::default());
+            lockstep.set_region(region);
+            catch_up.set_region(region);
+            catch_up.set_sync_mode(SyncMode::CatchUp);
+
+            let state = |bus: &Bus| (bus.ppu.frame(), bus.ppu.scanline(), bus.ppu.dot());
+
+            // Toggle NMIs and rendering at pseudo-random times over a few frames
+            let mut seed = 0x1234_5678u32;
+            for cycle in 0..100_000 {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let (addr, value) = match (seed >> 16) % 1000 {
+                    0 => (0x2000, (seed >> 8) as u8 & 0x80),
+                    1 => (0x2001, (seed >> 8) as u8 & 0x18),
+                    2 => (0x2002, 0),
+                    _ => (0x0000, 0),
+                };
+
+                if addr == 0x2002 {
+                    assert_eq!(lockstep.read(addr), catch_up.read(addr));
+                    assert_eq!(state(&lockstep), state(&catch_up), "cycle {cycle}");
+                } else {
+                    lockstep.write(addr, value);
+                    catch_up.write(addr, value);
+                }
+                assert_eq!(lockstep.nmi_line(), catch_up.nmi_line(), "cycle {cycle}");
+            }
+
+            assert!(catch_up.scheduler.pending_dots() > 0);
+            catch_up.sync_ppu();
+            assert_eq!(state(&lockstep), state(&catch_up));
+        }
+    }
+
+    #[test]
+    fn test_bus_steps_mapper_timers_per_cpu_cycle() {
+        let ines = INes {
+            prg_rom: vec![0; 0x8000].into(),
+            mapper: 16,
+            submapper: 5,
+            ..Default::default()
+        };
+        let mut bus = Bus::new(RomData::default());
+        bus.insert_cartridge(crate::mapper::new_mapper(&ines).unwrap().unwrap());
+
+        // A counter of 3 fires on the third cycle after it is enabled
+        bus.write(0x800B, 0x03);
+        bus.write(0x800C, 0x00);
+        bus.write(0x800A, 0x01);
+        bus.read(0x0000);
+        bus.read(0x0000);
+        assert!(!bus.irq_line());
+        bus.read(0x0000);
+        assert!(bus.irq_line());
+    }
+
+    #[test]
+    fn test_peek_leaves_the_bus_alone() {
+        let mut bus = Bus::new(RomData::default());
+        bus.ram[0x10] = 0x3C;
+        bus.write(0x0000, 0x5A);
+        assert_eq!(bus.peek(0x0010), 0x3C);
+        assert_eq!(bus.peek(0x5000), 0x5A);
+        assert_eq!(bus.open_bus(), 0x5A);
+    }
+
+    #[test]
+    fn test_partial_open_bus_on_io_ports() {
+        let mut bus = Bus::new(RomData::default());
+        bus.ram[0] = 0xFF;
+        bus.read(0x0000);
+        assert_eq!(bus.read(0x4016), 0xE0);
+        assert_eq!(bus.read(0x4017), 0xE0);
+
+        // $4015 keeps bit 5 of the bus and does not update it
+        bus.ram[0] = 0x3F;
+        bus.read(0x0000);
+        assert_eq!(bus.read(0x4015), 0x20);
+        assert_eq!(bus.open_bus(), 0x3F);
+    }
+
+    #[test]
+    fn test_devices_get_accesses_with_cycles() {
+        use crate::memory::device::{DEBUG_CONSOLE_PORT, DebugConsole, Device};
+
+        #[derive(Default)]
+        struct Latch {
+            value: u8,
+            cycles: Vec<u64>,
+        }
+
+        impl Device for Latch {
+            fn read(&mut self, _addr: u16, cycle: u64) -> Option<u8> {
+                self.cycles.push(cycle);
+                Some(self.value)
+            }
+
+            fn peek(&self, _addr: u16) -> Option<u8> {
+                Some(self.value)
+            }
+
+            fn write(&mut self, _addr: u16, value: u8, cycle: u64) {
+                self.cycles.push(cycle);
+                self.value = value;
+            }
+        }
+
+        let mut bus = Bus::new(RomData::default());
+        let console = bus
+            .register_device(
+                DEBUG_CONSOLE_PORT..=DEBUG_CONSOLE_PORT,
+                DebugConsole::new(false),
+            )
+            .unwrap();
+        let latch = bus
+            .register_device(0x5000..=0x5FFF, Latch::default())
+            .unwrap();
+        assert!(matches!(
+            bus.register_device(0x4018..=0x401F, DebugConsole::new(false)),
+            Err(Error::ErrorDeviceOverlap)
+        ));
+
+        for &byte in b"hi\n" {
+            bus.write(DEBUG_CONSOLE_PORT, byte);
+        }
+        assert_eq!(bus.device::<DebugConsole>(console).unwrap().text(), "hi\n");
+        assert!(bus.device::<Latch>(console).is_none());
+
+        // Write-only ports float
+        assert_eq!(bus.read(DEBUG_CONSOLE_PORT), b'\n');
+
+        bus.write(0x5123, 0x42);
+        assert_eq!(bus.read(0x5FFF), 0x42);
+        assert_eq!(bus.peek(0x5000), 0x42);
+        assert_eq!(bus.device::<Latch>(latch).unwrap().cycles, [5, 6]);
+
+        bus.remove_device(latch);
+        bus.write(0x5123, 0x00);
+        assert_eq!(bus.read(0x5123), 0x00);
+        assert!(bus.device::<Latch>(latch).is_none());
+    }
+
+    #[test]
+    fn test_instances_share_rom_data() {
+        let mut content = dummy_header(2, 1, 0x70, 0x40).to_vec(); // Camerica (71)
+        content.resize(16 + 0x8000 + 0x2000, 0xEA);
+        let ines = ROM::parse_ines(&content).unwrap();
+
+        let buses: Vec<Bus> = (0..4)
+            .map(|_| {
+                let mut bus = Bus::new(RomData::default());
+                bus.insert_cartridge(crate::mapper::new_mapper(&ines).unwrap().unwrap());
+                bus
+            })
+            .collect();
+        assert_eq!(RomData::strong_count(&ines.prg_rom), 5);
+        assert_eq!(RomData::strong_count(&ines.chr_rom), 5);
+
+        drop(buses);
+        assert_eq!(RomData::strong_count(&ines.prg_rom), 1);
+
+        // Boards without a mapper too
+        let mut bus = Bus::new(RomData::default());
+        bus.load_prg_rom(ines.prg_rom.clone()).unwrap();
+        assert!(RomData::ptr_eq(&bus.prg_rom, &ines.prg_rom));
+        let bus = Bus::new(ines.prg_rom.clone());
+        assert!(RomData::ptr_eq(&bus.prg_rom, &ines.prg_rom));
+    }
+
+    #[test]
+    fn test_cheats_patch_reads_and_freeze_ram() {
+        use crate::cheat::Cheat;
+
+        let mut prg = vec![0xEA; 0x8000];
+        prg[0x11D9] = 0xCE;
+        let mut bus = Bus::new(prg.into());
+        bus.load_prg_ram(crate::mapper::prg_ram::PrgRam::with_size(0x2000, false));
+
+        let lives = bus
+            .cheats
+            .add(Cheat::new("Lives", "SXIOPO+0075:09").unwrap());
+        bus.cheats.add(Cheat::new("RAM", "6000:42").unwrap());
+        assert_eq!(bus.read(0x91D9), 0xAD);
+        assert_eq!(bus.peek(0x91D9), 0xAD);
+        assert_eq!(bus.open_bus(), 0xAD);
+
+        bus.write(0x0075, 0x01);
+        assert_eq!(bus.read(0x0075), 0x01);
+        bus.apply_cheats();
+        assert_eq!(bus.read(0x0075), 0x09);
+        assert_eq!(bus.read(0x6000), 0x42);
+
+        bus.cheats.set_enabled(lives, false);
+        assert_eq!(bus.read(0x91D9), 0xCE);
+    }
+
+    #[test]
+    fn test_cheats_patch_peek_and_read_alike() {
+        use crate::cheat::Cheat;
+
+        let mut bus = Bus::new(vec![0xEA; 0x8000].into());
+        bus.cheats
+            .add(Cheat::new("Status", "2002?00:80+0010?00:42+C000:60").unwrap());
+        for addr in [0x2002, 0x0010, 0xC000] {
+            let peeked = bus.peek(addr);
+            assert_eq!(bus.read(addr), peeked, "${addr:04X}");
+        }
+        assert_eq!(bus.peek(0x2002), 0x80);
+        assert_eq!(bus.peek(0x0010), 0x42);
+    }
+
+    #[test]
+    fn test_run_frame_stops_at_vblank() {
+        use crate::cheat::Cheat;
+
+        for sync_mode in [SyncMode::Lockstep, SyncMode::CatchUp] {
+            let mut bus = Bus::new(RomData::default());
+            bus.set_sync_mode(sync_mode);
+            bus.cheats.add(Cheat::new("Lives", "0075:09").unwrap());
+
+            bus.run_frame();
+            assert!(bus.ppu.vblank());
+            assert_eq!(
+                (bus.ppu.scanline(), bus.ppu.dot()),
+                (crate::ppu::VBLANK_SCANLINE, 1)
+            );
+            assert_eq!(bus.ram[0x75], 0x09);
+
+            bus.ram[0x75] = 0x00;
+            let cycles = bus.scheduler.cpu_cycles();
+            bus.run_frame();
+            assert_eq!(bus.ram[0x75], 0x09);
+            assert_eq!(bus.ppu.frame(), 1);
+            assert!((29780..=29781).contains(&(bus.scheduler.cpu_cycles() - cycles)));
+        }
+    }
+
+    #[test]
+    fn test_power_on_state_fills_every_ram() {
+        use crate::memory::power_on::{PowerOnMemory, PowerOnState};
+
+        let ines = INes {
+            prg_rom: vec![0; 0x8000].into(),
+            mapper: 69,
+            ..Default::default()
+        };
+        let power_on = |state: PowerOnState| {
+            let mut bus = Bus::new(RomData::default());
+            bus.set_power_on_state(state);
+            bus.insert_cartridge(crate::mapper::new_mapper(&ines).unwrap().unwrap());
+            bus.load_trainer(&[0xAB; 512]).unwrap();
+            bus
+        };
+
+        let state = PowerOnState::Random(42);
+        let bus = power_on(state);
+        let mut expected = [0; RAM_SIZE];
+        state.fill(&mut expected, PowerOnMemory::CpuRam);
+        assert_eq!(bus.ram, expected);
+        assert!(bus.ppu.palette.iter().all(|&entry| entry < 0x40));
+        assert_eq!(bus.power_on_state().seed(), Some(42));
+
+        // Same seed, same console
+        let again = power_on(state);
+        let cartridge = |bus: &Bus| {
+            let chr = bus.cartridge.as_ref().unwrap().chr().unwrap().to_vec();
+            (chr, bus.cartridge_ram().unwrap().data().to_vec())
+        };
+        assert_eq!(cartridge(&bus), cartridge(&again));
+        assert_eq!(bus.ppu.oam, again.ppu.oam);
+
+        // The trainer goes on top
+        assert_eq!(bus.cartridge_ram().unwrap().read(0x7000), Some(0xAB));
+
+        let bus = power_on(PowerOnState::Pattern);
+        assert_eq!(bus.ram[..8], [0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(cartridge(&bus).0[4..8], [0xFF; 4]);
+        assert_eq!(cartridge(&bus).1[0..4], [0; 4]);
+    }
+
+    #[test]
+    fn test_ppudata_goes_through_the_cartridge() {
+        let mut content = dummy_header(2, 0, 0x71, 0x40).to_vec(); // Camerica (71), vertical
+        content.resize(16 + 0x8000, 0xEA);
+        let ines = ROM::parse_ines(&content).unwrap();
+        let mut bus = Bus::new(RomData::default());
+        bus.insert_cartridge(crate::mapper::new_mapper(&ines).unwrap().unwrap());
+
+        let set_address = |bus: &mut Bus, addr: u16| {
+            bus.write(0x2006, (addr >> 8) as u8);
+            bus.write(0x2006, addr as u8);
+        };
+        set_address(&mut bus, 0x0010);
+        bus.write(0x2007, 0x5A);
+        set_address(&mut bus, 0x2400);
+        bus.write(0x2007, 0xC3);
+        assert_eq!(bus.cartridge.as_ref().unwrap().chr().unwrap()[0x10], 0x5A);
+        assert_eq!(bus.ppu.ciram[0x0400], 0xC3);
+
+        // Reads come out one access late, through the buffer
+        set_address(&mut bus, 0x0010);
+        bus.read(0x2007);
+        assert_eq!(bus.read(0x2007), 0x5A);
+        set_address(&mut bus, 0x2C00);
+        assert_eq!(bus.peek(0x2007), 0x00);
+        bus.read(0x2007);
+        assert_eq!(bus.read(0x2007), 0xC3);
+    }
+
+    #[test]
+    fn test_trainer_needs_prg_ram() {
+        let mut content = dummy_header(2, 1, 0x74, 0x40).to_vec(); // Camerica (71), trainer
+        content.resize(16 + 512 + 0x8000 + 0x2000, 0xEA);
+        let ines = ROM::parse_ines(&content).unwrap();
+        let mut bus = Bus::new(RomData::default());
+        bus.insert_cartridge(crate::mapper::new_mapper(&ines).unwrap().unwrap());
+        assert_eq!(
+            bus.load_trainer(&ines.trainer_data),
+            Err(Error::ErrorLoadingTrainer)
+        );
+    }
+
+    #[test]
+    fn test_console_reset_reaches_the_cartridge() {
+        use crate::mapper::test_rom::test_rom;
+
+        let ines = test_rom(60).prg(0x4000, 4).chr(0x2000, 4).build();
+        let mut bus = Bus::new(RomData::default());
+        bus.insert_cartridge(crate::mapper::new_mapper(&ines).unwrap().unwrap());
+        let mut cpu = crate::cpu::Cpu::new();
+
+        // The reset-based 4-in-1 steps to the next game
+        assert_eq!(bus.read(0x8000), 0);
+        cpu.reset_console(&mut bus);
+        assert_eq!(bus.read(0x8000), 1);
+        assert_eq!(cpu.sp, 0xFF);
+    }
+
+    #[test]
+    fn test_ppu_fetches_reach_the_cartridge_in_both_sync_modes() {
+        use crate::mapper::test_rom::test_rom;
+
+        let ines = test_rom(9).prg(0x2000, 16).chr(0x1000, 32).build();
+        for sync_mode in [SyncMode::Lockstep, SyncMode::CatchUp] {
+            let mut bus = Bus::new(RomData::default());
+            bus.insert_cartridge(crate::mapper::new_mapper(&ines).unwrap().unwrap());
+            bus.set_sync_mode(sync_mode);
+            bus.write(0xB000, 1); // Left table, $FD latch
+            bus.write(0xC000, 2); // Left table, $FE latch
+
+            // Tile $FD on the second row, then the background on
+            for value in [0x20, 0x25] {
+                bus.write(0x2006, value);
+            }
+            bus.write(0x2007, 0xFD);
+            for value in [0x00, 0x00] {
+                bus.write(0x2006, value);
+            }
+            bus.write(0x2001, 0x08);
+
+            let cartridge = |bus: &Bus| bus.cartridge.as_ref().unwrap().ppu_read(0x0000);
+            assert_eq!(cartridge(&bus), 2);
+            bus.run_frame();
+            bus.run_frame();
+            assert_eq!(cartridge(&bus), 1, "{sync_mode:?}");
+        }
+    }
+
+    #[test]
+    fn test_ppudata_reads_nrom_chr() {
+        let mut content = dummy_header(1, 1, 0x00, 0x00).to_vec();
+        content.resize(16 + 0x4000, 0xEA);
+        content.extend((0..0x2000).map(|i| (i >> 4) as u8));
+        let rom = ROM {
+            format: ROM::parse_ines(&content).unwrap(),
+            rom_path: Path::new("dummy.nes"),
+            content,
+        };
+        let mut bus = Bus::new(RomData::default());
+        rom.write_rom_memory(&mut bus).unwrap();
+
+        bus.write(0x2006, 0x12);
+        bus.write(0x2006, 0x34);
+        bus.read(0x2007); // Fills the buffer
+        assert_eq!(bus.read(0x2007), 0x23);
+        assert_eq!(bus.read(0x2007), 0x23);
+        assert_eq!(bus.read(0xC000), 0xEA);
     }
 }
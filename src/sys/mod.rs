@@ -1,3 +1,4 @@
 pub mod errors;
 pub mod interfaces;
 pub mod rom_file;
+pub mod save;
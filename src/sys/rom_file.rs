@@ -3,6 +3,7 @@ use std::io::Read;
 use std::path::Path;
 
 use crate::{
+    mapper::{PRG_RAM_SIZE, new_mapper, prg_ram::PrgRam, registry::board_name},
     memory::BusInterface,
     sys::{
         errors::{Error, Extension},
@@ -10,7 +11,7 @@ use crate::{
     },
 };
 
-use super::interfaces::{HeaderBytes, INes, MirroringType};
+use super::interfaces::{HeaderBytes, INes, MirroringType, Region, RomData};
 
 pub const DEFAULT_NES_ROM_HEADER: &[u8; 4] = b"NES\x1A"; // [N, E, S, 1A]
 
@@ -40,22 +41,49 @@ impl<'a> ROMFs<'a> for ROM<'a> {
             prg_rom,
             chr_rom,
             trainer,
+            trainer_data: _,
             prg_size,
             chr_size,
             mapper,
+            submapper,
             mirroring,
+            battery,
+            prg_ram_size,
+            prg_nvram_size,
+            region,
         } = &self.format;
 
         println!("PRG ROM Size: {}", prg_size);
         println!("CHR ROM Size: {}", chr_size);
         println!("Trainer Size: {}", trainer);
-        println!("Mapper: {}", mapper);
+        println!("Mapper: {} ({})", mapper, board_name(*mapper, *submapper));
+        println!("Submapper: {}", submapper);
         println!("Mirroring: {:?}", mirroring);
-        bus.load_prg_rom(prg_rom)?;
+        println!("Region: {:?}", region);
+        println!("Battery: {}", battery);
+        println!(
+            "PRG RAM Size: {} + {} battery backed",
+            prg_ram_size, prg_nvram_size
+        );
+
+        match new_mapper(&self.format)? {
+            Some(cartridge) => bus.insert_cartridge(cartridge),
+            None => {
+                bus.load_prg_rom(prg_rom.clone())?;
+                bus.load_prg_ram(PrgRam::new(&self.format));
+            }
+        }
 
         Ok(())
     }
 
+    fn write_trainer<B: BusInterface>(&self, bus: &mut B) -> Result<(), Error> {
+        match self.format.trainer_data.as_slice() {
+            [] => Ok(()),
+            trainer_data => bus.load_trainer(trainer_data),
+        }
+    }
+
     fn validate_file<P: AsRef<Path>>(rom_path: P) -> Result<(), Error> {
         let rom_path = Path::new(rom_path.as_ref());
 
@@ -127,44 +155,108 @@ impl ROM<'_> {
             return Err(Error::ErrorInvalidROMFile);
         }
 
-        let prg_blocks = header[4] as usize;
-        let chr_blocks = header[5] as usize;
+        // NES 2.0 headers carry the submapper in the upper nibble of byte 8
+        let is_nes2 = header[7] & 0x0C == 0x08;
+        let submapper = if is_nes2 { header[8] >> 4 } else { 0 };
 
-        let prg_size = prg_blocks * 16 * 1024;
-        let chr_size = chr_blocks * 8 * 1024;
+        // NES 2.0 adds the upper size bits in byte 9
+        let (prg_msb, chr_msb) = match is_nes2 {
+            true => (header[9] & 0x0F, header[9] >> 4),
+            false => (0, 0),
+        };
+        let prg_size = Self::rom_size(header[4], prg_msb, 16 * 1024)?;
+        let chr_size = Self::rom_size(header[5], chr_msb, 8 * 1024)?;
 
         let has_trainer = header[6] & 0b00000100 != 0;
         let trainer_size = if has_trainer { 512 } else { 0 };
 
-        let prg_start = 16 + trainer_size;
-        let prg_end = prg_start + prg_size;
+        let prg_start: usize = 16 + trainer_size;
+        let prg_end = prg_start
+            .checked_add(prg_size)
+            .ok_or(Error::ErrorInvalidFileSize)?;
         let chr_start = prg_end;
-        let chr_end = chr_start + chr_size;
+        let chr_end = chr_start
+            .checked_add(chr_size)
+            .ok_or(Error::ErrorInvalidFileSize)?;
 
         if chr_end > content.len() || prg_end > content.len() {
             return Err(Error::ErrorInvalidFileSize);
         }
 
         let trainer = if has_trainer { 512 } else { 0 };
-        let prg_rom = content[prg_start..prg_end].to_vec();
-        let chr_rom = content[chr_start..chr_end].to_vec();
+        let trainer_data = content[16..prg_start].to_vec();
+        let prg_rom = RomData::from(&content[prg_start..prg_end]);
+        let chr_rom = RomData::from(&content[chr_start..chr_end]);
+
+        let mapper = ((header[7] & 0xF0) | (header[6] >> 4)) as u8;
 
-        let mirroring = match header[6] & 0b00001001 {
-            0x08 => MirroringType::FourScreen,
-            0x01 => MirroringType::Vertical,
+        // UNROM 512 reuses the four-screen bit alone for its one-screen board
+        let mirroring = match (mapper, header[6] & 0b00001001) {
+            (30, 0x08) => MirroringType::SingleScreenLower,
+            (30, 0x09) => MirroringType::FourScreen,
+            (_, 0x08) => MirroringType::FourScreen,
+            (_, 0x01) => MirroringType::Vertical,
             _ => MirroringType::Horizontal,
         };
 
-        let mapper = ((header[7] & 0xF0) | (header[6] >> 4)) as u8;
+        let battery = header[6] & 0b00000010 != 0;
+
+        // NES 2.0 gives work RAM and battery RAM as shift counts (64 << n, 0 for none), plain
+        // iNES headers a count of 8 KiB banks in byte 8 where 0 still means one bank
+        let (prg_ram_size, prg_nvram_size) = if is_nes2 {
+            (
+                Self::ram_size(header[10] & 0x0F),
+                Self::ram_size(header[10] >> 4),
+            )
+        } else {
+            let size = (header[8].max(1) as usize) * PRG_RAM_SIZE;
+            if battery { (0, size) } else { (size, 0) }
+        };
+
+        // NES 2.0 byte 12, iNES byte 9. Multi-region dumps run as NTSC
+        let pal = match is_nes2 {
+            true => header[12] & 0x03 == 0x01,
+            false => header[9] & 0x01 != 0,
+        };
+        let region = if pal { Region::Pal } else { Region::Ntsc };
 
         Ok(INes {
             prg_rom,
             chr_rom,
             trainer,
+            trainer_data,
             prg_size,
             chr_size,
             mapper,
+            submapper,
             mirroring,
+            battery,
+            prg_ram_size,
+            prg_nvram_size,
+            region,
         })
     }
+
+    fn ram_size(shift: u8) -> usize {
+        match shift {
+            0 => 0,
+            shift => 64 << shift,
+        }
+    }
+
+    // Size in bytes from the header's LSB byte and NES 2.0 MSB nibble. An MSB of $F switches
+    // to exponent-multiplier notation: LSB = EEEEEEMM, size = 2^E * (MM * 2 + 1). Sizes that
+    // don't fit in memory are rejected.
+    fn rom_size(lsb: u8, msb: u8, unit: usize) -> Result<usize, Error> {
+        if msb == 0x0F {
+            let exponent = (lsb >> 2) as u32;
+            let multiplier = (lsb & 0x03) as usize * 2 + 1;
+            return 1usize
+                .checked_shl(exponent)
+                .and_then(|size| size.checked_mul(multiplier))
+                .ok_or(Error::ErrorInvalidFileSize);
+        }
+
+        Ok((((msb as usize) << 8) | lsb as usize) * unit)
+    }
 }
@@ -0,0 +1,154 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::memory::Bus;
+use crate::sys::errors::Error;
+
+// How often battery memory that changed is written back while the game runs
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+// Battery backed cartridge memory is stored as `<rom>.sav`, next to the ROM unless a save
+// directory is given
+pub fn save_path<P: AsRef<Path>>(rom_path: P, save_dir: Option<&Path>) -> PathBuf {
+    let rom_path = rom_path.as_ref();
+
+    match (save_dir, rom_path.file_name()) {
+        (Some(dir), Some(file_name)) => dir.join(file_name).with_extension("sav"),
+        _ => rom_path.with_extension("sav"),
+    }
+}
+
+// `None` when the game was never saved
+pub fn read_save<P: AsRef<Path>>(save_path: P) -> Result<Option<Vec<u8>>, Error> {
+    match fs::read(save_path) {
+        Ok(data) => Ok(Some(data)),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+        Err(_) => Err(Error::ErrorReadingSaveFile),
+    }
+}
+
+pub fn write_save<P: AsRef<Path>>(save_path: P, data: &[u8]) -> Result<(), Error> {
+    let save_path = save_path.as_ref();
+    if let Some(dir) = save_path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|_| Error::ErrorWritingSaveFile)?;
+    }
+
+    fs::write(save_path, data).map_err(|_| Error::ErrorWritingSaveFile)
+}
+
+// Keeps the battery memory on the bus in sync with its save file: loaded at startup, flushed
+// every `flush_interval` while dirty and written once more on exit
+pub struct SaveFile {
+    path: PathBuf,
+    flush_interval: Duration,
+    last_flush: Instant,
+}
+
+impl SaveFile {
+    pub fn new(path: PathBuf, flush_interval: Duration) -> Self {
+        SaveFile {
+            path,
+            flush_interval,
+            last_flush: Instant::now(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // `false` when there was no save file yet
+    pub fn load(&self, bus: &mut Bus) -> Result<bool, Error> {
+        match read_save(&self.path)? {
+            Some(data) => {
+                bus.load_battery_ram(&data);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    // Call once per frame, `true` when the file was written
+    pub fn flush(&mut self, bus: &mut Bus) -> Result<bool, Error> {
+        if self.last_flush.elapsed() < self.flush_interval {
+            return Ok(false);
+        }
+        self.last_flush = Instant::now();
+
+        match bus.flush_battery_ram() {
+            Some(data) => write_save(&self.path, &data).map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    pub fn save(&self, bus: &Bus) -> Result<(), Error> {
+        match bus.battery_ram() {
+            Some(data) => write_save(&self.path, &data),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::prg_ram::PrgRam;
+    use crate::memory::BusInterface;
+    use tempfile::tempdir;
+
+    fn battery_bus() -> Bus {
+        let mut bus = Bus::new(vec![0; 0x4000].into());
+        bus.load_prg_ram(PrgRam::with_size(0x2000, true));
+        bus
+    }
+
+    #[test]
+    fn test_save_path_and_directory() {
+        assert_eq!(
+            save_path("roms/game.nes", None),
+            PathBuf::from("roms/game.sav")
+        );
+        assert_eq!(
+            save_path("roms/game.nes", Some(Path::new("/tmp/saves"))),
+            PathBuf::from("/tmp/saves/game.sav")
+        );
+    }
+
+    #[test]
+    fn test_round_trip_through_save_dir() {
+        let dir = tempdir().unwrap();
+        let path = save_path("roms/game.nes", Some(&dir.path().join("saves")));
+        let save = SaveFile::new(path, FLUSH_INTERVAL);
+
+        let mut bus = battery_bus();
+        assert!(!save.load(&mut bus).unwrap());
+        bus.write(0x6123, 0x42);
+        save.save(&bus).unwrap();
+
+        let mut bus = battery_bus();
+        assert!(save.load(&mut bus).unwrap());
+        assert_eq!(bus.read(0x6123), 0x42);
+    }
+
+    #[test]
+    fn test_flush_only_when_dirty() {
+        let dir = tempdir().unwrap();
+        let mut save = SaveFile::new(dir.path().join("game.sav"), Duration::ZERO);
+        let mut bus = battery_bus();
+
+        assert!(!save.flush(&mut bus).unwrap());
+        assert!(!save.path().exists());
+
+        bus.write(0x7FFF, 0x99);
+        assert!(save.flush(&mut bus).unwrap());
+        assert_eq!(read_save(save.path()).unwrap().unwrap()[0x1FFF], 0x99);
+        assert!(!save.flush(&mut bus).unwrap());
+
+        // Not yet due
+        let mut save = SaveFile::new(dir.path().join("game.sav"), FLUSH_INTERVAL);
+        bus.write(0x7FFF, 0x11);
+        assert!(!save.flush(&mut bus).unwrap());
+    }
+}